@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Eq, PartialEq, Debug)]
 pub enum Ast {
     Int(i32),
@@ -18,6 +20,28 @@ pub fn mul(e1: Box<Ast>, e2: Box<Ast>) -> Box<Ast> {
     Box::new(Ast::Mul(e1, e2))
 }
 
+// Sums and products with the usual precedence, `10 * (1 + 5)`.
+impl fmt::Display for Ast {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ast::Int(i) => write!(f, "{}", i),
+            Ast::Add(e1, e2) => write!(f, "{} + {}", e1, e2),
+            Ast::Mul(e1, e2) => {
+                for (i, e) in [e1, e2].into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " * ")?;
+                    }
+                    match **e {
+                        Ast::Add(_, _) => write!(f, "({})", e)?,
+                        _ => write!(f, "{}", e)?,
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -36,4 +60,10 @@ mod tests {
     fn make_mul() {
         assert_eq!(*mul(int(1), int(2)), Ast::Mul(int(1), int(2)));
     }
+
+    #[test]
+    fn print() {
+        let e = add(mul(int(10), add(int(1), int(5))), mul(int(2), int(3)));
+        assert_eq!(e.to_string(), "10 * (1 + 5) + 2 * 3");
+    }
 }
@@ -0,0 +1,307 @@
+use std::rc::Rc;
+
+// Every evaluator has its own copy of the term type; this lets generated
+// terms and desugared surface programs be turned into any of them.
+pub trait Build: Sized {
+    fn int(i: i32) -> Rc<Self>;
+    fn var(idx: usize) -> Rc<Self>;
+    fn lam(body: Rc<Self>) -> Rc<Self>;
+    fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self>;
+
+    // `None` when the language is not staged.
+    fn quo(_body: Rc<Self>) -> Option<Rc<Self>> {
+        None
+    }
+
+    fn unq(_body: Rc<Self>) -> Option<Rc<Self>> {
+        None
+    }
+
+    // Reference to a top-level definition of a linked program; `None` when
+    // the language has no globals.
+    fn global(_idx: usize) -> Option<Rc<Self>> {
+        None
+    }
+}
+
+mod stlc {
+    use super::Build;
+    use crate::stlc::{
+        anf, closconv, cps_trans, eval_cps, eval_defunct1, eval_defunct2, eval_secd, eval_subst,
+        nbe,
+    };
+    use std::rc::Rc;
+
+    impl Build for eval_cps::ast::Ast {
+        fn int(i: i32) -> Rc<Self> {
+            eval_cps::ast::int(i)
+        }
+        fn var(idx: usize) -> Rc<Self> {
+            eval_cps::ast::var(idx)
+        }
+        fn lam(body: Rc<Self>) -> Rc<Self> {
+            eval_cps::ast::lam(body)
+        }
+        fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self> {
+            eval_cps::ast::app(func, arg)
+        }
+        fn global(idx: usize) -> Option<Rc<Self>> {
+            Some(eval_cps::ast::global(idx))
+        }
+    }
+
+    impl Build for eval_defunct1::ast::Ast {
+        fn int(i: i32) -> Rc<Self> {
+            eval_defunct1::ast::int(i)
+        }
+        fn var(idx: usize) -> Rc<Self> {
+            eval_defunct1::ast::var(idx)
+        }
+        fn lam(body: Rc<Self>) -> Rc<Self> {
+            eval_defunct1::ast::lam(body)
+        }
+        fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self> {
+            eval_defunct1::ast::app(func, arg)
+        }
+        fn global(idx: usize) -> Option<Rc<Self>> {
+            Some(eval_defunct1::ast::global(idx))
+        }
+    }
+
+    impl Build for eval_defunct2::data::Ast {
+        fn int(i: i32) -> Rc<Self> {
+            eval_defunct2::data::ast::int(i)
+        }
+        fn var(idx: usize) -> Rc<Self> {
+            eval_defunct2::data::ast::var(idx)
+        }
+        fn lam(body: Rc<Self>) -> Rc<Self> {
+            eval_defunct2::data::ast::lam(body)
+        }
+        fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self> {
+            eval_defunct2::data::ast::app(func, arg)
+        }
+        fn global(idx: usize) -> Option<Rc<Self>> {
+            Some(eval_defunct2::data::ast::global(idx))
+        }
+    }
+
+    impl Build for eval_secd::data::Ast {
+        fn int(i: i32) -> Rc<Self> {
+            eval_secd::data::ast::int(i)
+        }
+        fn var(idx: usize) -> Rc<Self> {
+            eval_secd::data::ast::var(idx)
+        }
+        fn lam(body: Rc<Self>) -> Rc<Self> {
+            eval_secd::data::ast::lam(body)
+        }
+        fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self> {
+            eval_secd::data::ast::app(func, arg)
+        }
+    }
+
+    impl Build for eval_subst::data::Ast {
+        fn int(i: i32) -> Rc<Self> {
+            eval_subst::data::ast::int(i)
+        }
+        fn var(idx: usize) -> Rc<Self> {
+            eval_subst::data::ast::var(idx)
+        }
+        fn lam(body: Rc<Self>) -> Rc<Self> {
+            eval_subst::data::ast::lam(body)
+        }
+        fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self> {
+            eval_subst::data::ast::app(func, arg)
+        }
+    }
+
+    impl Build for closconv::data::Ast {
+        fn int(i: i32) -> Rc<Self> {
+            closconv::data::ast::int(i)
+        }
+        fn var(idx: usize) -> Rc<Self> {
+            closconv::data::ast::var(idx)
+        }
+        fn lam(body: Rc<Self>) -> Rc<Self> {
+            closconv::data::ast::lam(body)
+        }
+        fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self> {
+            closconv::data::ast::app(func, arg)
+        }
+    }
+
+    impl Build for anf::data::Ast {
+        fn int(i: i32) -> Rc<Self> {
+            anf::data::ast::int(i)
+        }
+        fn var(idx: usize) -> Rc<Self> {
+            anf::data::ast::var(idx)
+        }
+        fn lam(body: Rc<Self>) -> Rc<Self> {
+            anf::data::ast::lam(body)
+        }
+        fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self> {
+            anf::data::ast::app(func, arg)
+        }
+    }
+
+    impl Build for cps_trans::data::Ast {
+        fn int(i: i32) -> Rc<Self> {
+            cps_trans::data::ast::int(i)
+        }
+        fn var(idx: usize) -> Rc<Self> {
+            cps_trans::data::ast::var(idx)
+        }
+        fn lam(body: Rc<Self>) -> Rc<Self> {
+            cps_trans::data::ast::lam(body)
+        }
+        fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self> {
+            cps_trans::data::ast::app(func, arg)
+        }
+    }
+
+    impl Build for nbe::data::Ast {
+        fn int(i: i32) -> Rc<Self> {
+            nbe::data::ast::int(i)
+        }
+        fn var(idx: usize) -> Rc<Self> {
+            nbe::data::ast::var(idx)
+        }
+        fn lam(body: Rc<Self>) -> Rc<Self> {
+            nbe::data::ast::lam(body)
+        }
+        fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self> {
+            nbe::data::ast::app(func, arg)
+        }
+    }
+}
+
+mod lamcirc {
+    use super::Build;
+    use crate::lamcirc::{eval_cek, eval_cps, eval_vm, eval_vm2};
+    use std::rc::Rc;
+
+    impl Build for eval_cek::data::Ast {
+        fn int(i: i32) -> Rc<Self> {
+            eval_cek::data::ast::int(i)
+        }
+        fn var(idx: usize) -> Rc<Self> {
+            eval_cek::data::ast::var(idx)
+        }
+        fn lam(body: Rc<Self>) -> Rc<Self> {
+            eval_cek::data::ast::lam(body)
+        }
+        fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self> {
+            eval_cek::data::ast::app(func, arg)
+        }
+        fn quo(body: Rc<Self>) -> Option<Rc<Self>> {
+            Some(eval_cek::data::ast::quo(body))
+        }
+        fn unq(body: Rc<Self>) -> Option<Rc<Self>> {
+            Some(eval_cek::data::ast::unq(body))
+        }
+        fn global(idx: usize) -> Option<Rc<Self>> {
+            Some(eval_cek::data::ast::global(idx))
+        }
+    }
+
+    impl Build for eval_cps::data::Ast {
+        fn int(i: i32) -> Rc<Self> {
+            eval_cps::data::ast::int(i)
+        }
+        fn var(idx: usize) -> Rc<Self> {
+            eval_cps::data::ast::var(idx)
+        }
+        fn lam(body: Rc<Self>) -> Rc<Self> {
+            eval_cps::data::ast::lam(body)
+        }
+        fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self> {
+            eval_cps::data::ast::app(func, arg)
+        }
+        fn quo(body: Rc<Self>) -> Option<Rc<Self>> {
+            Some(eval_cps::data::ast::quo(body))
+        }
+        fn unq(body: Rc<Self>) -> Option<Rc<Self>> {
+            Some(eval_cps::data::ast::unq(body))
+        }
+        fn global(idx: usize) -> Option<Rc<Self>> {
+            Some(eval_cps::data::ast::global(idx))
+        }
+    }
+
+    impl Build for eval_vm::data::Ast {
+        fn int(i: i32) -> Rc<Self> {
+            eval_vm::data::ast::int(i)
+        }
+        fn var(idx: usize) -> Rc<Self> {
+            eval_vm::data::ast::var(idx)
+        }
+        fn lam(body: Rc<Self>) -> Rc<Self> {
+            eval_vm::data::ast::lam(body)
+        }
+        fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self> {
+            eval_vm::data::ast::app(func, arg)
+        }
+        fn quo(body: Rc<Self>) -> Option<Rc<Self>> {
+            Some(eval_vm::data::ast::quo(body))
+        }
+        fn unq(body: Rc<Self>) -> Option<Rc<Self>> {
+            Some(eval_vm::data::ast::unq(body))
+        }
+        fn global(idx: usize) -> Option<Rc<Self>> {
+            Some(eval_vm::data::ast::global(idx))
+        }
+    }
+
+    impl Build for eval_vm2::data::Ast {
+        fn int(i: i32) -> Rc<Self> {
+            eval_vm2::data::ast::int(i)
+        }
+        fn var(idx: usize) -> Rc<Self> {
+            eval_vm2::data::ast::var(idx)
+        }
+        fn lam(body: Rc<Self>) -> Rc<Self> {
+            eval_vm2::data::ast::lam(body)
+        }
+        fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self> {
+            eval_vm2::data::ast::app(func, arg)
+        }
+        fn quo(body: Rc<Self>) -> Option<Rc<Self>> {
+            Some(eval_vm2::data::ast::quo(body))
+        }
+        fn unq(body: Rc<Self>) -> Option<Rc<Self>> {
+            Some(eval_vm2::data::ast::unq(body))
+        }
+        fn global(idx: usize) -> Option<Rc<Self>> {
+            Some(eval_vm2::data::ast::global(idx))
+        }
+    }
+}
+
+mod surface {
+    use super::Build;
+    use crate::surface::data::{ast, Ast};
+    use std::rc::Rc;
+
+    impl Build for Ast {
+        fn int(i: i32) -> Rc<Self> {
+            ast::int(i)
+        }
+        fn var(idx: usize) -> Rc<Self> {
+            ast::var(idx)
+        }
+        fn lam(body: Rc<Self>) -> Rc<Self> {
+            ast::lam(body)
+        }
+        fn app(func: Rc<Self>, arg: Rc<Self>) -> Rc<Self> {
+            ast::app(func, arg)
+        }
+        fn quo(body: Rc<Self>) -> Option<Rc<Self>> {
+            Some(ast::quo(body))
+        }
+        fn unq(body: Rc<Self>) -> Option<Rc<Self>> {
+            Some(ast::unq(body))
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::stats;
+use crate::stats::Kind;
 use std::rc::Rc;
 
 #[derive(Eq, PartialEq, Debug)]
@@ -7,7 +9,8 @@ pub enum Ast {
     Lam(Rc<Ast>),
     App(Rc<Ast>, Rc<Ast>),
     Quo(Rc<Ast>),
-    Unq(Rc<Ast>)
+    Unq(Rc<Ast>),
+    Global(usize) // top-level definition, looked up in the globals table
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -25,67 +28,88 @@ pub enum Env {
     Cons(Rc<Val>, Rc<Env>)
 }
 
+stats::counted!(Ast, Val, Env);
+
 pub mod ast {
     use super::*;
 
+    fn alloc(a: Ast) -> Rc<Ast> {
+        stats::alloc(Kind::Code);
+        Rc::new(a)
+    }
+
     pub fn int(i: i32) -> Rc<Ast> {
-        Rc::new(Ast::Int(i))
+        alloc(Ast::Int(i))
     }
 
     pub fn var(i: usize) -> Rc<Ast> {
-        Rc::new(Ast::Var(i))
+        alloc(Ast::Var(i))
     }
 
     pub fn lam(ast: Rc<Ast>) -> Rc<Ast> {
-        Rc::new(Ast::Lam(ast))
+        alloc(Ast::Lam(ast))
     }
 
     pub fn app(ast1: Rc<Ast>, ast2: Rc<Ast>) -> Rc<Ast> {
-        Rc::new(Ast::App(ast1, ast2))
+        alloc(Ast::App(ast1, ast2))
     }
 
     pub fn quo(ast: Rc<Ast>) -> Rc<Ast> {
-        Rc::new(Ast::Quo(ast))
+        alloc(Ast::Quo(ast))
     }
 
     pub fn unq(ast: Rc<Ast>) -> Rc<Ast> {
-        Rc::new(Ast::Unq(ast))
+        alloc(Ast::Unq(ast))
+    }
+
+    pub fn global(i: usize) -> Rc<Ast> {
+        alloc(Ast::Global(i))
     }
 }
 
 pub mod val {
     use super::*;
 
+    fn alloc(v: Val) -> Rc<Val> {
+        stats::alloc(Kind::Val);
+        Rc::new(v)
+    }
+
     pub fn error(msg: &str) -> Rc<Val> {
-        return Rc::new(Val::Error(String::from(msg)));
+        return alloc(Val::Error(String::from(msg)));
     }
 
     pub fn int(i: i32) -> Rc<Val> {
-        return Rc::new(Val::Int(i));
+        return alloc(Val::Int(i));
     }
 
     pub fn clos(env: Rc<Env>, body: Rc<Ast>) -> Rc<Val> {
-        return Rc::new(Val::Clos(env, body));
+        return alloc(Val::Clos(env, body));
     }
 
     pub fn quo(ast: Rc<Ast>) -> Rc<Val> {
-        return Rc::new(Val::Quo(ast));
+        return alloc(Val::Quo(ast));
     }
 
     pub fn fut(ast: Rc<Ast>) -> Rc<Val> {
-        return Rc::new(Val::Fut(ast));
+        return alloc(Val::Fut(ast));
     }
 }
 
 pub mod env {
     use super::*;
 
+    fn alloc(env: Env) -> Rc<Env> {
+        stats::alloc(Kind::Env);
+        Rc::new(env)
+    }
+
     pub fn empty() -> Rc<Env> {
-        return Rc::new(Env::Nil);
+        return alloc(Env::Nil);
     }
 
     pub fn cons(elm: Rc<Val>, env: Rc<Env>) -> Rc<Env> {
-        return Rc::new(Env::Cons(elm.clone(), env.clone()));
+        return alloc(Env::Cons(elm.clone(), env.clone()));
     }
 
     pub fn lookup(env: Rc<Env>, idx: usize) -> Option<Rc<Val>> {
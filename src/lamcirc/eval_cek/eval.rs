@@ -1,7 +1,8 @@
 use crate::lamcirc::eval_cek::data::*;
+use crate::stats;
 use std::rc::Rc;
 
-enum Cont {
+pub enum Cont {
     End,
     EvalArg(Rc<Ast>, Rc<Env>, Box<Cont>),
     ReduceFunc(Rc<Ast>, Rc<Env>, Box<Cont>),
@@ -14,85 +15,163 @@ enum Cont {
     FutUnq(Box<Cont>),
 }
 
-fn apply_cont(cont: Cont, v: Rc<Val>) -> Rc<Val> {
+// Each call of `eval_term` or `apply_cont` does a single transition of the
+// machine; `run` iterates them until the machine halts or runs out of fuel.
+pub enum State {
+    Eval(i32, Rc<Ast>, Rc<Env>, Cont),
+    Apply(Cont, Rc<Val>),
+    Halt(Rc<Val>),
+    Undefined(usize), // a global used before it is defined
+}
+
+pub enum Outcome {
+    Done(Rc<Val>),
+    Undefined(usize),
+    OutOfFuel(State), // pass it back to `run` to continue
+}
+
+fn apply_cont(cont: Cont, v: Rc<Val>) -> State {
     match cont {
-        Cont::End => v,
+        Cont::End => State::Halt(v),
         Cont::EvalArg(arg, env, rest) => match &*v {
             Val::Clos(cenv, body) => {
                 let body2 = body.clone();
                 let cenv2 = cenv.clone();
-                eval(0, arg, env, Cont::ReduceFunc(body2, cenv2, rest))
+                State::Eval(0, arg, env, Cont::ReduceFunc(body2, cenv2, rest))
             }
-            _ => val::error("Expected closure"),
+            _ => State::Halt(val::error("Expected closure")),
         },
-        Cont::ReduceFunc(body, cenv, rest) => eval(0, body, env::cons(v, cenv), *rest),
+        Cont::ReduceFunc(body, cenv, rest) => State::Eval(0, body, env::cons(v, cenv), *rest),
         Cont::ToQuo(rest) => match &*v {
-            Val::Fut(normcode) => apply_cont(*rest, val::quo(normcode.clone())),
-            _ => val::error("Expected future code fragment"),
+            Val::Fut(normcode) => State::Apply(*rest, val::quo(normcode.clone())),
+            _ => State::Halt(val::error("Expected future code fragment")),
         },
         Cont::RedQuo(rest) => match &*v {
-            Val::Quo(normcode) => apply_cont(*rest, val::fut(normcode.clone())),
-            _ => val::error("Expected quoted code"),
+            Val::Quo(normcode) => State::Apply(*rest, val::fut(normcode.clone())),
+            _ => State::Halt(val::error("Expected quoted code")),
         },
         Cont::FutLam(rest) => match &*v {
-            Val::Fut(normcode) => apply_cont(*rest, val::fut(ast::lam(normcode.clone()))),
-            _ => val::error("Expected future code fratgment"),
+            Val::Fut(normcode) => State::Apply(*rest, val::fut(ast::lam(normcode.clone()))),
+            _ => State::Halt(val::error("Expected future code fratgment")),
         },
         Cont::FutAppArg(lev, arg, env, cont) => match &*v {
             Val::Fut(funcnorm) => {
                 let funcnorm2 = funcnorm.clone();
-                eval(lev, arg, env, Cont::FutApp(funcnorm2, cont))
+                State::Eval(lev, arg, env, Cont::FutApp(funcnorm2, cont))
             }
-            _ => val::error("Expected future code fratgment"),
+            _ => State::Halt(val::error("Expected future code fratgment")),
         },
         Cont::FutApp(funcnorm, rest) => match &*v {
-            Val::Fut(argnorm) => apply_cont(*rest, val::fut(ast::app(funcnorm, argnorm.clone()))),
-            _ => val::error("Expected future code fratgment"),
+            Val::Fut(argnorm) => State::Apply(*rest, val::fut(ast::app(funcnorm, argnorm.clone()))),
+            _ => State::Halt(val::error("Expected future code fratgment")),
         },
         Cont::FutQuo(rest) => match &*v {
-            Val::Fut(codev) => apply_cont(*rest, val::fut(ast::quo(codev.clone()))),
-            _ => val::error("Expected future code fragment"),
+            Val::Fut(codev) => State::Apply(*rest, val::fut(ast::quo(codev.clone()))),
+            _ => State::Halt(val::error("Expected future code fragment")),
         },
         Cont::FutUnq(rest) => match &*v {
-            Val::Fut(codev) => apply_cont(*rest, val::fut(ast::unq(codev.clone()))),
-            _ => val::error("Expected future code fragment"),
+            Val::Fut(codev) => State::Apply(*rest, val::fut(ast::unq(codev.clone()))),
+            _ => State::Halt(val::error("Expected future code fragment")),
         },
     }
 }
 
-pub fn eval(lev: i32, ast: Rc<Ast>, env: Rc<Env>, cont: Cont) -> Rc<Val> {
+fn eval_term(globals: &[Rc<Val>], lev: i32, ast: Rc<Ast>, env: Rc<Env>, cont: Cont) -> State {
     //    println!("evaluating {:?} at level {}", &ast, lev);
     match (lev, &*ast) {
         // Top-level evaluation
-        (0, Ast::Int(i)) => apply_cont(cont, val::int(*i)),
+        (0, Ast::Int(i)) => State::Apply(cont, val::int(*i)),
         (0, Ast::Var(idx)) => env::lookup(env, *idx)
-            .map(|v| apply_cont(cont, v))
-            .unwrap_or(val::error("Undefined variable")),
-        (0, Ast::Lam(body)) => apply_cont(cont, Rc::new(Val::Clos(env.clone(), body.clone()))),
-        (0, Ast::App(func, arg)) => eval(
+            .map(|v| State::Apply(cont, v))
+            .unwrap_or_else(|| State::Halt(val::error("Undefined variable"))),
+        (0, Ast::Lam(body)) => State::Apply(cont, val::clos(env.clone(), body.clone())),
+        (0, Ast::App(func, arg)) => State::Eval(
             0,
             func.clone(),
             env.clone(),
-            Cont::EvalArg(arg.clone(), env, Box::new(cont)),
+            Cont::EvalArg(arg.clone(), env, stats::frame(cont)),
         ),
-        (0, Ast::Quo(code)) => eval(1, code.clone(), env, Cont::ToQuo(Box::new(cont))),
-        (0, Ast::Unq(_)) => val::error("Top-level unquote is NOT allowed"),
-        (1, Ast::Unq(code)) => eval(0, code.clone(), env, Cont::RedQuo(Box::new(cont))),
+        (0, Ast::Quo(code)) => State::Eval(1, code.clone(), env, Cont::ToQuo(stats::frame(cont))),
+        (0, Ast::Unq(_)) => State::Halt(val::error("Top-level unquote is NOT allowed")),
+        (1, Ast::Unq(code)) => State::Eval(0, code.clone(), env, Cont::RedQuo(stats::frame(cont))),
+        (0, Ast::Global(idx)) => globals
+            .get(*idx)
+            .map(|v| State::Apply(cont, v.clone()))
+            .unwrap_or(State::Undefined(*idx)),
         // Future-level evaluation
-        (_, Ast::Int(i)) => apply_cont(cont, val::fut(ast::int(*i))),
-        (_, Ast::Var(idx)) => apply_cont(cont, val::fut(ast::var(*idx))),
-        (lev, Ast::Lam(body)) => eval(lev, body.clone(), env, Cont::FutLam(Box::new(cont))),
-        (lev, Ast::App(func, arg)) => eval(
+        (_, Ast::Int(i)) => State::Apply(cont, val::fut(ast::int(*i))),
+        (_, Ast::Var(idx)) => State::Apply(cont, val::fut(ast::var(*idx))),
+        (lev, Ast::Lam(body)) => {
+            State::Eval(lev, body.clone(), env, Cont::FutLam(stats::frame(cont)))
+        }
+        (lev, Ast::App(func, arg)) => State::Eval(
             lev,
             func.clone(),
             env.clone(),
-            Cont::FutAppArg(lev, arg.clone(), env, Box::new(cont)),
+            Cont::FutAppArg(lev, arg.clone(), env, stats::frame(cont)),
         ),
-        (lev, Ast::Quo(code)) => eval(lev + 1, code.clone(), env, Cont::FutQuo(Box::new(cont))),
-        (_, Ast::Unq(code)) => eval(lev - 1, code.clone(), env, Cont::FutUnq(Box::new(cont))),
+        (lev, Ast::Quo(code)) => {
+            State::Eval(lev + 1, code.clone(), env, Cont::FutQuo(stats::frame(cont)))
+        }
+        (_, Ast::Unq(code)) => {
+            State::Eval(lev - 1, code.clone(), env, Cont::FutUnq(stats::frame(cont)))
+        }
+        // globals are shared by all stages, so code refers to them by name
+        (_, Ast::Global(idx)) => State::Apply(cont, val::fut(ast::global(*idx))),
     }
 }
 
+// `fuel` bounds the number of terms evaluated, `None` means no limit.
+pub fn run(state: State, fuel: Option<usize>) -> Outcome {
+    run_with(&[], state, fuel)
+}
+
+// Like `run`, with `globals` holding the values of top-level definitions.
+pub fn run_with(globals: &[Rc<Val>], state: State, fuel: Option<usize>) -> Outcome {
+    let mut state = state;
+    let mut fuel = fuel;
+    loop {
+        state = match state {
+            State::Halt(v) => return Outcome::Done(v),
+            State::Undefined(idx) => return Outcome::Undefined(idx),
+            State::Eval(..) if fuel == Some(0) => return Outcome::OutOfFuel(state),
+            State::Eval(lev, ast, env, cont) => {
+                fuel = fuel.map(|fuel| fuel - 1);
+                eval_term(globals, lev, ast, env, cont)
+            }
+            State::Apply(cont, v) => apply_cont(cont, v),
+        }
+    }
+}
+
+pub fn eval_fuel(fuel: usize, lev: i32, ast: Rc<Ast>, env: Rc<Env>, cont: Cont) -> Outcome {
+    run(State::Eval(lev, ast, env, cont), Some(fuel))
+}
+
+pub fn eval(lev: i32, ast: Rc<Ast>, env: Rc<Env>, cont: Cont) -> Rc<Val> {
+    match run(State::Eval(lev, ast, env, cont), None) {
+        Outcome::Done(v) => v,
+        Outcome::Undefined(_) => val::error("Undefined global"),
+        Outcome::OutOfFuel(_) => unreachable!(),
+    }
+}
+
+// Evaluate the top-level definitions of a program in order. A definition
+// can use later ones only under a lambda; fails with the index of one that
+// needs a later one.
+pub fn define_globals(defs: &[Rc<Ast>]) -> Result<Vec<Rc<Val>>, usize> {
+    let mut globals = Vec::new();
+    for (i, def) in defs.iter().enumerate() {
+        let state = State::Eval(0, def.clone(), env::empty(), Cont::End);
+        match run_with(&globals, state, None) {
+            Outcome::Done(v) => globals.push(v),
+            Outcome::Undefined(_) => return Err(i),
+            Outcome::OutOfFuel(_) => unreachable!(),
+        }
+    }
+    Ok(globals)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -162,4 +241,41 @@ mod tests {
             val::quo(quo(unq(var(1))))
         );
     }
+
+    // (\x. x x) (\x. x x)
+    fn omega() -> Rc<Ast> {
+        app(lam(app(var(0), var(0))), lam(app(var(0), var(0))))
+    }
+
+    #[test]
+    fn eval_out_of_fuel() {
+        assert!(matches!(
+            eval_fuel(1000, 0, omega(), env1(), Cont::End),
+            Outcome::OutOfFuel(State::Eval(0, _, _, _))
+        ));
+        // code generation of a divergent term terminates
+        assert!(matches!(
+            eval_fuel(1000, 0, quo(omega()), env1(), Cont::End),
+            Outcome::Done(v) if v == val::quo(omega())
+        ));
+        assert!(matches!(
+            eval_fuel(1000, 0, quo(unq(omega())), env1(), Cont::End),
+            Outcome::OutOfFuel(State::Eval(0, _, _, _))
+        ));
+    }
+
+    #[test]
+    fn eval_resume() {
+        let state = match eval_fuel(
+            2,
+            0,
+            app(app(lam(lam(var(1))), int(33)), int(44)),
+            env1(),
+            Cont::End,
+        ) {
+            Outcome::OutOfFuel(state) => state,
+            _ => panic!("should run out of fuel"),
+        };
+        assert!(matches!(run(state, Some(5)), Outcome::Done(v) if v == val::int(33)));
+    }
 }
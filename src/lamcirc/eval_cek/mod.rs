@@ -1,2 +1,3 @@
 pub mod data;
-pub mod eval;
\ No newline at end of file
+pub mod eval;
+pub mod print;
@@ -1,4 +1,5 @@
 use crate::lamcirc::eval_cps::data::*;
+use crate::stats;
 use std::rc::Rc;
 
 pub fn eval(
@@ -6,6 +7,19 @@ pub fn eval(
     ast: Rc<Ast>,
     env: Rc<Env>,
     cont: Box<dyn FnOnce(Rc<Val>) -> Rc<Val>>,
+) -> Rc<Val> {
+    eval_with(Rc::from([]), lev, ast, env, cont)
+}
+
+const UNDEFINED_GLOBAL: &str = "Undefined global";
+
+// Like `eval`, with `globals` holding the values of top-level definitions.
+pub fn eval_with(
+    globals: Rc<[Rc<Val>]>,
+    lev: i32,
+    ast: Rc<Ast>,
+    env: Rc<Env>,
+    cont: Box<dyn FnOnce(Rc<Val>) -> Rc<Val>>,
 ) -> Rc<Val> {
     //    println!("evaluating {:?} at level {}", &ast, lev);
     match (lev, &*ast) {
@@ -13,75 +27,88 @@ pub fn eval(
         (0, Ast::Int(i)) => cont(val::int(*i)),
         (0, Ast::Var(idx)) => env::lookup(env, *idx)
             .map(|v| cont(v))
-            .unwrap_or(val::error("Undefined variable")),
-        (0, Ast::Lam(body)) => cont(Rc::new(Val::Clos(env.clone(), body.clone()))),
+            .unwrap_or_else(|| val::error("Undefined variable")),
+        (0, Ast::Lam(body)) => cont(val::clos(env.clone(), body.clone())),
         (0, Ast::App(func, arg)) => {
             let env2 = env.clone();
             let arg2 = arg.clone();
-            eval(
+            eval_with(
+                globals.clone(),
                 0,
                 func.clone(),
                 env,
-                Box::new(|funcv| match &*funcv {
+                stats::frame(|funcv| match &*funcv {
                     Val::Clos(cenv, body) => {
                         let body2 = body.clone();
                         let cenv2 = cenv.clone();
-                        eval(
+                        eval_with(
+                            globals.clone(),
                             0,
                             arg2,
                             env2,
-                            Box::new(|argval| eval(0, body2, env::cons(argval, cenv2), cont)),
+                            stats::frame(|argval| {
+                                eval_with(globals, 0, body2, env::cons(argval, cenv2), cont)
+                            }),
                         )
                     }
                     _ => val::error("Expected closure"),
                 }),
             )
         }
-        (0, Ast::Quo(code)) => eval(
+        (0, Ast::Quo(code)) => eval_with(
+            globals,
             1,
             code.clone(),
             env,
-            Box::new(|codev| match &*codev {
+            stats::frame(|codev| match &*codev {
                 Val::Fut(normcode) => cont(val::quo(normcode.clone())),
                 _ => val::error("Expected future code fragment"),
             }),
         ),
         (0, Ast::Unq(_)) => val::error("Top-level unquote is NOT allowed"),
-        (1, Ast::Unq(code)) => eval(
+        (1, Ast::Unq(code)) => eval_with(
+            globals,
             0,
             code.clone(),
             env,
-            Box::new(|codev| match &*codev {
+            stats::frame(|codev| match &*codev {
                 Val::Quo(normcode) => cont(val::fut(normcode.clone())),
                 _ => val::error("Expected quoted code"),
             }),
         ),
+        (0, Ast::Global(idx)) => match globals.get(*idx) {
+            Some(v) => cont(v.clone()),
+            None => val::error(UNDEFINED_GLOBAL),
+        },
         // Future-level evaluation
         (_, Ast::Int(i)) => cont(val::fut(ast::int(*i))),
         (_, Ast::Var(idx)) => cont(val::fut(ast::var(*idx))),
-        (lev, Ast::Lam(body)) => eval(
+        (lev, Ast::Lam(body)) => eval_with(
+            globals,
             lev,
             body.clone(),
             env,
-            Box::new(|codev| match &*codev {
+            stats::frame(|codev| match &*codev {
                 Val::Fut(normcode) => cont(val::fut(ast::lam(normcode.clone()))),
                 _ => val::error("Expected future code fratgment"),
             }),
         ),
         (lev, Ast::App(func, arg)) => {
             let arg2 = arg.clone();
-            eval(
+            eval_with(
+                globals.clone(),
                 lev,
                 func.clone(),
                 env.clone(),
-                Box::new(move |funcv| match &*funcv {
+                stats::frame(move |funcv| match &*funcv {
                     Val::Fut(funcnorm) => {
                         let funcnorm2 = funcnorm.clone();
-                        eval(
+                        eval_with(
+                            globals,
                             lev,
                             arg2,
                             env,
-                            Box::new(|argv| match &*argv {
+                            stats::frame(|argv| match &*argv {
                                 Val::Fut(argnorm) => {
                                     cont(val::fut(ast::app(funcnorm2, argnorm.clone())))
                                 }
@@ -93,25 +120,45 @@ pub fn eval(
                 }),
             )
         }
-        (lev, Ast::Quo(code)) => eval(
+        (lev, Ast::Quo(code)) => eval_with(
+            globals,
             lev + 1,
             code.clone(),
             env,
-            Box::new(|v| match &*v {
+            stats::frame(|v| match &*v {
                 Val::Fut(codev) => cont(val::fut(ast::quo(codev.clone()))),
                 _ => val::error("Expected future code fragment"),
             }),
         ),
-        (_, Ast::Unq(code)) => eval(
+        (_, Ast::Unq(code)) => eval_with(
+            globals,
             lev - 1,
             code.clone(),
             env,
-            Box::new(|v| match &*v {
+            stats::frame(|v| match &*v {
                 Val::Fut(codev) => cont(val::fut(ast::unq(codev.clone()))),
                 _ => val::error("Expected future code fragment"),
             }),
         ),
+        // globals are shared by all stages, so code refers to them by name
+        (_, Ast::Global(idx)) => cont(val::fut(ast::global(*idx))),
+    }
+}
+
+// Evaluate the top-level definitions of a program in order. A definition
+// can use later ones only under a lambda; fails with the index of one that
+// needs a later one.
+pub fn define_globals(defs: &[Rc<Ast>]) -> Result<Rc<[Rc<Val>]>, usize> {
+    let mut globals = Vec::new();
+    for (i, def) in defs.iter().enumerate() {
+        let defined = Rc::from(globals.as_slice());
+        let v = eval_with(defined, 0, def.clone(), env::empty(), Box::new(|v| v));
+        match &*v {
+            Val::Error(msg) if msg == UNDEFINED_GLOBAL => return Err(i),
+            _ => globals.push(v),
+        }
     }
+    Ok(Rc::from(globals))
 }
 
 #[cfg(test)]
@@ -0,0 +1,57 @@
+use crate::lamcirc::eval_cps::data::{Ast, Env, Val};
+use crate::pretty;
+use crate::pretty::{Ctx, Prec, Term};
+use std::fmt;
+
+impl Term for Ast {
+    fn prec(&self) -> Prec {
+        match self {
+            Ast::Int(_) | Ast::Var(_) | Ast::Quo(_) | Ast::Global(_) => Prec::Atom,
+            Ast::App(_, _) | Ast::Unq(_) => Prec::App,
+            Ast::Lam(_) => Prec::Open,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ast::Int(i) => write!(f, "{}", i),
+            Ast::Var(idx) => write!(f, "{}", ctx.var(*idx, lev)),
+            Ast::Lam(body) => pretty::lam(&**body, ctx, lev, f),
+            Ast::App(func, arg) => pretty::app(&**func, &**arg, ctx, lev, f),
+            Ast::Quo(body) => pretty::quo(&**body, ctx, lev, f),
+            Ast::Unq(body) => pretty::unq(&**body, ctx, lev, f),
+            Ast::Global(i) => write!(f, "g{}", i),
+        }
+    }
+}
+
+pretty::display!(Ast);
+
+fn entries(mut env: &Env) -> Vec<&Val> {
+    let mut entries = vec![];
+    while let Env::Cons(head, tail) = env {
+        entries.push(&**head);
+        env = tail;
+    }
+    entries
+}
+
+impl fmt::Display for Env {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pretty::env(&entries(self), f)
+    }
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Val::Error(msg) => write!(f, "error: {}", msg),
+            Val::Int(i) => write!(f, "{}", i),
+            Val::Clos(env, body) => {
+                pretty::closure(&entries(env), f, |ctx, f| pretty::lam(&**body, ctx, 0, f))
+            }
+            Val::Quo(code) => pretty::quo(&**code, &mut Ctx::new(), 0, f),
+            Val::Fut(code) => code.fmt_in(&mut Ctx::new(), 0, f),
+        }
+    }
+}
@@ -1,96 +1,176 @@
 use crate::lamcirc::eval_vm::data::*;
+use crate::stats;
 use std::rc::Rc;
 
-fn apply_cont(cont: Cont, v: Rc<Val>) -> Rc<Val> {
+fn apply_cont(cont: Cont, v: Rc<Val>) -> State {
     match cont {
-        Cont::End => v,
+        Cont::End => State::Halt(v),
         Cont::EvalArg(arg, env, rest) => match &*v {
-            Val::Clos(cenv, compbody) => run(
+            Val::Clos(cenv, compbody) => State::Run(
                 arg,
                 0,
                 env,
-                Box::new(Cont::ReduceFunc(compbody.clone(), cenv.clone(), rest)),
+                stats::frame(Cont::ReduceFunc(compbody.clone(), cenv.clone(), rest)),
             ),
-            _ => val::error("Expected closure"),
+            _ => State::Halt(val::error("Expected closure")),
         },
-        Cont::ReduceFunc(compbody, cenv, rest) => run(compbody, 0, env::cons(v, cenv), rest),
+        Cont::ReduceFunc(compbody, cenv, rest) => State::Run(compbody, 0, env::cons(v, cenv), rest),
         Cont::ToQuo(rest) => match &*v {
-            Val::Fut(normcode) => apply_cont(*rest, val::quo(normcode.clone())),
-            _ => val::error("Expected future code fragment"),
+            Val::Fut(normcode) => State::Apply(*rest, val::quo(normcode.clone())),
+            _ => State::Halt(val::error("Expected future code fragment")),
         },
         Cont::RedQuo(rest) => match &*v {
-            Val::Quo(normcode) => apply_cont(*rest, val::fut(normcode.clone())),
-            _ => val::error("Expected quoted code"),
+            Val::Quo(normcode) => State::Apply(*rest, val::fut(normcode.clone())),
+            _ => State::Halt(val::error("Expected quoted code")),
         },
         Cont::FutLam(rest) => match &*v {
-            Val::Fut(normcode) => apply_cont(*rest, val::fut(ast::lam(normcode.clone()))),
-            _ => val::error("Expected future code fratgment"),
+            Val::Fut(normcode) => State::Apply(*rest, val::fut(ast::lam(normcode.clone()))),
+            _ => State::Halt(val::error("Expected future code fratgment")),
         },
         Cont::FutAppArg(lev, comparg, env, cont) => match &*v {
             Val::Fut(funcnorm) => {
                 let funcnorm2 = funcnorm.clone();
-                run(comparg, lev, env, Box::new(Cont::FutApp(funcnorm2, cont)))
+                State::Run(
+                    comparg,
+                    lev,
+                    env,
+                    stats::frame(Cont::FutApp(funcnorm2, cont)),
+                )
             }
-            _ => val::error("Expected future code fratgment"),
+            _ => State::Halt(val::error("Expected future code fratgment")),
         },
         Cont::FutApp(funcnorm, rest) => match &*v {
-            Val::Fut(argnorm) => apply_cont(*rest, val::fut(ast::app(funcnorm, argnorm.clone()))),
-            _ => val::error("Expected future code fratgment"),
+            Val::Fut(argnorm) => State::Apply(*rest, val::fut(ast::app(funcnorm, argnorm.clone()))),
+            _ => State::Halt(val::error("Expected future code fratgment")),
         },
         Cont::FutQuo(rest) => match &*v {
-            Val::Fut(codev) => apply_cont(*rest, val::fut(ast::quo(codev.clone()))),
-            _ => val::error("Expected future code fragment"),
+            Val::Fut(codev) => State::Apply(*rest, val::fut(ast::quo(codev.clone()))),
+            _ => State::Halt(val::error("Expected future code fragment")),
         },
         Cont::FutUnq(rest) => match &*v {
-            Val::Fut(codev) => apply_cont(*rest, val::fut(ast::unq(codev.clone()))),
-            _ => val::error("Expected future code fragment"),
+            Val::Fut(codev) => State::Apply(*rest, val::fut(ast::unq(codev.clone()))),
+            _ => State::Halt(val::error("Expected future code fragment")),
         },
     }
 }
 
-pub fn run(instrs: Rc<InstrSeq>, lev: i32, env: Rc<Env>, cont: Box<Cont>) -> Rc<Val> {
+fn exec(
+    globals: &[Rc<Val>],
+    instrs: Rc<InstrSeq>,
+    lev: i32,
+    env: Rc<Env>,
+    cont: Box<Cont>,
+) -> State {
     match &*instrs {
         InstrSeq::Seq(Instr::Lit(i), _) => match lev {
-            0 => apply_cont(*cont, val::int(*i)),
-            _ => apply_cont(*cont, val::fut(ast::int(*i))),
+            0 => State::Apply(*cont, val::int(*i)),
+            _ => State::Apply(*cont, val::fut(ast::int(*i))),
         },
         InstrSeq::Seq(Instr::Var(idx), _) => match lev {
             0 => env::lookup(env, *idx)
-                .map(|v| apply_cont(*cont, v))
-                .unwrap_or(val::error("Undefined variable")),
-            _ => apply_cont(*cont, val::fut(ast::var(*idx))),
+                .map(|v| State::Apply(*cont, v))
+                .unwrap_or_else(|| State::Halt(val::error("Undefined variable"))),
+            _ => State::Apply(*cont, val::fut(ast::var(*idx))),
         },
         InstrSeq::Seq(Instr::Clos(body), _) => match lev {
-            0 => apply_cont(*cont, val::clos(env.clone(), body.clone())),
-            lev => run(body.clone(), lev, env, Box::new(Cont::FutLam(cont))),
+            0 => State::Apply(*cont, val::clos(env.clone(), body.clone())),
+            lev => State::Run(body.clone(), lev, env, stats::frame(Cont::FutLam(cont))),
         },
         InstrSeq::Seq(Instr::Push(func), arg) => match lev {
-            0 => run(
+            0 => State::Run(
                 func.clone(),
                 0,
                 env.clone(),
-                Box::new(Cont::EvalArg(arg.clone(), env, cont)),
+                stats::frame(Cont::EvalArg(arg.clone(), env, cont)),
             ),
-            lev => run(
+            lev => State::Run(
                 func.clone(),
                 lev,
                 env.clone(),
-                Box::new(Cont::FutAppArg(lev, arg.clone(), env, cont)),
+                stats::frame(Cont::FutAppArg(lev, arg.clone(), env, cont)),
             ),
         },
         InstrSeq::Seq(Instr::Ent, code) => match lev {
-            0 => run(code.clone(), 1, env, Box::new(Cont::ToQuo(cont))),
-            lev => run(code.clone(), lev + 1, env, Box::new(Cont::FutQuo(cont))),
+            0 => State::Run(code.clone(), 1, env, stats::frame(Cont::ToQuo(cont))),
+            lev => State::Run(code.clone(), lev + 1, env, stats::frame(Cont::FutQuo(cont))),
         },
         InstrSeq::Seq(Instr::Leave, code) => match lev {
-            0 => val::error("Top-level unquote is NOT allowed"),
-            1 => run(code.clone(), 0, env, Box::new(Cont::RedQuo(cont))),
-            lev => run(code.clone(), lev - 1, env, Box::new(Cont::FutUnq(cont))),
+            0 => State::Halt(val::error("Top-level unquote is NOT allowed")),
+            1 => State::Run(code.clone(), 0, env, stats::frame(Cont::RedQuo(cont))),
+            lev => State::Run(code.clone(), lev - 1, env, stats::frame(Cont::FutUnq(cont))),
+        },
+        InstrSeq::Seq(Instr::Global(idx), _) => match lev {
+            0 => globals
+                .get(*idx)
+                .map(|v| State::Apply(*cont, v.clone()))
+                .unwrap_or(State::Undefined(*idx)),
+            // globals are shared by all stages, so code refers to them by name
+            _ => State::Apply(*cont, val::fut(ast::global(*idx))),
         },
-        InstrSeq::End => val::error("Reached end of instruction sequence, unexpectedly"),
+        InstrSeq::End => State::Halt(val::error(
+            "Reached end of instruction sequence, unexpectedly",
+        )),
+    }
+}
+
+// `fuel` bounds the number of instructions executed, `None` means no limit.
+pub fn run_state(state: State, fuel: Option<usize>) -> Outcome {
+    run_state_with(&[], state, fuel)
+}
+
+// Like `run_state`, with `globals` holding the values of top-level
+// definitions.
+pub fn run_state_with(globals: &[Rc<Val>], state: State, fuel: Option<usize>) -> Outcome {
+    let mut state = state;
+    let mut fuel = fuel;
+    loop {
+        state = match state {
+            State::Halt(v) => return Outcome::Done(v),
+            State::Undefined(idx) => return Outcome::Undefined(idx),
+            State::Run(..) if fuel == Some(0) => return Outcome::OutOfFuel(state),
+            State::Run(instrs, lev, env, cont) => {
+                fuel = fuel.map(|fuel| fuel - 1);
+                exec(globals, instrs, lev, env, cont)
+            }
+            State::Apply(cont, v) => apply_cont(cont, v),
+        }
     }
 }
 
+pub fn run_fuel(
+    fuel: usize,
+    instrs: Rc<InstrSeq>,
+    lev: i32,
+    env: Rc<Env>,
+    cont: Box<Cont>,
+) -> Outcome {
+    run_state(State::Run(instrs, lev, env, cont), Some(fuel))
+}
+
+pub fn run(instrs: Rc<InstrSeq>, lev: i32, env: Rc<Env>, cont: Box<Cont>) -> Rc<Val> {
+    match run_state(State::Run(instrs, lev, env, cont), None) {
+        Outcome::Done(v) => v,
+        Outcome::Undefined(_) => val::error("Undefined global"),
+        Outcome::OutOfFuel(_) => unreachable!(),
+    }
+}
+
+// Compile and run the top-level definitions of a program in order. A
+// definition can use later ones only under a lambda; fails with the index
+// of one that needs a later one.
+pub fn define_globals(defs: &[Rc<Ast>]) -> Result<Vec<Rc<Val>>, usize> {
+    let mut globals = Vec::new();
+    for (i, def) in defs.iter().enumerate() {
+        let state = State::Run(compile(def.clone()), 0, env::empty(), Box::new(Cont::End));
+        match run_state_with(&globals, state, None) {
+            Outcome::Done(v) => globals.push(v),
+            Outcome::Undefined(_) => return Err(i),
+            Outcome::OutOfFuel(_) => unreachable!(),
+        }
+    }
+    Ok(globals)
+}
+
 pub fn compile(ast: Rc<Ast>) -> Rc<InstrSeq> {
     match &*ast {
         Ast::Int(i) => instrseq::singleton(instr::lit(*i)),
@@ -101,6 +181,7 @@ pub fn compile(ast: Rc<Ast>) -> Rc<InstrSeq> {
         }
         Ast::Quo(code) => instrseq::seq(instr::ent(), compile(code.clone())),
         Ast::Unq(code) => instrseq::seq(instr::leave(), compile(code.clone())),
+        Ast::Global(idx) => instrseq::singleton(instr::global(*idx)),
     }
 }
 
@@ -218,4 +299,31 @@ mod tests {
             val::quo(quo(unq(var(1))))
         );
     }
+
+    // (\x. x x) (\x. x x)
+    fn omega() -> Rc<Ast> {
+        app(lam(app(var(0), var(0))), lam(app(var(0), var(0))))
+    }
+
+    #[test]
+    fn eval_out_of_fuel() {
+        assert!(matches!(
+            run_fuel(1000, compile(omega()), 0, env1(), Box::new(Cont::End)),
+            Outcome::OutOfFuel(State::Run(_, 0, _, _))
+        ));
+        assert!(matches!(
+            run_fuel(1000, compile(quo(omega())), 0, env1(), Box::new(Cont::End)),
+            Outcome::Done(v) if v == val::quo(omega())
+        ));
+    }
+
+    #[test]
+    fn eval_resume() {
+        let code = compile(app(app(lam(lam(var(1))), int(33)), int(44)));
+        let state = match run_fuel(2, code, 0, env1(), Box::new(Cont::End)) {
+            Outcome::OutOfFuel(state) => state,
+            _ => panic!("should run out of fuel"),
+        };
+        assert!(matches!(run_state(state, Some(5)), Outcome::Done(v) if v == val::int(33)));
+    }
 }
@@ -1,3 +1,5 @@
+use crate::stats;
+use crate::stats::Kind;
 use std::{fmt::Debug, rc::Rc};
 
 #[derive(Eq, PartialEq, Debug)]
@@ -8,6 +10,7 @@ pub enum Ast {
     App(Rc<Ast>, Rc<Ast>),
     Quo(Rc<Ast>),
     Unq(Rc<Ast>),
+    Global(usize), // top-level definition, looked up in the globals table
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -39,6 +42,7 @@ pub enum Instr {
     Push(Rc<InstrSeq>),
     Ent,
     Leave,
+    Global(usize),
 }
 
 pub enum Cont {
@@ -54,6 +58,8 @@ pub enum Cont {
     FutUnq(Box<Cont>),
 }
 
+stats::counted!(Val, Env, InstrSeq);
+
 pub mod ast {
     use super::*;
 
@@ -80,41 +86,55 @@ pub mod ast {
     pub fn unq(ast: Rc<Ast>) -> Rc<Ast> {
         Rc::new(Ast::Unq(ast))
     }
+
+    pub fn global(i: usize) -> Rc<Ast> {
+        Rc::new(Ast::Global(i))
+    }
 }
 
 pub mod val {
     use super::*;
 
+    fn alloc(v: Val) -> Rc<Val> {
+        stats::alloc(Kind::Val);
+        Rc::new(v)
+    }
+
     pub fn error(msg: &str) -> Rc<Val> {
-        return Rc::new(Val::Error(String::from(msg)));
+        return alloc(Val::Error(String::from(msg)));
     }
 
     pub fn int(i: i32) -> Rc<Val> {
-        return Rc::new(Val::Int(i));
+        return alloc(Val::Int(i));
     }
 
     pub fn clos(env: Rc<Env>, body: Rc<InstrSeq>) -> Rc<Val> {
-        return Rc::new(Val::Clos(env, body));
+        return alloc(Val::Clos(env, body));
     }
 
     pub fn quo(instrseq: Rc<InstrSeq>) -> Rc<Val> {
-        return Rc::new(Val::Quo(instrseq));
+        return alloc(Val::Quo(instrseq));
     }
 
     pub fn fut(instrseq: Rc<InstrSeq>) -> Rc<Val> {
-        return Rc::new(Val::Fut(instrseq));
+        return alloc(Val::Fut(instrseq));
     }
 }
 
 pub mod env {
     use super::*;
 
+    fn alloc(env: Env) -> Rc<Env> {
+        stats::alloc(Kind::Env);
+        Rc::new(env)
+    }
+
     pub fn empty() -> Rc<Env> {
-        return Rc::new(Env::Nil);
+        return alloc(Env::Nil);
     }
 
     pub fn cons(elm: Rc<Val>, env: Rc<Env>) -> Rc<Env> {
-        return Rc::new(Env::Cons(elm.clone(), env.clone()));
+        return alloc(Env::Cons(elm.clone(), env.clone()));
     }
 
     pub fn lookup(env: Rc<Env>, idx: usize) -> Option<Rc<Val>> {
@@ -134,12 +154,17 @@ pub mod env {
 pub mod instrseq {
     use super::*;
 
+    fn alloc(i: InstrSeq) -> Rc<InstrSeq> {
+        stats::alloc(Kind::Code);
+        Rc::new(i)
+    }
+
     pub fn end() -> Rc<InstrSeq> {
-        Rc::new(InstrSeq::End)
+        alloc(InstrSeq::End)
     }
 
     pub fn seq(instr: Instr, rest: Rc<InstrSeq>) -> Rc<InstrSeq> {
-        Rc::new(InstrSeq::Seq(instr, rest))
+        alloc(InstrSeq::Seq(instr, rest))
     }
 
     pub fn singleton(instr: Instr) -> Rc<InstrSeq> {
@@ -173,4 +198,8 @@ pub mod instr {
     pub fn leave() -> Instr {
         Instr::Leave
     }
+
+    pub fn global(idx: usize) -> Instr {
+        Instr::Global(idx)
+    }
 }
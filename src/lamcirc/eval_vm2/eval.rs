@@ -1,29 +1,34 @@
 use crate::lamcirc::eval_vm2::data::*;
+use crate::stats;
 use std::rc::Rc;
 
-fn apply_cont(cont: Cont, v: Rc<Val>) -> Rc<Val> {
+fn apply_cont(globals: &[Rc<Val>], cont: Cont, v: Rc<Val>) -> Rc<Val> {
     match cont {
         Cont::End => v,
         Cont::EvalArg(arg, env, rest) => match &*v {
-            Val::Clos(cenv, compbody) => run(
+            Val::Clos(cenv, compbody) => run_with(
+                globals,
                 arg,
                 0,
                 env,
-                Box::new(Cont::ReduceFunc(compbody.clone(), cenv.clone(), rest)),
+                stats::frame(Cont::ReduceFunc(compbody.clone(), cenv.clone(), rest)),
             ),
             _ => val::error("Expected closure"),
         },
-        Cont::ReduceFunc(compbody, cenv, rest) => run(compbody, 0, env::cons(v, cenv), rest),
+        Cont::ReduceFunc(compbody, cenv, rest) => {
+            run_with(globals, compbody, 0, env::cons(v, cenv), rest)
+        }
         Cont::ToQuo(rest) => match &*v {
-            Val::Fut(normcode) => apply_cont(*rest, val::quo(normcode.clone())),
+            Val::Fut(normcode) => apply_cont(globals, *rest, val::quo(normcode.clone())),
             _ => val::error("Expected future code fragment"),
         },
         Cont::RedQuo(rest) => match &*v {
-            Val::Quo(normcode) => apply_cont(*rest, val::fut(normcode.clone())),
+            Val::Quo(normcode) => apply_cont(globals, *rest, val::fut(normcode.clone())),
             _ => val::error("Expected quoted code"),
         },
         Cont::FutLam(rest) => match &*v {
             Val::Fut(normcode) => apply_cont(
+                globals,
                 *rest,
                 val::fut(instrseq::singleton(instr::clos(normcode.clone()))),
             ),
@@ -32,25 +37,35 @@ fn apply_cont(cont: Cont, v: Rc<Val>) -> Rc<Val> {
         Cont::FutAppArg(lev, comparg, env, cont) => match &*v {
             Val::Fut(funcnorm) => {
                 let funcnorm2 = funcnorm.clone();
-                run(comparg, lev, env, Box::new(Cont::FutApp(funcnorm2, cont)))
+                run_with(
+                    globals,
+                    comparg,
+                    lev,
+                    env,
+                    stats::frame(Cont::FutApp(funcnorm2, cont)),
+                )
             }
             _ => val::error("Expected future code fratgment"),
         },
         Cont::FutApp(funcnorm, rest) => match &*v {
             Val::Fut(argnorm) => apply_cont(
+                globals,
                 *rest,
                 val::fut(instrseq::seq(instr::push(funcnorm), argnorm.clone())),
             ),
             _ => val::error("Expected future code fratgment"),
         },
         Cont::FutQuo(rest) => match &*v {
-            Val::Fut(codev) => {
-                apply_cont(*rest, val::fut(instrseq::seq(instr::ent(), codev.clone())))
-            }
+            Val::Fut(codev) => apply_cont(
+                globals,
+                *rest,
+                val::fut(instrseq::seq(instr::ent(), codev.clone())),
+            ),
             _ => val::error("Expected future code fragment"),
         },
         Cont::FutUnq(rest) => match &*v {
             Val::Fut(codev) => apply_cont(
+                globals,
                 *rest,
                 val::fut(instrseq::seq(instr::leave(), codev.clone())),
             ),
@@ -60,43 +75,108 @@ fn apply_cont(cont: Cont, v: Rc<Val>) -> Rc<Val> {
 }
 
 pub fn run(instrs: Rc<InstrSeq>, lev: i32, env: Rc<Env>, cont: Box<Cont>) -> Rc<Val> {
+    run_with(&[], instrs, lev, env, cont)
+}
+
+const UNDEFINED_GLOBAL: &str = "Undefined global";
+
+// Like `run`, with `globals` holding the values of top-level definitions.
+pub fn run_with(
+    globals: &[Rc<Val>],
+    instrs: Rc<InstrSeq>,
+    lev: i32,
+    env: Rc<Env>,
+    cont: Box<Cont>,
+) -> Rc<Val> {
     match &*instrs {
         InstrSeq::Seq(Instr::Lit(i), _) => match lev {
-            0 => apply_cont(*cont, val::int(*i)),
-            _ => apply_cont(*cont, val::fut(instrseq::singleton(instr::lit(*i)))),
+            0 => apply_cont(globals, *cont, val::int(*i)),
+            _ => apply_cont(
+                globals,
+                *cont,
+                val::fut(instrseq::singleton(instr::lit(*i))),
+            ),
         },
         InstrSeq::Seq(Instr::Var(idx), _) => match lev {
             0 => env::lookup(env, *idx)
-                .map(|v| apply_cont(*cont, v))
-                .unwrap_or(val::error("Undefined variable")),
-            _ => apply_cont(*cont, val::fut(instrseq::singleton(instr::var(*idx)))),
+                .map(|v| apply_cont(globals, *cont, v))
+                .unwrap_or_else(|| val::error("Undefined variable")),
+            _ => apply_cont(
+                globals,
+                *cont,
+                val::fut(instrseq::singleton(instr::var(*idx))),
+            ),
         },
         InstrSeq::Seq(Instr::Clos(body), _) => match lev {
-            0 => apply_cont(*cont, val::clos(env.clone(), body.clone())),
-            lev => run(body.clone(), lev, env, Box::new(Cont::FutLam(cont))),
+            0 => apply_cont(globals, *cont, val::clos(env.clone(), body.clone())),
+            lev => run_with(
+                globals,
+                body.clone(),
+                lev,
+                env,
+                stats::frame(Cont::FutLam(cont)),
+            ),
         },
         InstrSeq::Seq(Instr::Push(func), arg) => match lev {
-            0 => run(
+            0 => run_with(
+                globals,
                 func.clone(),
                 0,
                 env.clone(),
-                Box::new(Cont::EvalArg(arg.clone(), env, cont)),
+                stats::frame(Cont::EvalArg(arg.clone(), env, cont)),
             ),
-            lev => run(
+            lev => run_with(
+                globals,
                 func.clone(),
                 lev,
                 env.clone(),
-                Box::new(Cont::FutAppArg(lev, arg.clone(), env, cont)),
+                stats::frame(Cont::FutAppArg(lev, arg.clone(), env, cont)),
             ),
         },
         InstrSeq::Seq(Instr::Ent, code) => match lev {
-            0 => run(code.clone(), 1, env, Box::new(Cont::ToQuo(cont))),
-            lev => run(code.clone(), lev + 1, env, Box::new(Cont::FutQuo(cont))),
+            0 => run_with(
+                globals,
+                code.clone(),
+                1,
+                env,
+                stats::frame(Cont::ToQuo(cont)),
+            ),
+            lev => run_with(
+                globals,
+                code.clone(),
+                lev + 1,
+                env,
+                stats::frame(Cont::FutQuo(cont)),
+            ),
         },
         InstrSeq::Seq(Instr::Leave, code) => match lev {
             0 => val::error("Top-level unquote is NOT allowed"),
-            1 => run(code.clone(), 0, env, Box::new(Cont::RedQuo(cont))),
-            lev => run(code.clone(), lev - 1, env, Box::new(Cont::FutUnq(cont))),
+            1 => run_with(
+                globals,
+                code.clone(),
+                0,
+                env,
+                stats::frame(Cont::RedQuo(cont)),
+            ),
+            lev => run_with(
+                globals,
+                code.clone(),
+                lev - 1,
+                env,
+                stats::frame(Cont::FutUnq(cont)),
+            ),
+        },
+        InstrSeq::Seq(Instr::Global(idx), _) => match lev {
+            0 => match globals.get(*idx) {
+                Some(v) => apply_cont(globals, *cont, v.clone()),
+                None => val::error(UNDEFINED_GLOBAL),
+            },
+            // globals are shared by all stages, so code refers to them by name
+            _ => apply_cont(
+                globals,
+                *cont,
+                val::fut(instrseq::singleton(instr::global(*idx))),
+            ),
         },
         InstrSeq::End => val::error("Reached end of instruction sequence, unexpectedly"),
     }
@@ -112,7 +192,24 @@ pub fn compile(ast: Rc<Ast>) -> Rc<InstrSeq> {
         }
         Ast::Quo(code) => instrseq::seq(instr::ent(), compile(code.clone())),
         Ast::Unq(code) => instrseq::seq(instr::leave(), compile(code.clone())),
+        Ast::Global(idx) => instrseq::singleton(instr::global(*idx)),
+    }
+}
+
+// Compile and run the top-level definitions of a program in order. A
+// definition can use later ones only under a lambda; fails with the index
+// of one that needs a later one.
+pub fn define_globals(defs: &[Rc<Ast>]) -> Result<Vec<Rc<Val>>, usize> {
+    let mut globals = Vec::new();
+    for (i, def) in defs.iter().enumerate() {
+        let code = compile(def.clone());
+        let v = run_with(&globals, code, 0, env::empty(), Box::new(Cont::End));
+        match &*v {
+            Val::Error(msg) if msg == UNDEFINED_GLOBAL => return Err(i),
+            _ => globals.push(v),
+        }
     }
+    Ok(globals)
 }
 
 #[cfg(test)]
@@ -0,0 +1,107 @@
+use crate::lamcirc::eval_vm2::data::{Ast, Env, Instr, InstrSeq, Val};
+use crate::pretty;
+use crate::pretty::{Ctx, Prec, Term};
+use std::fmt;
+
+impl Term for Ast {
+    fn prec(&self) -> Prec {
+        match self {
+            Ast::Int(_) | Ast::Var(_) | Ast::Quo(_) | Ast::Global(_) => Prec::Atom,
+            Ast::App(_, _) | Ast::Unq(_) => Prec::App,
+            Ast::Lam(_) => Prec::Open,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ast::Int(i) => write!(f, "{}", i),
+            Ast::Var(idx) => write!(f, "{}", ctx.var(*idx, lev)),
+            Ast::Lam(body) => pretty::lam(&**body, ctx, lev, f),
+            Ast::App(func, arg) => pretty::app(&**func, &**arg, ctx, lev, f),
+            Ast::Quo(body) => pretty::quo(&**body, ctx, lev, f),
+            Ast::Unq(body) => pretty::unq(&**body, ctx, lev, f),
+            Ast::Global(i) => write!(f, "g{}", i),
+        }
+    }
+}
+
+// `Clos` binds a variable in its body, and the code after `Ent` and
+// `Leave` is one stage later and earlier.
+impl Term for InstrSeq {
+    fn prec(&self) -> Prec {
+        Prec::Atom
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        let (instr, rest) = match self {
+            InstrSeq::End => return Ok(()),
+            InstrSeq::Seq(instr, rest) => (instr, rest),
+        };
+        instr.fmt_in(ctx, lev, f)?;
+        if let InstrSeq::End = **rest {
+            return Ok(());
+        }
+        write!(f, "; ")?;
+        match instr {
+            Instr::Ent => rest.fmt_in(ctx, lev + 1, f),
+            Instr::Leave => rest.fmt_in(ctx, lev - 1, f),
+            _ => rest.fmt_in(ctx, lev, f),
+        }
+    }
+}
+
+impl Term for Instr {
+    fn prec(&self) -> Prec {
+        Prec::Atom
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instr::Lit(i) => write!(f, "lit {}", i),
+            Instr::Var(idx) => write!(f, "var {}", ctx.var(*idx, lev)),
+            Instr::Clos(body) => {
+                pretty::bind(|x, f| write!(f, "clos {} {{", x), &**body, ctx, lev, f)?;
+                write!(f, "}}")
+            }
+            Instr::Push(func) => {
+                write!(f, "push {{")?;
+                func.fmt_in(ctx, lev, f)?;
+                write!(f, "}}")
+            }
+            Instr::Ent => write!(f, "ent"),
+            Instr::Leave => write!(f, "leave"),
+            Instr::Global(i) => write!(f, "global g{}", i),
+        }
+    }
+}
+
+pretty::display!(Ast, InstrSeq, Instr);
+
+fn entries(mut env: &Env) -> Vec<&Val> {
+    let mut entries = vec![];
+    while let Env::Cons(head, tail) = env {
+        entries.push(&**head);
+        env = tail;
+    }
+    entries
+}
+
+impl fmt::Display for Env {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pretty::env(&entries(self), f)
+    }
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Val::Error(msg) => write!(f, "error: {}", msg),
+            Val::Int(i) => write!(f, "{}", i),
+            Val::Clos(env, body) => {
+                pretty::closure(&entries(env), f, |ctx, f| pretty::lam(&**body, ctx, 0, f))
+            }
+            Val::Quo(code) => pretty::quo(&**code, &mut Ctx::new(), 0, f),
+            Val::Fut(code) => code.fmt_in(&mut Ctx::new(), 0, f),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+pub use crate::lamcirc::eval_vm::data::{ast, instr, instrseq, Ast, Instr, InstrSeq};
+use std::rc::Rc;
+
+// The machine of `eval_vm` with values and environments on a traced heap
+// instead of behind `Rc`. Code and quoted terms are immutable trees, so
+// they stay reference counted.
+
+// Handle of an object on the heap.
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub struct Ptr(pub usize);
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Val {
+    Error(String),
+    Int(i32),
+    Clos(Ptr, Rc<InstrSeq>), // environment and body
+    Quo(Rc<Ast>),
+    Fut(Rc<Ast>),
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Env {
+    Nil,
+    Cons(Ptr, Ptr), // value and rest of the environment
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Obj {
+    Val(Val),
+    Env(Env),
+}
+
+// The pointers in continuation frames and states are the roots of the
+// collector.
+pub enum Cont {
+    End,
+    EvalArg(Rc<InstrSeq>, Ptr, Box<Cont>),
+    ReduceFunc(Rc<InstrSeq>, Ptr, Box<Cont>),
+    ToQuo(Box<Cont>),
+    RedQuo(Box<Cont>),
+    FutLam(Box<Cont>),
+    FutAppArg(i32, Rc<InstrSeq>, Ptr, Box<Cont>),
+    FutApp(Rc<Ast>, Box<Cont>),
+    FutQuo(Box<Cont>),
+    FutUnq(Box<Cont>),
+}
+
+pub enum State {
+    Run(Rc<InstrSeq>, i32, Ptr, Box<Cont>),
+    Apply(Cont, Ptr),
+    Halt(Ptr),
+    Undefined(usize), // a global used before it is defined
+}
+
+pub enum Outcome {
+    Done(Ptr),
+    Undefined(usize),
+    OutOfFuel(State), // pass it back to `run_state` to continue
+}
@@ -0,0 +1,364 @@
+use crate::lamcirc::eval_vm_gc::data::*;
+use crate::lamcirc::eval_vm_gc::heap::Heap;
+use crate::stats;
+use std::rc::Rc;
+
+pub use crate::lamcirc::eval_vm::eval::compile;
+
+pub struct Machine {
+    pub heap: Heap,
+    pub globals: Vec<Ptr>,   // values of top-level definitions, also roots
+    pub gc_threshold: usize, // collect once this many objects are live
+    pub collections: usize,
+}
+
+impl Default for Machine {
+    fn default() -> Machine {
+        Machine::with_threshold(1024)
+    }
+}
+
+impl Machine {
+    pub fn new() -> Machine {
+        Machine::default()
+    }
+
+    pub fn with_threshold(gc_threshold: usize) -> Machine {
+        Machine {
+            heap: Heap::new(),
+            globals: Vec::new(),
+            gc_threshold,
+            collections: 0,
+        }
+    }
+
+    // Collect everything the machine state cannot reach, returning how
+    // many objects were freed.
+    pub fn collect(&mut self, state: &State) -> usize {
+        self.collections += 1;
+        let mut roots = roots(state);
+        roots.extend_from_slice(&self.globals);
+        self.heap.collect(&roots)
+    }
+}
+
+// The registers of the machine: the environment or value of the state,
+// and the environments saved in the continuation.
+fn roots(state: &State) -> Vec<Ptr> {
+    let (mut roots, mut cont) = match state {
+        State::Run(_, _, env, cont) => (vec![*env], &**cont),
+        State::Apply(cont, v) => (vec![*v], cont),
+        State::Halt(v) => return vec![*v],
+        State::Undefined(_) => return vec![],
+    };
+    loop {
+        cont = match cont {
+            Cont::End => return roots,
+            Cont::EvalArg(_, env, rest)
+            | Cont::ReduceFunc(_, env, rest)
+            | Cont::FutAppArg(_, _, env, rest) => {
+                roots.push(*env);
+                rest
+            }
+            Cont::ToQuo(rest)
+            | Cont::RedQuo(rest)
+            | Cont::FutLam(rest)
+            | Cont::FutApp(_, rest)
+            | Cont::FutQuo(rest)
+            | Cont::FutUnq(rest) => rest,
+        }
+    }
+}
+
+fn apply_cont(h: &mut Heap, cont: Cont, v: Ptr) -> State {
+    match cont {
+        Cont::End => State::Halt(v),
+        Cont::EvalArg(arg, env, rest) => match h.val(v) {
+            Val::Clos(cenv, compbody) => State::Run(
+                arg,
+                0,
+                env,
+                stats::frame(Cont::ReduceFunc(compbody.clone(), *cenv, rest)),
+            ),
+            _ => State::Halt(h.error("Expected closure")),
+        },
+        Cont::ReduceFunc(compbody, cenv, rest) => State::Run(compbody, 0, h.cons(v, cenv), rest),
+        Cont::ToQuo(rest) => match h.val(v) {
+            Val::Fut(normcode) => {
+                let normcode = normcode.clone();
+                State::Apply(*rest, h.quo(normcode))
+            }
+            _ => State::Halt(h.error("Expected future code fragment")),
+        },
+        Cont::RedQuo(rest) => match h.val(v) {
+            Val::Quo(normcode) => {
+                let normcode = normcode.clone();
+                State::Apply(*rest, h.fut(normcode))
+            }
+            _ => State::Halt(h.error("Expected quoted code")),
+        },
+        Cont::FutLam(rest) => match h.val(v) {
+            Val::Fut(normcode) => {
+                let code = ast::lam(normcode.clone());
+                State::Apply(*rest, h.fut(code))
+            }
+            _ => State::Halt(h.error("Expected future code fragment")),
+        },
+        Cont::FutAppArg(lev, comparg, env, cont) => match h.val(v) {
+            Val::Fut(funcnorm) => State::Run(
+                comparg,
+                lev,
+                env,
+                stats::frame(Cont::FutApp(funcnorm.clone(), cont)),
+            ),
+            _ => State::Halt(h.error("Expected future code fragment")),
+        },
+        Cont::FutApp(funcnorm, rest) => match h.val(v) {
+            Val::Fut(argnorm) => {
+                let code = ast::app(funcnorm, argnorm.clone());
+                State::Apply(*rest, h.fut(code))
+            }
+            _ => State::Halt(h.error("Expected future code fragment")),
+        },
+        Cont::FutQuo(rest) => match h.val(v) {
+            Val::Fut(codev) => {
+                let code = ast::quo(codev.clone());
+                State::Apply(*rest, h.fut(code))
+            }
+            _ => State::Halt(h.error("Expected future code fragment")),
+        },
+        Cont::FutUnq(rest) => match h.val(v) {
+            Val::Fut(codev) => {
+                let code = ast::unq(codev.clone());
+                State::Apply(*rest, h.fut(code))
+            }
+            _ => State::Halt(h.error("Expected future code fragment")),
+        },
+    }
+}
+
+fn exec(
+    h: &mut Heap,
+    globals: &[Ptr],
+    instrs: Rc<InstrSeq>,
+    lev: i32,
+    env: Ptr,
+    cont: Box<Cont>,
+) -> State {
+    match &*instrs {
+        InstrSeq::Seq(Instr::Lit(i), _) => match lev {
+            0 => State::Apply(*cont, h.int(*i)),
+            _ => State::Apply(*cont, h.fut(ast::int(*i))),
+        },
+        InstrSeq::Seq(Instr::Var(idx), _) => match lev {
+            0 => match h.lookup(env, *idx) {
+                Some(v) => State::Apply(*cont, v),
+                None => State::Halt(h.error("Undefined variable")),
+            },
+            _ => State::Apply(*cont, h.fut(ast::var(*idx))),
+        },
+        InstrSeq::Seq(Instr::Clos(body), _) => match lev {
+            0 => State::Apply(*cont, h.clos(env, body.clone())),
+            lev => State::Run(body.clone(), lev, env, stats::frame(Cont::FutLam(cont))),
+        },
+        InstrSeq::Seq(Instr::Push(func), arg) => match lev {
+            0 => State::Run(
+                func.clone(),
+                0,
+                env,
+                stats::frame(Cont::EvalArg(arg.clone(), env, cont)),
+            ),
+            lev => State::Run(
+                func.clone(),
+                lev,
+                env,
+                stats::frame(Cont::FutAppArg(lev, arg.clone(), env, cont)),
+            ),
+        },
+        InstrSeq::Seq(Instr::Ent, code) => match lev {
+            0 => State::Run(code.clone(), 1, env, stats::frame(Cont::ToQuo(cont))),
+            lev => State::Run(code.clone(), lev + 1, env, stats::frame(Cont::FutQuo(cont))),
+        },
+        InstrSeq::Seq(Instr::Leave, code) => match lev {
+            0 => State::Halt(h.error("Top-level unquote is NOT allowed")),
+            1 => State::Run(code.clone(), 0, env, stats::frame(Cont::RedQuo(cont))),
+            lev => State::Run(code.clone(), lev - 1, env, stats::frame(Cont::FutUnq(cont))),
+        },
+        InstrSeq::Seq(Instr::Global(idx), _) => match lev {
+            0 => match globals.get(*idx) {
+                Some(v) => State::Apply(*cont, *v),
+                None => State::Undefined(*idx),
+            },
+            _ => State::Apply(*cont, h.fut(ast::global(*idx))),
+        },
+        InstrSeq::End => State::Halt(h.error("Reached end of instruction sequence, unexpectedly")),
+    }
+}
+
+// `fuel` bounds the number of instructions executed, `None` means no
+// limit. The heap is collected between instructions, when it has grown
+// past the threshold; the threshold doubles if that frees too little.
+// Pointers held only by the caller are not roots, and may be freed.
+pub fn run_state(m: &mut Machine, state: State, fuel: Option<usize>) -> Outcome {
+    let mut state = state;
+    let mut fuel = fuel;
+    loop {
+        state = match state {
+            State::Halt(v) => return Outcome::Done(v),
+            State::Undefined(idx) => return Outcome::Undefined(idx),
+            State::Run(..) if fuel == Some(0) => return Outcome::OutOfFuel(state),
+            State::Run(instrs, lev, env, cont) => {
+                fuel = fuel.map(|fuel| fuel - 1);
+                exec(&mut m.heap, &m.globals, instrs, lev, env, cont)
+            }
+            State::Apply(cont, v) => apply_cont(&mut m.heap, cont, v),
+        };
+        if m.heap.live() >= m.gc_threshold {
+            m.collect(&state);
+            if m.heap.live() * 2 >= m.gc_threshold {
+                m.gc_threshold *= 2;
+            }
+        }
+    }
+}
+
+pub fn run(m: &mut Machine, instrs: Rc<InstrSeq>, lev: i32, env: Ptr, cont: Box<Cont>) -> Ptr {
+    match run_state(m, State::Run(instrs, lev, env, cont), None) {
+        Outcome::Done(v) => v,
+        Outcome::Undefined(_) => m.heap.error("Undefined global"),
+        Outcome::OutOfFuel(_) => unreachable!(),
+    }
+}
+
+// Compile and run the top-level definitions of a program in order. A
+// definition can use later ones only under a lambda; fails with the index
+// of one that needs a later one.
+pub fn define_globals(m: &mut Machine, defs: &[Rc<Ast>]) -> Result<(), usize> {
+    m.globals.clear();
+    for (i, def) in defs.iter().enumerate() {
+        let env = m.heap.nil();
+        let state = State::Run(compile(def.clone()), 0, env, Box::new(Cont::End));
+        match run_state(m, state, None) {
+            Outcome::Done(v) => m.globals.push(v),
+            Outcome::Undefined(_) => return Err(i),
+            Outcome::OutOfFuel(_) => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lamcirc::eval_vm_gc::data::ast::*;
+
+    fn env1(m: &mut Machine) -> Ptr {
+        let (ten, twenty, nil) = (m.heap.int(10), m.heap.int(20), m.heap.nil());
+        let env = m.heap.cons(twenty, nil);
+        m.heap.cons(ten, env)
+    }
+
+    fn eval(m: &mut Machine, ast: Rc<Ast>) -> &Val {
+        let env = env1(m);
+        let v = run(m, compile(ast), 0, env, Box::new(Cont::End));
+        m.heap.val(v)
+    }
+
+    // (\x. x x) (\x. x x)
+    fn omega() -> Rc<Ast> {
+        app(lam(app(var(0), var(0))), lam(app(var(0), var(0))))
+    }
+
+    #[test]
+    fn eval_misc() {
+        let mut m = Machine::new();
+        assert_eq!(*eval(&mut m, int(1)), Val::Int(1));
+        assert_eq!(*eval(&mut m, var(1)), Val::Int(20));
+        assert_eq!(
+            *eval(&mut m, app(app(lam(lam(var(1))), int(33)), int(44))),
+            Val::Int(33)
+        );
+        assert_eq!(
+            *eval(&mut m, app(lam(quo(unq(var(0)))), quo(int(1)))),
+            Val::Quo(int(1))
+        );
+        assert_eq!(
+            *eval(&mut m, quo(quo(unq(unq(quo(var(1))))))),
+            Val::Quo(quo(unq(var(1))))
+        );
+        assert_eq!(
+            *eval(&mut m, unq(int(1))),
+            Val::Error(String::from("Top-level unquote is NOT allowed"))
+        );
+    }
+
+    #[test]
+    fn eval_in_bounded_heap() {
+        // omega allocates an environment per step but keeps few alive
+        let mut m = Machine::with_threshold(64);
+        let env = m.heap.nil();
+        let state = State::Run(compile(omega()), 0, env, Box::new(Cont::End));
+        assert!(matches!(
+            run_state(&mut m, state, Some(100_000)),
+            Outcome::OutOfFuel(_)
+        ));
+        assert!(m.collections > 100);
+        assert_eq!(m.gc_threshold, 64);
+        assert!(m.heap.live() < 64);
+    }
+
+    #[test]
+    fn eval_stats_bounded() {
+        // the same run allocates far more than is ever live at once
+        let mut m = Machine::with_threshold(64);
+        let env = m.heap.nil();
+        let code = compile(omega());
+        let state = State::Run(code, 0, env, Box::new(Cont::End));
+        let (_, stats) = stats::measure(|| run_state(&mut m, state, Some(10_000)));
+        assert!(stats.vals + stats.envs > 1000);
+        assert!(stats.peak_live <= 64);
+    }
+
+    #[test]
+    fn eval_globals_survive_collection() {
+        // g0 = \x. x, g1 = g0 5
+        let mut m = Machine::with_threshold(1 << 20);
+        define_globals(&mut m, &[lam(var(0)), app(global(0), int(5))]).unwrap();
+        // from a state without roots, the globals are left: the closure,
+        // its environment and 5
+        m.collect(&State::Undefined(0));
+        assert_eq!(m.heap.live(), 3);
+        let nil = m.heap.nil();
+        let v = run(
+            &mut m,
+            compile(app(global(0), global(1))),
+            0,
+            nil,
+            Box::new(Cont::End),
+        );
+        assert_eq!(*m.heap.val(v), Val::Int(5));
+    }
+
+    #[test]
+    fn eval_with_cyclic_closure() {
+        // f's environment holds f itself; f = \x. x, so f 5 = 5
+        let mut m = Machine::with_threshold(1 << 20);
+        let nil = m.heap.nil();
+        let env = m.heap.cons(nil, nil);
+        let f = m.heap.clos(env, compile(var(0)));
+        m.heap.set(env, Obj::Env(Env::Cons(f, nil)));
+        let v = run(
+            &mut m,
+            compile(app(var(0), int(5))),
+            0,
+            env,
+            Box::new(Cont::End),
+        );
+        assert_eq!(*m.heap.val(v), Val::Int(5));
+
+        // once only the result is a root, the knot is garbage
+        let live = m.heap.live();
+        assert_eq!(m.collect(&State::Halt(v)), live - 1);
+        assert_eq!(*m.heap.val(v), Val::Int(5));
+    }
+}
@@ -0,0 +1,191 @@
+use crate::lamcirc::eval_vm_gc::data::*;
+use crate::stats;
+use crate::stats::Kind;
+use std::rc::Rc;
+
+// An arena of slots. Objects refer to each other by index, so cycles are
+// no problem: `collect` marks everything reachable from the roots and
+// sweeps the rest onto the free list, which `alloc` reuses first.
+#[derive(Default)]
+pub struct Heap {
+    slots: Vec<Option<Obj>>,
+    free: Vec<usize>,
+}
+
+impl Heap {
+    pub fn new() -> Heap {
+        Heap::default()
+    }
+
+    pub fn alloc(&mut self, obj: Obj) -> Ptr {
+        stats::alloc(match obj {
+            Obj::Val(_) => Kind::Val,
+            Obj::Env(_) => Kind::Env,
+        });
+        match self.free.pop() {
+            Some(i) => {
+                self.slots[i] = Some(obj);
+                Ptr(i)
+            }
+            None => {
+                self.slots.push(Some(obj));
+                Ptr(self.slots.len() - 1)
+            }
+        }
+    }
+
+    pub fn get(&self, p: Ptr) -> &Obj {
+        self.slots[p.0].as_ref().expect("dangling pointer")
+    }
+
+    // Overwrite an object in place, e.g. to tie a recursive knot.
+    pub fn set(&mut self, p: Ptr, obj: Obj) {
+        assert!(self.slots[p.0].is_some(), "dangling pointer");
+        self.slots[p.0] = Some(obj);
+    }
+
+    pub fn val(&self, p: Ptr) -> &Val {
+        match self.get(p) {
+            Obj::Val(v) => v,
+            Obj::Env(_) => panic!("expected a value"),
+        }
+    }
+
+    pub fn env(&self, p: Ptr) -> &Env {
+        match self.get(p) {
+            Obj::Env(env) => env,
+            Obj::Val(_) => panic!("expected an environment"),
+        }
+    }
+
+    // Number of objects allocated and not yet collected.
+    pub fn live(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    // Free every object the roots do not reach, returning how many.
+    pub fn collect(&mut self, roots: &[Ptr]) -> usize {
+        let mut marked = vec![false; self.slots.len()];
+        let mut stack = roots.to_vec();
+        while let Some(p) = stack.pop() {
+            if marked[p.0] {
+                continue;
+            }
+            marked[p.0] = true;
+            match self.get(p) {
+                Obj::Val(Val::Clos(env, _)) => stack.push(*env),
+                Obj::Env(Env::Cons(v, rest)) => {
+                    stack.push(*v);
+                    stack.push(*rest);
+                }
+                _ => {}
+            }
+        }
+
+        let mut freed = 0;
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            if slot.is_some() && !marked[i] {
+                *slot = None;
+                self.free.push(i);
+                stats::free();
+                freed += 1;
+            }
+        }
+        freed
+    }
+
+    pub fn lookup(&self, env: Ptr, idx: usize) -> Option<Ptr> {
+        let mut env = env;
+        let mut idx = idx;
+        loop {
+            match self.env(env) {
+                Env::Nil => return None,
+                Env::Cons(head, _) if idx == 0 => return Some(*head),
+                Env::Cons(_, tail) => {
+                    env = *tail;
+                    idx -= 1;
+                }
+            }
+        }
+    }
+
+    // Constructors
+
+    pub fn error(&mut self, msg: &str) -> Ptr {
+        self.alloc(Obj::Val(Val::Error(String::from(msg))))
+    }
+
+    pub fn int(&mut self, i: i32) -> Ptr {
+        self.alloc(Obj::Val(Val::Int(i)))
+    }
+
+    pub fn clos(&mut self, env: Ptr, body: Rc<InstrSeq>) -> Ptr {
+        self.alloc(Obj::Val(Val::Clos(env, body)))
+    }
+
+    pub fn quo(&mut self, ast: Rc<Ast>) -> Ptr {
+        self.alloc(Obj::Val(Val::Quo(ast)))
+    }
+
+    pub fn fut(&mut self, ast: Rc<Ast>) -> Ptr {
+        self.alloc(Obj::Val(Val::Fut(ast)))
+    }
+
+    pub fn nil(&mut self) -> Ptr {
+        self.alloc(Obj::Env(Env::Nil))
+    }
+
+    pub fn cons(&mut self, v: Ptr, env: Ptr) -> Ptr {
+        self.alloc(Obj::Env(Env::Cons(v, env)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A closure whose environment holds the closure itself, as a
+    // recursive definition would build it.
+    fn knot(h: &mut Heap) -> (Ptr, Ptr) {
+        let nil = h.nil();
+        let placeholder = h.int(0);
+        let env = h.cons(placeholder, nil);
+        let f = h.clos(env, instrseq::singleton(instr::var(0)));
+        h.set(env, Obj::Env(Env::Cons(f, nil)));
+        (f, env)
+    }
+
+    #[test]
+    fn collect_unreachable() {
+        let mut h = Heap::new();
+        let one = h.int(1);
+        let two = h.int(2);
+        let env = h.nil();
+        let env = h.cons(two, env);
+        assert_eq!(h.live(), 4);
+        assert_eq!(h.collect(&[one, env]), 0);
+        assert_eq!(h.collect(&[one]), 3);
+        assert_eq!(h.live(), 1);
+        assert_eq!(*h.val(one), Val::Int(1));
+        // freed slots are reused
+        let three = h.int(3);
+        assert!(three.0 < 4);
+        assert_eq!(h.live(), 2);
+    }
+
+    #[test]
+    fn collect_cycles() {
+        let mut h = Heap::new();
+        let (f, env) = knot(&mut h);
+        assert_eq!(h.lookup(env, 0), Some(f));
+        assert_eq!(h.live(), 4); // nil, placeholder, env and f
+
+        // a reachable cycle is kept, and the placeholder is gone
+        assert_eq!(h.collect(&[f]), 1);
+        assert_eq!(h.lookup(env, 0), Some(f));
+
+        // an unreachable one is reclaimed, which counting references cannot
+        assert_eq!(h.collect(&[]), 3);
+        assert_eq!(h.live(), 0);
+    }
+}
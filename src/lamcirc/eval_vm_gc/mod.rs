@@ -0,0 +1,4 @@
+pub mod data;
+pub mod eval;
+pub mod heap;
+pub mod print;
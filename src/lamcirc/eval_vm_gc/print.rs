@@ -0,0 +1,64 @@
+use crate::lamcirc::eval_vm_gc::data::{Env, Obj, Ptr, Val};
+use crate::pretty;
+use crate::pretty::{Ctx, Term};
+use std::fmt;
+
+// Objects refer to each other by handle, which are printed as addresses;
+// the variables a closure takes from its environment stay unnamed.
+impl fmt::Display for Ptr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "@{}", self.0)
+    }
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Val::Error(msg) => write!(f, "error: {}", msg),
+            Val::Int(i) => write!(f, "{}", i),
+            Val::Clos(env, body) => {
+                write!(f, "{{")?;
+                pretty::lam(&**body, &mut Ctx::new(), 0, f)?;
+                write!(f, " | {}}}", env)
+            }
+            Val::Quo(code) => pretty::quo(&**code, &mut Ctx::new(), 0, f),
+            Val::Fut(code) => code.fmt_in(&mut Ctx::new(), 0, f),
+        }
+    }
+}
+
+impl fmt::Display for Env {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Env::Nil => write!(f, "[]"),
+            Env::Cons(v, rest) => write!(f, "{} :: {}", v, rest),
+        }
+    }
+}
+
+impl fmt::Display for Obj {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Obj::Val(v) => v.fmt(f),
+            Obj::Env(env) => env.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lamcirc::eval_vm_gc::data::instr;
+    use crate::lamcirc::eval_vm_gc::data::instrseq;
+    use crate::lamcirc::eval_vm_gc::heap::Heap;
+
+    #[test]
+    fn print_heap() {
+        let mut h = Heap::new();
+        let nil = h.nil();
+        let one = h.int(1);
+        let env = h.cons(one, nil);
+        let clos = h.clos(env, instrseq::singleton(instr::var(1)));
+        assert_eq!(h.get(env).to_string(), "@1 :: @0");
+        assert_eq!(h.get(clos).to_string(), "{\\x0. var #1 | @2}");
+    }
+}
@@ -2,3 +2,4 @@ pub mod eval_cek;
 pub mod eval_cps;
 pub mod eval_vm;
 pub mod eval_vm2;
+pub mod eval_vm_gc;
@@ -1,3 +1,8 @@
 pub mod arith;
 pub mod stlc;
-pub mod lamcirc;
\ No newline at end of file
+pub mod lamcirc;
+pub mod termgen;
+pub mod surface;
+pub mod stats;
+pub mod pretty;
+pub mod build;
@@ -1,23 +1,20 @@
-mod arith;
-mod stlc;
-
 use std::rc::Rc;
 
 fn main() {
     {
-        use crate::arith::ast::{int, add, mul};
-        use crate::arith::eval::eval;
+        use compiler_learning::arith::ast::{int, add, mul};
+        use compiler_learning::arith::eval::eval;
 
         let expr = mul(int(10),add(int(1), int(5)));
-        println!("l = {:?} evals to {}", expr, eval(&*expr))
+        println!("l = {} evals to {}", expr, eval(&*expr))
     }
 
     {
-        use crate::stlc::eval_cps::ast::{int, var, lam, app};
-        use crate::stlc::eval_cps::env::empty;
-        use crate::stlc::eval_cps::eval::eval;
+        use compiler_learning::stlc::eval_cps::ast::{int, var, lam, app};
+        use compiler_learning::stlc::eval_cps::env::empty;
+        use compiler_learning::stlc::eval_cps::eval::run;
 
         let expr = app(lam(var(0)), int(20));
-        println!("l = {:?}  evals to {:?}", Rc::clone(&expr), eval(expr, empty(), Box::new(|v| v)))
+        println!("l = {}  evals to {}", Rc::clone(&expr), run(expr, empty()))
     }
 }
@@ -0,0 +1,309 @@
+use std::fmt;
+
+// One notation for the terms, values and code of every evaluator: `\x0. e`
+// for lambdas, `<e>` and `~e` for quote and unquote, and names for de
+// Bruijn indices. The binder at position n of the context is called `xn`,
+// counting only binders of the same stage; unbound variables print as
+// `#idx`. Environments list their innermost entry first and elide the
+// entries past a depth, given as the precision (`{:.2}`) or `DEPTH`; the
+// values in them are printed with one less.
+
+pub const DEPTH: usize = 3;
+
+pub fn depth(f: &fmt::Formatter) -> usize {
+    f.precision().unwrap_or(DEPTH)
+}
+
+// How tightly a form binds, loosest first.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Copy, Debug)]
+pub enum Prec {
+    Open, // binders and infix operators, which extend as far as they can
+    App,  // applications, also of keywords like `fst p`
+    Atom,
+}
+
+// The stages of the binders in scope, outermost first.
+#[derive(Default)]
+pub struct Ctx {
+    binders: Vec<i32>,
+}
+
+impl Ctx {
+    pub fn new() -> Ctx {
+        Ctx::default()
+    }
+
+    // The context of `len` environment entries at stage 0.
+    pub fn with_env(len: usize) -> Ctx {
+        Ctx {
+            binders: vec![0; len],
+        }
+    }
+
+    pub fn var(&self, idx: usize, lev: i32) -> String {
+        let binder = (0..self.binders.len())
+            .rev()
+            .filter(|n| self.binders[*n] == lev)
+            .nth(idx);
+        match binder {
+            Some(n) => format!("x{}", n),
+            None => format!("#{}", idx),
+        }
+    }
+
+    // The name of the next variable bound.
+    pub fn next(&self) -> String {
+        format!("x{}", self.binders.len())
+    }
+
+    // Bind a variable, returning its name; undo with `pop`.
+    pub fn push(&mut self, lev: i32) -> String {
+        let x = self.next();
+        self.binders.push(lev);
+        x
+    }
+
+    pub fn pop(&mut self) {
+        self.binders.pop();
+    }
+}
+
+pub trait Term {
+    fn prec(&self) -> Prec;
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result;
+}
+
+// `t` where a form binding at least as tightly as `prec` is expected.
+pub fn fmt_at<T: Term + ?Sized>(
+    t: &T,
+    prec: Prec,
+    ctx: &mut Ctx,
+    lev: i32,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    if t.prec() < prec {
+        write!(f, "(")?;
+        t.fmt_in(ctx, lev, f)?;
+        write!(f, ")")
+    } else {
+        t.fmt_in(ctx, lev, f)
+    }
+}
+
+// `body` under a new binder, whose name `head` writes first.
+pub fn bind<T: Term + ?Sized>(
+    head: impl FnOnce(&str, &mut fmt::Formatter) -> fmt::Result,
+    body: &T,
+    ctx: &mut Ctx,
+    lev: i32,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    let x = ctx.push(lev);
+    let result = head(&x, f).and_then(|_| body.fmt_in(ctx, lev, f));
+    ctx.pop();
+    result
+}
+
+pub fn lam<T: Term + ?Sized>(
+    body: &T,
+    ctx: &mut Ctx,
+    lev: i32,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    bind(|x, f| write!(f, "\\{}. ", x), body, ctx, lev, f)
+}
+
+pub fn app<F: Term + ?Sized, A: Term + ?Sized>(
+    func: &F,
+    arg: &A,
+    ctx: &mut Ctx,
+    lev: i32,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    fmt_at(func, Prec::App, ctx, lev, f)?;
+    write!(f, " ")?;
+    fmt_at(arg, Prec::Atom, ctx, lev, f)
+}
+
+// A keyword applied to an argument, like `fst p`.
+pub fn keyword<A: Term + ?Sized>(
+    kw: &str,
+    arg: &A,
+    ctx: &mut Ctx,
+    lev: i32,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    write!(f, "{} ", kw)?;
+    fmt_at(arg, Prec::Atom, ctx, lev, f)
+}
+
+pub fn infix<L: Term + ?Sized, R: Term + ?Sized>(
+    lhs: &L,
+    op: &str,
+    rhs: &R,
+    ctx: &mut Ctx,
+    lev: i32,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    fmt_at(lhs, Prec::App, ctx, lev, f)?;
+    write!(f, " {} ", op)?;
+    fmt_at(rhs, Prec::App, ctx, lev, f)
+}
+
+pub fn pair<A: Term + ?Sized, B: Term + ?Sized>(
+    fst: &A,
+    snd: &B,
+    ctx: &mut Ctx,
+    lev: i32,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    write!(f, "(")?;
+    fst.fmt_in(ctx, lev, f)?;
+    write!(f, ", ")?;
+    snd.fmt_in(ctx, lev, f)?;
+    write!(f, ")")
+}
+
+// Both branches bind the payload.
+pub fn case<S: Term + ?Sized, B: Term + ?Sized>(
+    scrut: &S,
+    left: &B,
+    right: &B,
+    ctx: &mut Ctx,
+    lev: i32,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    write!(f, "case ")?;
+    scrut.fmt_in(ctx, lev, f)?;
+    bind(|x, f| write!(f, " of inl {} -> ", x), left, ctx, lev, f)?;
+    bind(|x, f| write!(f, " | inr {} -> ", x), right, ctx, lev, f)
+}
+
+pub fn if_<C: Term + ?Sized, B: Term + ?Sized>(
+    cond: &C,
+    then: &B,
+    els: &B,
+    ctx: &mut Ctx,
+    lev: i32,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    write!(f, "if ")?;
+    cond.fmt_in(ctx, lev, f)?;
+    write!(f, " then ")?;
+    then.fmt_in(ctx, lev, f)?;
+    write!(f, " else ")?;
+    els.fmt_in(ctx, lev, f)
+}
+
+pub fn quo<T: Term + ?Sized>(
+    body: &T,
+    ctx: &mut Ctx,
+    lev: i32,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    write!(f, "<")?;
+    body.fmt_in(ctx, lev + 1, f)?;
+    write!(f, ">")
+}
+
+pub fn unq<T: Term + ?Sized>(
+    body: &T,
+    ctx: &mut Ctx,
+    lev: i32,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    write!(f, "~")?;
+    fmt_at(body, Prec::Atom, ctx, lev - 1, f)
+}
+
+// Instructions separated by `;`.
+pub fn seq<I>(
+    instrs: &[I],
+    ctx: &mut Ctx,
+    f: &mut fmt::Formatter,
+    mut fmt_instr: impl FnMut(&I, &mut Ctx, &mut fmt::Formatter) -> fmt::Result,
+) -> fmt::Result {
+    for (i, instr) in instrs.iter().enumerate() {
+        if i > 0 {
+            write!(f, "; ")?;
+        }
+        fmt_instr(instr, ctx, f)?;
+    }
+    Ok(())
+}
+
+// The entries of an environment, innermost first, up to the depth.
+fn entries<V: fmt::Display + ?Sized>(
+    env: &[&V],
+    named: bool,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    let depth = depth(f);
+    for (i, v) in env.iter().take(depth).enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        if named {
+            write!(f, "x{} = ", env.len() - 1 - i)?;
+        }
+        write!(f, "{:.*}", depth - 1, v)?;
+    }
+    if env.len() > depth {
+        write!(f, "{}..", if depth > 0 { ", " } else { "" })?;
+    }
+    Ok(())
+}
+
+pub fn env<V: fmt::Display + ?Sized>(env: &[&V], f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "[")?;
+    entries(env, false, f)?;
+    write!(f, "]")
+}
+
+// Code with its environment, `{\x2. x2 x1 | x1 = 10, x0 = 20}`: the
+// entries are named as the binders around the code.
+pub fn closure<V: fmt::Display + ?Sized>(
+    env: &[&V],
+    f: &mut fmt::Formatter,
+    code: impl FnOnce(&mut Ctx, &mut fmt::Formatter) -> fmt::Result,
+) -> fmt::Result {
+    write!(f, "{{")?;
+    code(&mut Ctx::with_env(env.len()), f)?;
+    if env.is_empty() {
+        return write!(f, "}}");
+    }
+    write!(f, " | ")?;
+    entries(env, true, f)?;
+    write!(f, "}}")
+}
+
+// Display through `Term` for the given types.
+macro_rules! display {
+    ($($ty:ident),+) => {
+        $(impl std::fmt::Display for $ty {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                crate::pretty::Term::fmt_in(self, &mut crate::pretty::Ctx::new(), 0, f)
+            }
+        })+
+    };
+}
+
+pub(crate) use display;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_by_stage() {
+        let mut ctx = Ctx::with_env(2);
+        assert_eq!(ctx.push(1), "x2");
+        assert_eq!(ctx.next(), "x3");
+        assert_eq!(ctx.var(0, 0), "x1");
+        assert_eq!(ctx.var(1, 0), "x0");
+        assert_eq!(ctx.var(0, 1), "x2");
+        assert_eq!(ctx.var(1, 1), "#1");
+        ctx.pop();
+        assert_eq!(ctx.var(2, 0), "#2");
+    }
+}
@@ -0,0 +1,359 @@
+use std::rc::Rc;
+
+// Allocation counts of the evaluators, collected only while `measure`
+// runs. The constructors of each backend report what they allocate, so an
+// object behind `Rc` is counted once however often it is shared. Frames
+// are those of the explicit continuations and stacks; evaluators that
+// recurse on the Rust stack allocate none. Objects on the heap of
+// `eval_vm_gc` are live until they are collected.
+#[derive(Eq, PartialEq, Debug, Default, Clone, Copy)]
+pub struct Stats {
+    pub vals: usize,
+    pub envs: usize,
+    pub conts: usize,     // continuation frames
+    pub code: usize,      // compiled code and terms built while evaluating
+    pub peak_live: usize, // most values, environments and code alive at once
+    pub live: usize,      // those still alive at the end, as the result
+}
+
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Kind {
+    Val,
+    Env,
+    Cont,
+    Code,
+}
+
+// Counting costs a thread-local lookup per allocation, so it is only
+// compiled in with the `stats` feature, and for the unit tests; otherwise
+// `measure` reports nothing.
+#[cfg(any(test, feature = "stats"))]
+mod counter {
+    use super::{Kind, Stats};
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct Counter {
+        stats: Stats,
+        live: isize, // relative to the start, as older objects may be freed
+    }
+
+    thread_local! {
+        static COUNTER: RefCell<Option<Counter>> = const { RefCell::new(None) };
+    }
+
+    // Count an allocation of an object that does not report being freed,
+    // like a continuation frame, which is moved out of when popped.
+    pub fn count(kind: Kind) {
+        COUNTER.with(|counter| {
+            if let Some(c) = counter.borrow_mut().as_mut() {
+                match kind {
+                    Kind::Val => c.stats.vals += 1,
+                    Kind::Env => c.stats.envs += 1,
+                    Kind::Cont => c.stats.conts += 1,
+                    Kind::Code => c.stats.code += 1,
+                }
+            }
+        })
+    }
+
+    // Count an allocation of an object of a `counted!` type, which is live
+    // until it is dropped.
+    pub fn alloc(kind: Kind) {
+        count(kind);
+        COUNTER.with(|counter| {
+            if let Some(c) = counter.borrow_mut().as_mut() {
+                c.live += 1;
+                c.stats.peak_live = c.stats.peak_live.max(c.live.max(0) as usize);
+            }
+        })
+    }
+
+    pub fn free() {
+        // the counter may already be gone when a thread shuts down
+        let _ = COUNTER.try_with(|counter| {
+            if let Some(c) = counter.borrow_mut().as_mut() {
+                c.live -= 1;
+            }
+        });
+    }
+
+    // Run `f`, counting what it allocates. Build the input before, so that
+    // only the evaluation itself is counted.
+    pub fn measure<T>(f: impl FnOnce() -> T) -> (T, Stats) {
+        let outer = COUNTER.with(|counter| counter.replace(Some(Counter::default())));
+        let result = f();
+        let counter = COUNTER.with(|counter| counter.replace(outer));
+        let stats = counter.map(|c| Stats {
+            live: c.live.max(0) as usize,
+            ..c.stats
+        });
+        (result, stats.unwrap_or_default())
+    }
+}
+
+#[cfg(not(any(test, feature = "stats")))]
+mod counter {
+    use super::{Kind, Stats};
+
+    #[inline]
+    pub fn count(_kind: Kind) {}
+
+    #[inline]
+    pub fn alloc(_kind: Kind) {}
+
+    #[inline]
+    pub fn free() {}
+
+    pub fn measure<T>(f: impl FnOnce() -> T) -> (T, Stats) {
+        (f(), Stats::default())
+    }
+}
+
+pub use counter::{alloc, count, free, measure};
+
+// A continuation frame on the heap.
+pub fn frame<T>(frame: T) -> Box<T> {
+    count(Kind::Cont);
+    Box::new(frame)
+}
+
+pub fn shared_frame<T>(frame: T) -> Rc<T> {
+    count(Kind::Cont);
+    Rc::new(frame)
+}
+
+// Objects of these types report being freed.
+macro_rules! counted {
+    ($($ty:ident $(<$param:ident>)?),+) => {
+        $(impl$(<$param>)? Drop for $ty$(<$param>)? {
+            fn drop(&mut self) {
+                crate::stats::free();
+            }
+        })+
+    };
+}
+
+pub(crate) use counted;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::termgen::build::build;
+    use crate::termgen::data::{term::*, ty, Term};
+
+    #[test]
+    fn measure_counts() {
+        let ((), stats) = measure(|| {
+            alloc(Kind::Val);
+            alloc(Kind::Env);
+            free();
+            alloc(Kind::Val);
+            count(Kind::Cont);
+            let ((), inner) = measure(|| alloc(Kind::Code));
+            assert_eq!(inner.code, 1);
+        });
+        assert_eq!(
+            stats,
+            Stats {
+                vals: 2,
+                envs: 1,
+                conts: 1,
+                code: 0,
+                peak_live: 2,
+                live: 2
+            }
+        );
+        // nothing is counted outside of `measure`
+        alloc(Kind::Val);
+        assert_eq!(measure(|| ()).1, Stats::default());
+    }
+
+    type Backend = fn(&Rc<Term>) -> (String, Stats);
+
+    // Every instrumented backend but `eval_vm_gc`, whose objects live on
+    // until they are collected. Inputs are built outside of `measure` and
+    // kept alive over it, the compilers run inside.
+    fn backends() -> Vec<(&'static str, Backend)> {
+        use crate::lamcirc::{eval_cek, eval_cps as lamcirc_cps, eval_vm, eval_vm2};
+        use crate::stlc::eval_defunct1::eval::{eval_with, Cont, Machine, Strategy};
+        use crate::stlc::eval_defunct2::{bytecode, eval as eval_defunct2};
+        use crate::stlc::{anf, closconv, eval_cps, eval_defunct1, eval_defunct2 as d2};
+        use crate::stlc::{eval_krivine, eval_secd, nbe};
+
+        fn defunct1(t: &Rc<Term>, strategy: Strategy) -> (String, Stats) {
+            let ast = build::<eval_defunct1::ast::Ast>(t);
+            let env = eval_defunct1::env::empty();
+            let mut m = Machine::new(strategy);
+            let (v, stats) = measure(|| eval_with(&mut m, ast.clone(), env.clone(), Cont::Cont0));
+            (v.to_string(), stats)
+        }
+
+        vec![
+            ("stlc eval_cps", |t| {
+                let (ast, env) = (build::<eval_cps::ast::Ast>(t), eval_cps::env::empty());
+                let (v, stats) = measure(|| eval_cps::eval::run(ast.clone(), env.clone()));
+                (v.to_string(), stats)
+            }),
+            ("eval_defunct1 by value", |t| {
+                defunct1(t, Strategy::CallByValue)
+            }),
+            ("eval_defunct1 by name", |t| {
+                defunct1(t, Strategy::CallByName)
+            }),
+            ("eval_defunct1 by need", |t| {
+                defunct1(t, Strategy::CallByNeed)
+            }),
+            ("eval_defunct2", |t| {
+                let (ast, env) = (build::<d2::data::Ast>(t), d2::data::env::empty());
+                let (v, stats) = measure(|| {
+                    eval_defunct2::apply_compt(
+                        eval_defunct2::eval(ast.clone()),
+                        env.clone(),
+                        d2::data::Cont::Cont0,
+                    )
+                });
+                (v.to_string(), stats)
+            }),
+            ("bytecode", |t| {
+                let (ast, env) = (build::<d2::data::Ast>(t), bytecode::env::empty());
+                let (v, stats) = measure(|| {
+                    bytecode::run(
+                        &bytecode::lower(&eval_defunct2::eval(ast.clone())),
+                        env.clone(),
+                    )
+                });
+                (v.to_string(), stats)
+            }),
+            ("krivine", |t| {
+                let (ast, env) = (
+                    build::<eval_defunct1::ast::Ast>(t),
+                    eval_defunct1::env::empty(),
+                );
+                let (v, stats) = measure(|| eval_krivine::eval::eval(ast.clone(), env.clone()));
+                (v.to_string(), stats)
+            }),
+            ("zam", |t| {
+                let ast: Rc<eval_defunct1::ast::Ast> = build(t);
+                let env = eval_defunct1::env::empty();
+                let (v, stats) = measure(|| {
+                    eval_krivine::zam::run(eval_krivine::zam::compile(&ast), env.clone())
+                });
+                (v.to_string(), stats)
+            }),
+            ("secd", |t| {
+                let (ast, env) = (
+                    build::<eval_secd::data::Ast>(t),
+                    eval_secd::data::env::empty(),
+                );
+                let (v, stats) = measure(|| eval_secd::eval::eval(&ast, env.clone()));
+                (v.to_string(), stats)
+            }),
+            ("nbe", |t| {
+                let ast = build::<nbe::data::Ast>(t);
+                let (v, stats) = measure(|| nbe::eval::normalize(ast.clone()));
+                (v.map_or(String::from("error"), |v| v.to_string()), stats)
+            }),
+            ("closconv", |t| {
+                let ast = build::<closconv::data::Ast>(t);
+                let (v, stats) =
+                    measure(|| closconv::eval::run(&closconv::convert::convert(ast.clone()), &[]));
+                (v.to_string(), stats)
+            }),
+            ("anf", |t| {
+                let (ast, env) = (build::<anf::data::Ast>(t), anf::data::env::empty());
+                let (v, stats) =
+                    measure(|| anf::eval::eval(anf::convert::convert(&ast), env.clone()));
+                (v.to_string(), stats)
+            }),
+            ("lamcirc eval_cek", |t| {
+                let (ast, env) = (
+                    build::<eval_cek::data::Ast>(t),
+                    eval_cek::data::env::empty(),
+                );
+                let (v, stats) = measure(|| {
+                    eval_cek::eval::eval(0, ast.clone(), env.clone(), eval_cek::eval::Cont::End)
+                });
+                (v.to_string(), stats)
+            }),
+            ("lamcirc eval_cps", |t| {
+                let (ast, env) = (
+                    build::<lamcirc_cps::data::Ast>(t),
+                    lamcirc_cps::data::env::empty(),
+                );
+                let (v, stats) = measure(|| {
+                    lamcirc_cps::eval::eval(0, ast.clone(), env.clone(), Box::new(|v| v))
+                });
+                (v.to_string(), stats)
+            }),
+            ("lamcirc eval_vm", |t| {
+                let (ast, env) = (build::<eval_vm::data::Ast>(t), eval_vm::data::env::empty());
+                let (v, stats) = measure(|| {
+                    let code = eval_vm::eval::compile(ast.clone());
+                    eval_vm::eval::run(code, 0, env.clone(), Box::new(eval_vm::data::Cont::End))
+                });
+                (v.to_string(), stats)
+            }),
+            ("lamcirc eval_vm2", |t| {
+                let (ast, env) = (
+                    build::<eval_vm2::data::Ast>(t),
+                    eval_vm2::data::env::empty(),
+                );
+                let (v, stats) = measure(|| {
+                    let code = eval_vm2::eval::compile(ast.clone());
+                    eval_vm2::eval::run(code, 0, env.clone(), Box::new(eval_vm2::data::Cont::End))
+                });
+                (v.to_string(), stats)
+            }),
+        ]
+    }
+
+    // When a run returns, only its result is alive, and at no point was
+    // more alive than was allocated.
+    #[test]
+    fn peak_live_per_backend() {
+        let arrow = || ty::arrow(ty::int(), ty::int());
+        // (\f. \x. f (f (f x)))
+        let three = lam(
+            arrow(),
+            lam(ty::int(), app(var(1), app(var(1), app(var(1), var(0))))),
+        );
+        let terms = vec![
+            (int(7), "7"),
+            // (\x. \y. x) 33 44
+            (
+                app(
+                    app(lam(ty::int(), lam(ty::int(), var(1))), int(33)),
+                    int(44),
+                ),
+                "33",
+            ),
+            // (\n. n (\x. x) 7) three
+            (
+                app(
+                    lam(
+                        ty::arrow(arrow(), arrow()),
+                        app(app(var(0), lam(ty::int(), var(0))), int(7)),
+                    ),
+                    three,
+                ),
+                "7",
+            ),
+        ];
+        for (name, backend) in backends() {
+            for (t, expected) in &terms {
+                let (v, stats) = backend(t);
+                assert_eq!(v, *expected, "{}", name);
+                // only the integer result
+                assert_eq!(stats.live, 1, "{}: {:?}", name, stats);
+                assert!(stats.peak_live >= stats.live, "{}: {:?}", name, stats);
+                assert!(
+                    stats.peak_live <= stats.vals + stats.envs + stats.code,
+                    "{}: {:?}",
+                    name,
+                    stats
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use crate::stlc::anf::data::*;
+use crate::stlc::lambda::free_vars;
+use std::rc::Rc;
+
+// Conversion works on named binders, since a `Let` introduced for an inner
+// application shifts every variable around it; names are turned into de
+// Bruijn indices once the whole term is built.
+enum NAtom {
+    Int(i32),
+    Var(usize),
+    Lam(usize, Box<NExpr>),
+}
+
+enum NExpr {
+    Ret(NAtom),
+    App(NAtom, NAtom),
+    Let(usize, NAtom, NAtom, Box<NExpr>),
+}
+
+struct Converter {
+    next: usize,
+    ctx: Vec<usize>, // names of the source variables, innermost last
+}
+
+impl Converter {
+    fn fresh(&mut self) -> usize {
+        self.next += 1;
+        self.next - 1
+    }
+
+    // Reduce `ast` to an atom, queueing the applications it needs.
+    fn atom(&mut self, ast: &Ast, binds: &mut Vec<(usize, NAtom, NAtom)>) -> NAtom {
+        match ast {
+            Ast::Int(i) => NAtom::Int(*i),
+            Ast::Var(idx) => NAtom::Var(self.ctx[self.ctx.len() - 1 - idx]),
+            Ast::Lam(body) => {
+                let x = self.fresh();
+                self.ctx.push(x);
+                let body = self.expr(body);
+                self.ctx.pop();
+                NAtom::Lam(x, Box::new(body))
+            }
+            Ast::App(func, arg) => {
+                let funcv = self.atom(func, binds);
+                let argv = self.atom(arg, binds);
+                let x = self.fresh();
+                binds.push((x, funcv, argv));
+                NAtom::Var(x)
+            }
+        }
+    }
+
+    fn expr(&mut self, ast: &Ast) -> NExpr {
+        let mut binds = vec![];
+        let mut tail = match ast {
+            Ast::App(func, arg) => {
+                let funcv = self.atom(func, &mut binds);
+                let argv = self.atom(arg, &mut binds);
+                NExpr::App(funcv, argv)
+            }
+            _ => NExpr::Ret(self.atom(ast, &mut binds)),
+        };
+        while let Some((x, funcv, argv)) = binds.pop() {
+            tail = NExpr::Let(x, funcv, argv, Box::new(tail));
+        }
+        tail
+    }
+}
+
+fn index(scope: &[usize], name: usize) -> usize {
+    let pos = scope.iter().rposition(|n| *n == name).unwrap();
+    scope.len() - 1 - pos
+}
+
+fn to_atom(a: &NAtom, scope: &mut Vec<usize>) -> Rc<Atom> {
+    match a {
+        NAtom::Int(i) => atom::int(*i),
+        NAtom::Var(name) => atom::var(index(scope, *name)),
+        NAtom::Lam(x, body) => {
+            scope.push(*x);
+            let body = to_expr(body, scope);
+            scope.pop();
+            atom::lam(body)
+        }
+    }
+}
+
+fn to_expr(e: &NExpr, scope: &mut Vec<usize>) -> Rc<Expr> {
+    match e {
+        NExpr::Ret(a) => expr::ret(to_atom(a, scope)),
+        NExpr::App(func, arg) => expr::app(to_atom(func, scope), to_atom(arg, scope)),
+        NExpr::Let(x, func, arg, body) => {
+            let funcv = to_atom(func, scope);
+            let argv = to_atom(arg, scope);
+            scope.push(*x);
+            let body = to_expr(body, scope);
+            scope.pop();
+            expr::let_app(funcv, argv, body)
+        }
+    }
+}
+
+// Name every intermediate application result, evaluating left to right
+// as the call-by-value evaluators do. Free variables keep their indices.
+pub fn convert(ast: &Ast) -> Rc<Expr> {
+    let nfree = free_vars(ast);
+    let mut scope: Vec<usize> = (0..nfree).rev().collect();
+    let mut conv = Converter {
+        next: nfree,
+        ctx: scope.clone(),
+    };
+    let e = conv.expr(ast);
+    to_expr(&e, &mut scope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stlc::anf::data::ast::*;
+
+    #[test]
+    fn convert_atoms() {
+        assert_eq!(convert(&int(1)), expr::ret(atom::int(1)));
+        assert_eq!(convert(&var(1)), expr::ret(atom::var(1)));
+        assert_eq!(
+            convert(&lam(app(var(0), var(1)))),
+            expr::ret(atom::lam(expr::app(atom::var(0), atom::var(1))))
+        );
+    }
+
+    #[test]
+    fn convert_nested_apps() {
+        // f (g x) => let y = g x in f y
+        assert_eq!(
+            convert(&app(var(0), app(var(1), var(2)))),
+            expr::let_app(
+                atom::var(1),
+                atom::var(2),
+                expr::app(atom::var(1), atom::var(0))
+            )
+        );
+        // (f x) (g y) => let a = f x in let b = g y in a b
+        assert_eq!(
+            convert(&app(app(var(0), var(1)), app(var(2), var(3)))),
+            expr::let_app(
+                atom::var(0),
+                atom::var(1),
+                expr::let_app(
+                    atom::var(3),
+                    atom::var(4),
+                    expr::app(atom::var(1), atom::var(0))
+                )
+            )
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use crate::stats;
+use crate::stats::Kind;
+use std::rc::Rc;
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Ast {
+    Int(i32),
+    Var(usize),
+    Lam(Rc<Ast>),
+    App(Rc<Ast>, Rc<Ast>),
+}
+
+// Trivial expressions: evaluating them never calls a function.
+#[derive(Eq, PartialEq, Debug)]
+pub enum Atom {
+    Int(i32),
+    Var(usize),
+    Lam(Rc<Expr>),
+}
+
+// A-normal form: applications only take atoms, and every non-tail
+// application result is named by a `Let`, which binds variable 0 in its body.
+#[derive(Eq, PartialEq, Debug)]
+pub enum Expr {
+    Ret(Rc<Atom>),
+    App(Rc<Atom>, Rc<Atom>),           // tail call
+    Let(Rc<Atom>, Rc<Atom>, Rc<Expr>), // let x = f a in body
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Val {
+    Error,
+    Int(i32),
+    Clos(Rc<Env>, Rc<Expr>),
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Env {
+    Nil,
+    Cons(Rc<Val>, Rc<Env>),
+}
+
+stats::counted!(Atom, Expr, Val, Env);
+
+pub mod ast {
+    use super::*;
+
+    pub fn int(i: i32) -> Rc<Ast> {
+        Rc::new(Ast::Int(i))
+    }
+
+    pub fn var(i: usize) -> Rc<Ast> {
+        Rc::new(Ast::Var(i))
+    }
+
+    pub fn lam(a: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Lam(a))
+    }
+
+    pub fn app(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::App(a1, a2))
+    }
+}
+
+pub mod atom {
+    use super::*;
+
+    fn alloc(a: Atom) -> Rc<Atom> {
+        stats::alloc(Kind::Code);
+        Rc::new(a)
+    }
+
+    pub fn int(i: i32) -> Rc<Atom> {
+        alloc(Atom::Int(i))
+    }
+
+    pub fn var(i: usize) -> Rc<Atom> {
+        alloc(Atom::Var(i))
+    }
+
+    pub fn lam(body: Rc<Expr>) -> Rc<Atom> {
+        alloc(Atom::Lam(body))
+    }
+}
+
+pub mod expr {
+    use super::*;
+
+    fn alloc(e: Expr) -> Rc<Expr> {
+        stats::alloc(Kind::Code);
+        Rc::new(e)
+    }
+
+    pub fn ret(a: Rc<Atom>) -> Rc<Expr> {
+        alloc(Expr::Ret(a))
+    }
+
+    pub fn app(func: Rc<Atom>, arg: Rc<Atom>) -> Rc<Expr> {
+        alloc(Expr::App(func, arg))
+    }
+
+    pub fn let_app(func: Rc<Atom>, arg: Rc<Atom>, body: Rc<Expr>) -> Rc<Expr> {
+        alloc(Expr::Let(func, arg, body))
+    }
+}
+
+pub mod val {
+    use super::*;
+
+    fn alloc(v: Val) -> Rc<Val> {
+        stats::alloc(Kind::Val);
+        Rc::new(v)
+    }
+
+    pub fn error() -> Rc<Val> {
+        alloc(Val::Error)
+    }
+
+    pub fn int(i: i32) -> Rc<Val> {
+        alloc(Val::Int(i))
+    }
+
+    pub fn clos(env: Rc<Env>, body: Rc<Expr>) -> Rc<Val> {
+        alloc(Val::Clos(env, body))
+    }
+}
+
+pub mod env {
+    use super::*;
+
+    fn alloc(env: Env) -> Rc<Env> {
+        stats::alloc(Kind::Env);
+        Rc::new(env)
+    }
+
+    pub fn empty() -> Rc<Env> {
+        alloc(Env::Nil)
+    }
+
+    pub fn cons(elm: Rc<Val>, env: Rc<Env>) -> Rc<Env> {
+        alloc(Env::Cons(elm, env))
+    }
+
+    pub fn lookup(env: Rc<Env>, idx: usize) -> Option<Rc<Val>> {
+        match &*env {
+            Env::Nil => None,
+            Env::Cons(head, tail) => {
+                if idx == 0 {
+                    Some(head.clone())
+                } else {
+                    lookup(tail.clone(), idx - 1)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use crate::stlc::anf::data::*;
+use std::rc::Rc;
+
+fn eval_atom(a: &Atom, env: &Rc<Env>) -> Rc<Val> {
+    match a {
+        Atom::Int(i) => val::int(*i),
+        Atom::Var(idx) => env::lookup(env.clone(), *idx).unwrap_or_else(val::error),
+        Atom::Lam(body) => val::clos(env.clone(), body.clone()),
+    }
+}
+
+// Tail calls loop instead of recursing, so only `Let` grows the Rust stack.
+pub fn eval(e: Rc<Expr>, env: Rc<Env>) -> Rc<Val> {
+    let mut e = e;
+    let mut env = env;
+    loop {
+        match &*e.clone() {
+            Expr::Ret(a) => return eval_atom(a, &env),
+            Expr::App(func, arg) => {
+                let funcv = eval_atom(func, &env);
+                let argv = eval_atom(arg, &env);
+                match &*funcv {
+                    Val::Clos(env2, body) => {
+                        e = body.clone();
+                        env = env::cons(argv, env2.clone());
+                    }
+                    _ => return val::error(),
+                }
+            }
+            Expr::Let(func, arg, body) => {
+                let funcv = eval_atom(func, &env);
+                let argv = eval_atom(arg, &env);
+                let result = match &*funcv {
+                    Val::Clos(env2, fbody) => eval(fbody.clone(), env::cons(argv, env2.clone())),
+                    _ => val::error(),
+                };
+                e = body.clone();
+                env = env::cons(result, env);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stlc::anf::convert::convert;
+    use crate::stlc::anf::data::ast::*;
+    use crate::termgen::build::build;
+    use crate::termgen::data::Term;
+    use crate::termgen::shrink::check_agreement;
+
+    fn env1() -> Rc<Env> {
+        env::cons(val::int(10), env::cons(val::int(20), env::empty()))
+    }
+
+    #[test]
+    fn eval_literal() {
+        assert_eq!(eval(convert(&int(1)), env1()), val::int(1));
+        assert_eq!(eval(convert(&var(0)), env1()), val::int(10));
+        assert_eq!(eval(convert(&var(1)), env1()), val::int(20));
+        assert_eq!(
+            eval(
+                convert(&app(app(lam(lam(var(1))), int(33)), int(44))),
+                env1()
+            ),
+            val::int(33)
+        );
+        assert_eq!(
+            eval(convert(&app(lam(var(0)), app(lam(var(0)), var(1)))), env1()),
+            val::int(20)
+        );
+    }
+
+    // Same result as eval_defunct1 and eval_defunct2, up to the
+    // representation of closures.
+    fn agrees(t: &Term) -> bool {
+        use crate::stlc::eval_defunct1::{env as env1, eval::eval as eval1, eval::Cont, val as v1};
+        use crate::stlc::eval_defunct2::{data as d2, eval as eval2};
+
+        let expected = eval1(build(t), env1::empty(), Cont::Cont0);
+        let compt = eval2::eval(build(t));
+        let expected2 = eval2::apply_compt(compt, d2::env::empty(), d2::Cont::Cont0);
+        let actual = eval(convert(&build::<Ast>(t)), env::empty());
+        match (&*expected, &*expected2, &*actual) {
+            (v1::Val::Int(i), d2::Val::Int(j), Val::Int(k)) => i == k && j == k,
+            (v1::Val::Clos(_, _), d2::Val::Clos(_, _), Val::Clos(_, _)) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn eval_matches_other_evaluators() {
+        check_agreement(false, 500, |t| agrees(t));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod convert;
+pub mod data;
+pub mod eval;
+pub mod print;
@@ -0,0 +1,113 @@
+use crate::pretty;
+use crate::pretty::{Ctx, Prec, Term};
+use crate::stlc::anf::data::{Ast, Atom, Env, Expr, Val};
+use std::fmt;
+
+impl Term for Ast {
+    fn prec(&self) -> Prec {
+        match self {
+            Ast::Int(_) | Ast::Var(_) => Prec::Atom,
+            Ast::Lam(_) => Prec::Open,
+            Ast::App(_, _) => Prec::App,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ast::Int(i) => write!(f, "{}", i),
+            Ast::Var(idx) => write!(f, "{}", ctx.var(*idx, lev)),
+            Ast::Lam(body) => pretty::lam(&**body, ctx, lev, f),
+            Ast::App(func, arg) => pretty::app(&**func, &**arg, ctx, lev, f),
+        }
+    }
+}
+
+impl Term for Atom {
+    fn prec(&self) -> Prec {
+        match self {
+            Atom::Int(_) | Atom::Var(_) => Prec::Atom,
+            Atom::Lam(_) => Prec::Open,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Atom::Int(i) => write!(f, "{}", i),
+            Atom::Var(idx) => write!(f, "{}", ctx.var(*idx, lev)),
+            Atom::Lam(body) => pretty::lam(&**body, ctx, lev, f),
+        }
+    }
+}
+
+impl Term for Expr {
+    fn prec(&self) -> Prec {
+        match self {
+            Expr::Ret(atom) => atom.prec(),
+            Expr::App(_, _) => Prec::App,
+            Expr::Let(_, _, _) => Prec::Open,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Ret(atom) => atom.fmt_in(ctx, lev, f),
+            Expr::App(func, arg) => pretty::app(&**func, &**arg, ctx, lev, f),
+            Expr::Let(func, arg, body) => {
+                // the call is outside the scope of the variable
+                write!(f, "let {} = ", ctx.next())?;
+                pretty::app(&**func, &**arg, ctx, lev, f)?;
+                pretty::bind(|_, f| write!(f, " in "), &**body, ctx, lev, f)
+            }
+        }
+    }
+}
+
+pretty::display!(Ast, Atom, Expr);
+
+fn entries(mut env: &Env) -> Vec<&Val> {
+    let mut entries = vec![];
+    while let Env::Cons(head, tail) = env {
+        entries.push(&**head);
+        env = tail;
+    }
+    entries
+}
+
+impl fmt::Display for Env {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pretty::env(&entries(self), f)
+    }
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Val::Error => write!(f, "error"),
+            Val::Int(i) => write!(f, "{}", i),
+            Val::Clos(env, body) => {
+                pretty::closure(&entries(env), f, |ctx, f| pretty::lam(&**body, ctx, 0, f))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stlc::anf::convert::convert;
+    use crate::stlc::anf::data::ast::*;
+    use crate::stlc::anf::data::{env, val};
+
+    #[test]
+    fn print_anf() {
+        // \f. \g. \x. f (g x)
+        let compose = lam(lam(lam(app(var(2), app(var(1), var(0))))));
+        assert_eq!(compose.to_string(), "\\x0. \\x1. \\x2. x0 (x1 x2)");
+        let e = convert(&compose);
+        assert_eq!(e.to_string(), "\\x0. \\x1. \\x2. let x3 = x1 x2 in x0 x3");
+        let clos = val::clos(env::cons(val::int(1), env::empty()), e);
+        assert_eq!(
+            clos.to_string(),
+            "{\\x1. \\x2. \\x3. \\x4. let x5 = x3 x4 in x2 x5 | x0 = 1}"
+        );
+    }
+}
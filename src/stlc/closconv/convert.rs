@@ -0,0 +1,126 @@
+use crate::stlc::closconv::data::*;
+use std::rc::Rc;
+
+// How the variables of the source term are reached in the target.
+enum Scope {
+    Main,             // variable i is field i of the initial environment
+    Code(Vec<usize>), // variable 0 is the argument, variable fv[j] + 1 is field j
+}
+
+fn access(scope: &Scope, idx: usize) -> Rc<Expr> {
+    match scope {
+        Scope::Main => expr::field(idx),
+        Scope::Code(_) if idx == 0 => expr::arg(),
+        Scope::Code(fv) => match fv.iter().position(|v| *v == idx - 1) {
+            Some(j) => expr::field(j),
+            None => unreachable!("free variable missing from the closure"),
+        },
+    }
+}
+
+// Free variables of a term under `depth` binders, in increasing order.
+fn free_vars(ast: &Ast, depth: usize, acc: &mut Vec<usize>) {
+    match ast {
+        Ast::Int(_) => {}
+        Ast::Var(idx) if *idx >= depth => {
+            if !acc.contains(&(idx - depth)) {
+                acc.push(idx - depth);
+            }
+        }
+        Ast::Var(_) => {}
+        Ast::Lam(body) => free_vars(body, depth + 1, acc),
+        Ast::App(func, arg) => {
+            free_vars(func, depth, acc);
+            free_vars(arg, depth, acc);
+        }
+    }
+}
+
+fn convert_in(ast: &Ast, scope: &Scope, codes: &mut Vec<Code>) -> Rc<Expr> {
+    match ast {
+        Ast::Int(i) => expr::int(*i),
+        Ast::Var(idx) => access(scope, *idx),
+        Ast::Lam(body) => {
+            let mut fv = Vec::new();
+            free_vars(body, 1, &mut fv);
+            fv.sort();
+            let fields = fv.iter().map(|v| access(scope, *v)).collect();
+            let nfields = fv.len();
+            let body = convert_in(body, &Scope::Code(fv), codes);
+            codes.push(Code { nfields, body });
+            expr::mk_clos(codes.len() - 1, fields)
+        }
+        Ast::App(func, arg) => expr::app(
+            convert_in(func, scope, codes),
+            convert_in(arg, scope, codes),
+        ),
+    }
+}
+
+// Closure conversion and lambda lifting in one pass: every lambda becomes
+// a top-level `Code` plus a `MkClos` capturing exactly its free variables.
+pub fn convert(ast: Rc<Ast>) -> Program {
+    let mut codes = Vec::new();
+    let main = convert_in(&ast, &Scope::Main, &mut codes);
+    Program { codes, main }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stlc::closconv::data::ast::*;
+
+    #[test]
+    fn convert_closed() {
+        assert_eq!(
+            convert(app(lam(var(0)), int(20))),
+            Program {
+                codes: vec![Code {
+                    nfields: 0,
+                    body: expr::arg()
+                }],
+                main: expr::app(expr::mk_clos(0, vec![]), expr::int(20)),
+            }
+        );
+    }
+
+    #[test]
+    fn convert_captures_free_variables() {
+        // \x. \y. \z. x z: the inner closure captures only x
+        let prog = convert(lam(lam(lam(app(var(2), var(0))))));
+        assert_eq!(
+            prog.codes,
+            vec![
+                Code {
+                    nfields: 1,
+                    body: expr::app(expr::field(0), expr::arg()),
+                },
+                Code {
+                    nfields: 1,
+                    body: expr::mk_clos(0, vec![expr::field(0)]),
+                },
+                Code {
+                    nfields: 0,
+                    body: expr::mk_clos(1, vec![expr::arg()]),
+                },
+            ]
+        );
+        assert_eq!(prog.main, expr::mk_clos(2, vec![]));
+    }
+
+    #[test]
+    fn convert_open() {
+        let prog = convert(lam(app(var(2), var(1))));
+        assert_eq!(
+            prog.codes,
+            vec![Code {
+                nfields: 2,
+                body: expr::app(expr::field(1), expr::field(0)),
+            }]
+        );
+        assert_eq!(
+            prog.main,
+            expr::mk_clos(0, vec![expr::field(0), expr::field(1)])
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use crate::stats;
+use crate::stats::Kind;
+use std::rc::Rc;
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Ast {
+    Int(i32),
+    Var(usize),
+    Lam(Rc<Ast>),
+    App(Rc<Ast>, Rc<Ast>),
+}
+
+// First-order target: no nested functions, every variable is either the
+// parameter of the enclosing code or a slot of its environment tuple.
+#[derive(Eq, PartialEq, Debug)]
+pub enum Expr {
+    Int(i32),
+    Arg,                          // parameter of the current code
+    Field(usize),                 // slot of the current environment tuple
+    MkClos(usize, Vec<Rc<Expr>>), // code label and captured values
+    App(Rc<Expr>, Rc<Expr>),      // call the code of a closure record
+}
+
+// A lifted lambda, closed over its parameter and environment tuple.
+#[derive(Eq, PartialEq, Debug)]
+pub struct Code {
+    pub nfields: usize,
+    pub body: Rc<Expr>,
+}
+
+// `main` reads the initial environment through `Field`.
+#[derive(Eq, PartialEq, Debug)]
+pub struct Program {
+    pub codes: Vec<Code>,
+    pub main: Rc<Expr>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Val {
+    Error,
+    Int(i32),
+    Clos(usize, Rc<Vec<Rc<Val>>>), // closure record: code label and environment tuple
+}
+
+stats::counted!(Expr, Val);
+
+pub mod ast {
+    use super::*;
+
+    pub fn int(i: i32) -> Rc<Ast> {
+        Rc::new(Ast::Int(i))
+    }
+
+    pub fn var(i: usize) -> Rc<Ast> {
+        Rc::new(Ast::Var(i))
+    }
+
+    pub fn lam(a: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Lam(a))
+    }
+
+    pub fn app(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::App(a1, a2))
+    }
+}
+
+pub mod expr {
+    use super::*;
+
+    fn alloc(e: Expr) -> Rc<Expr> {
+        stats::alloc(Kind::Code);
+        Rc::new(e)
+    }
+
+    pub fn int(i: i32) -> Rc<Expr> {
+        alloc(Expr::Int(i))
+    }
+
+    pub fn arg() -> Rc<Expr> {
+        alloc(Expr::Arg)
+    }
+
+    pub fn field(i: usize) -> Rc<Expr> {
+        alloc(Expr::Field(i))
+    }
+
+    pub fn mk_clos(label: usize, fields: Vec<Rc<Expr>>) -> Rc<Expr> {
+        alloc(Expr::MkClos(label, fields))
+    }
+
+    pub fn app(func: Rc<Expr>, arg: Rc<Expr>) -> Rc<Expr> {
+        alloc(Expr::App(func, arg))
+    }
+}
+
+pub mod val {
+    use super::*;
+
+    fn alloc(v: Val) -> Rc<Val> {
+        stats::alloc(Kind::Val);
+        Rc::new(v)
+    }
+
+    pub fn error() -> Rc<Val> {
+        alloc(Val::Error)
+    }
+
+    pub fn int(i: i32) -> Rc<Val> {
+        alloc(Val::Int(i))
+    }
+
+    pub fn clos(label: usize, fields: Rc<Vec<Rc<Val>>>) -> Rc<Val> {
+        alloc(Val::Clos(label, fields))
+    }
+}
@@ -0,0 +1,4 @@
+pub mod data;
+pub mod convert;
+pub mod eval;
+pub mod print;
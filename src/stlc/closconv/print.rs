@@ -0,0 +1,96 @@
+use crate::pretty;
+use crate::pretty::{Ctx, Prec, Term};
+use crate::stlc::closconv::data::{Ast, Expr, Program, Val};
+use std::fmt;
+
+impl Term for Ast {
+    fn prec(&self) -> Prec {
+        match self {
+            Ast::Int(_) | Ast::Var(_) => Prec::Atom,
+            Ast::Lam(_) => Prec::Open,
+            Ast::App(_, _) => Prec::App,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ast::Int(i) => write!(f, "{}", i),
+            Ast::Var(idx) => write!(f, "{}", ctx.var(*idx, lev)),
+            Ast::Lam(body) => pretty::lam(&**body, ctx, lev, f),
+            Ast::App(func, arg) => pretty::app(&**func, &**arg, ctx, lev, f),
+        }
+    }
+}
+
+// Converted code has no binders: the parameter is `arg` and captured
+// values are fields of `env`.
+impl Term for Expr {
+    fn prec(&self) -> Prec {
+        match self {
+            Expr::Int(_) | Expr::Arg | Expr::Field(_) => Prec::Atom,
+            Expr::MkClos(_, _) | Expr::App(_, _) => Prec::App,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Int(i) => write!(f, "{}", i),
+            Expr::Arg => write!(f, "arg"),
+            Expr::Field(i) => write!(f, "env.{}", i),
+            Expr::MkClos(label, fields) => {
+                write!(f, "clos code{} [", label)?;
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    field.fmt_in(ctx, lev, f)?;
+                }
+                write!(f, "]")
+            }
+            Expr::App(func, arg) => pretty::app(&**func, &**arg, ctx, lev, f),
+        }
+    }
+}
+
+pretty::display!(Ast, Expr);
+
+// One line for each code, then the main expression.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (label, code) in self.codes.iter().enumerate() {
+            writeln!(f, "code{} = {}", label, code.body)?;
+        }
+        write!(f, "main = {}", self.main)
+    }
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Val::Error => write!(f, "error"),
+            Val::Int(i) => write!(f, "{}", i),
+            Val::Clos(label, fields) => {
+                let fields: Vec<&Val> = fields.iter().map(|v| &**v).collect();
+                write!(f, "{{code{} ", label)?;
+                pretty::env(&fields, f)?;
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stlc::closconv::convert::convert;
+    use crate::stlc::closconv::data::ast::*;
+
+    #[test]
+    fn print_program() {
+        // \x. \y. x
+        let prog = convert(lam(lam(var(1))));
+        assert_eq!(
+            prog.to_string(),
+            "code0 = env.0\ncode1 = clos code0 [arg]\nmain = clos code1 []"
+        );
+    }
+}
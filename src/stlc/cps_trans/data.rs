@@ -0,0 +1,29 @@
+use std::rc::Rc;
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Ast {
+    Int(i32),
+    Var(usize),
+    Lam(Rc<Ast>),
+    App(Rc<Ast>, Rc<Ast>),
+}
+
+pub mod ast {
+    use super::*;
+
+    pub fn int(i: i32) -> Rc<Ast> {
+        Rc::new(Ast::Int(i))
+    }
+
+    pub fn var(i: usize) -> Rc<Ast> {
+        Rc::new(Ast::Var(i))
+    }
+
+    pub fn lam(a: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Lam(a))
+    }
+
+    pub fn app(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::App(a1, a2))
+    }
+}
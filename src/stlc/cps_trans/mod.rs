@@ -0,0 +1,3 @@
+pub mod data;
+pub mod trans;
+pub mod print;
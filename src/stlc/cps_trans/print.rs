@@ -0,0 +1,25 @@
+use crate::pretty;
+use crate::pretty::{Ctx, Prec, Term};
+use crate::stlc::cps_trans::data::Ast;
+use std::fmt;
+
+impl Term for Ast {
+    fn prec(&self) -> Prec {
+        match self {
+            Ast::Int(_) | Ast::Var(_) => Prec::Atom,
+            Ast::Lam(_) => Prec::Open,
+            Ast::App(_, _) => Prec::App,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ast::Int(i) => write!(f, "{}", i),
+            Ast::Var(idx) => write!(f, "{}", ctx.var(*idx, lev)),
+            Ast::Lam(body) => pretty::lam(&**body, ctx, lev, f),
+            Ast::App(func, arg) => pretty::app(&**func, &**arg, ctx, lev, f),
+        }
+    }
+}
+
+pretty::display!(Ast);
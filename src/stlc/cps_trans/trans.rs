@@ -0,0 +1,227 @@
+use crate::stlc::cps_trans::data::*;
+use crate::stlc::lambda::free_vars;
+use std::cell::Cell;
+use std::rc::Rc;
+
+// Output terms use named binders so that a value built by the translator
+// can be plugged in under binders introduced later; `to_ast` turns the
+// names back into de Bruijn indices at the end.
+enum Tm {
+    Int(i32),
+    Var(usize),
+    Lam(usize, Rc<Tm>),
+    App(Rc<Tm>, Rc<Tm>),
+}
+
+type Names = Rc<Cell<usize>>;
+
+fn fresh(names: &Names) -> usize {
+    let name = names.get();
+    names.set(name + 1);
+    name
+}
+
+// A continuation is either known at translation time (static, applied by
+// the translator itself) or an object-level term (dynamic).
+enum Kont {
+    Static(Box<dyn FnOnce(Rc<Tm>) -> Rc<Tm>>),
+    Dynamic(Rc<Tm>),
+}
+
+fn apply_kont(k: Kont, v: Rc<Tm>) -> Rc<Tm> {
+    match k {
+        Kont::Static(f) => f(v),
+        Kont::Dynamic(kt) => Rc::new(Tm::App(kt, v)),
+    }
+}
+
+fn reify(k: Kont, names: &Names) -> Rc<Tm> {
+    match k {
+        Kont::Static(f) => {
+            let v = fresh(names);
+            Rc::new(Tm::Lam(v, f(Rc::new(Tm::Var(v)))))
+        }
+        Kont::Dynamic(kt) => kt,
+    }
+}
+
+// `ctx` maps source variables (innermost last) to output names.
+fn cps(ast: &Rc<Ast>, ctx: Rc<Vec<usize>>, names: Names, k: Kont) -> Rc<Tm> {
+    match &**ast {
+        Ast::Int(i) => apply_kont(k, Rc::new(Tm::Int(*i))),
+        Ast::Var(idx) => {
+            let name = ctx[ctx.len() - 1 - idx];
+            apply_kont(k, Rc::new(Tm::Var(name)))
+        }
+        Ast::Lam(body) => {
+            // \x. \k. [body] k
+            let x = fresh(&names);
+            let kx = fresh(&names);
+            let mut ctx2 = (*ctx).clone();
+            ctx2.push(x);
+            let kvar = Kont::Dynamic(Rc::new(Tm::Var(kx)));
+            let body = cps(body, Rc::new(ctx2), names, kvar);
+            let lam = Tm::Lam(x, Rc::new(Tm::Lam(kx, body)));
+            apply_kont(k, Rc::new(lam))
+        }
+        Ast::App(func, arg) => {
+            let arg = arg.clone();
+            cps(
+                func,
+                ctx.clone(),
+                names.clone(),
+                Kont::Static(Box::new(move |funcv| {
+                    cps(
+                        &arg,
+                        ctx,
+                        names.clone(),
+                        Kont::Static(Box::new(move |argv| {
+                            let call = Rc::new(Tm::App(funcv, argv));
+                            Rc::new(Tm::App(call, reify(k, &names)))
+                        })),
+                    )
+                })),
+            )
+        }
+    }
+}
+
+fn to_ast(tm: &Tm, scope: &mut Vec<usize>) -> Rc<Ast> {
+    match tm {
+        Tm::Int(i) => ast::int(*i),
+        Tm::Var(name) => {
+            let pos = scope.iter().rposition(|n| n == name).unwrap();
+            ast::var(scope.len() - 1 - pos)
+        }
+        Tm::Lam(name, body) => {
+            scope.push(*name);
+            let body = to_ast(body, scope);
+            scope.pop();
+            ast::lam(body)
+        }
+        Tm::App(func, arg) => ast::app(to_ast(func, scope), to_ast(arg, scope)),
+    }
+}
+
+// One-pass call-by-value CPS transformation (Danvy and Filinski, 1992).
+// The result takes the continuation as its argument: `\k. [ast] k`.
+// Source lambdas `\x. e` become `\x. \k. [e] k`. Free variables keep
+// their indices, so an open term runs in the same environment as before.
+pub fn transform(ast: Rc<Ast>) -> Rc<Ast> {
+    let nfree = free_vars(&*ast);
+    let names = Rc::new(Cell::new(nfree + 1));
+    let mut scope: Vec<usize> = (0..nfree).rev().collect();
+    let ctx = Rc::new(scope.clone());
+    let body = cps(&ast, ctx, names, Kont::Dynamic(Rc::new(Tm::Var(nfree))));
+    to_ast(&Tm::Lam(nfree, body), &mut scope)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stlc::cps_trans::data::ast::*;
+    use crate::stlc::eval_defunct1;
+    use crate::termgen::build::build;
+    use crate::termgen::shrink::check_agreement;
+
+    fn to_defunct1(ast: &Ast) -> Rc<eval_defunct1::ast::Ast> {
+        match ast {
+            Ast::Int(i) => eval_defunct1::ast::int(*i),
+            Ast::Var(idx) => eval_defunct1::ast::var(*idx),
+            Ast::Lam(body) => eval_defunct1::ast::lam(to_defunct1(body)),
+            Ast::App(func, arg) => eval_defunct1::ast::app(to_defunct1(func), to_defunct1(arg)),
+        }
+    }
+
+    fn eval(ast: Rc<Ast>) -> Rc<eval_defunct1::val::Val> {
+        use eval_defunct1::env::{cons, empty};
+        use eval_defunct1::val;
+
+        let env1 = cons(val::int(10), cons(val::int(20), empty()));
+        eval_defunct1::eval::eval(to_defunct1(&ast), env1, eval_defunct1::eval::Cont::Cont0)
+    }
+
+    // Run the transformed term with the identity continuation.
+    fn eval_cps(ast: Rc<Ast>) -> Rc<eval_defunct1::val::Val> {
+        eval(app(transform(ast), lam(var(0))))
+    }
+
+    fn has_redex(ast: &Ast) -> bool {
+        match ast {
+            Ast::Int(_) | Ast::Var(_) => false,
+            Ast::Lam(body) => has_redex(body),
+            Ast::App(func, arg) => {
+                matches!(**func, Ast::Lam(_)) || has_redex(func) || has_redex(arg)
+            }
+        }
+    }
+
+    #[test]
+    fn transform_values() {
+        assert_eq!(transform(int(1)), lam(app(var(0), int(1))));
+        assert_eq!(transform(var(0)), lam(app(var(0), var(1))));
+        assert_eq!(
+            transform(lam(var(0))),
+            lam(app(var(0), lam(lam(app(var(0), var(1))))))
+        );
+    }
+
+    #[test]
+    fn transform_app() {
+        // \k. f x k, without administrative redexes
+        assert_eq!(
+            transform(app(var(0), var(1))),
+            lam(app(app(var(1), var(2)), var(0)))
+        );
+        // \k. f x (\v. v y k)
+        assert_eq!(
+            transform(app(app(var(0), var(1)), var(2))),
+            lam(app(
+                app(var(1), var(2)),
+                lam(app(app(var(0), var(4)), var(1)))
+            ))
+        );
+    }
+
+    #[test]
+    fn transform_no_administrative_redexes() {
+        let exprs = [
+            app(app(var(0), var(1)), app(var(1), var(0))),
+            lam(app(var(0), app(var(1), lam(app(var(0), var(2)))))),
+            app(var(0), app(var(0), app(var(0), int(3)))),
+        ];
+        for expr in exprs {
+            assert!(!has_redex(&expr));
+            assert!(!has_redex(&transform(expr)));
+        }
+    }
+
+    #[test]
+    fn eval_transformed() {
+        assert_eq!(eval_cps(int(1)), eval_defunct1::val::int(1));
+        assert_eq!(eval_cps(var(0)), eval_defunct1::val::int(10));
+        assert_eq!(eval_cps(var(1)), eval_defunct1::val::int(20));
+        assert_eq!(
+            eval_cps(app(app(lam(lam(var(1))), int(33)), int(44))),
+            eval_defunct1::val::int(33)
+        );
+        assert_eq!(
+            eval_cps(app(lam(app(var(0), var(2))), lam(var(0)))),
+            eval_defunct1::val::int(20)
+        );
+    }
+
+    #[test]
+    fn eval_transformed_generated() {
+        use eval_defunct1::val::Val;
+
+        check_agreement(false, 500, |t| {
+            let expr: Rc<Ast> = build(t);
+            match (&*eval_cps(expr.clone()), &*eval(expr)) {
+                (Val::Int(i), Val::Int(j)) => i == j,
+                (Val::Clos(_, _), Val::Clos(_, _)) => true,
+                _ => false,
+            }
+        });
+    }
+}
@@ -5,7 +5,22 @@ pub enum Ast {
     Int(i32),
     Var(usize),
     Lam(Rc<Ast>),
-    App(Rc<Ast>, Rc<Ast>)
+    App(Rc<Ast>, Rc<Ast>),
+    Pair(Rc<Ast>, Rc<Ast>),
+    Fst(Rc<Ast>),
+    Snd(Rc<Ast>),
+    Inl(Rc<Ast>),
+    Inr(Rc<Ast>),
+    Case(Rc<Ast>, Rc<Ast>, Rc<Ast>), // both branches bind the payload as variable 0
+    Bool(bool),
+    If(Rc<Ast>, Rc<Ast>, Rc<Ast>),
+    Str(String),
+    Concat(Rc<Ast>, Rc<Ast>),
+    Length(Rc<Ast>),
+    StrEq(Rc<Ast>, Rc<Ast>),
+    Reset(Rc<Ast>),
+    Shift(Rc<Ast>), // binds the continuation up to the nearest reset as variable 0
+    Global(usize) // top-level definition, looked up in the globals table
 }
 
 // Constructors
@@ -25,6 +40,66 @@ pub fn app(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
     Rc::new(Ast::App(Rc::clone(&a1), Rc::clone(&a2)))
 }
 
+pub fn pair(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
+    Rc::new(Ast::Pair(a1, a2))
+}
+
+pub fn fst(a: Rc<Ast>) -> Rc<Ast> {
+    Rc::new(Ast::Fst(a))
+}
+
+pub fn snd(a: Rc<Ast>) -> Rc<Ast> {
+    Rc::new(Ast::Snd(a))
+}
+
+pub fn inl(a: Rc<Ast>) -> Rc<Ast> {
+    Rc::new(Ast::Inl(a))
+}
+
+pub fn inr(a: Rc<Ast>) -> Rc<Ast> {
+    Rc::new(Ast::Inr(a))
+}
+
+pub fn case(scrut: Rc<Ast>, left: Rc<Ast>, right: Rc<Ast>) -> Rc<Ast> {
+    Rc::new(Ast::Case(scrut, left, right))
+}
+
+pub fn bool(b: bool) -> Rc<Ast> {
+    Rc::new(Ast::Bool(b))
+}
+
+pub fn if_(cond: Rc<Ast>, then: Rc<Ast>, els: Rc<Ast>) -> Rc<Ast> {
+    Rc::new(Ast::If(cond, then, els))
+}
+
+pub fn str(s: &str) -> Rc<Ast> {
+    Rc::new(Ast::Str(String::from(s)))
+}
+
+pub fn concat(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
+    Rc::new(Ast::Concat(a1, a2))
+}
+
+pub fn length(a: Rc<Ast>) -> Rc<Ast> {
+    Rc::new(Ast::Length(a))
+}
+
+pub fn str_eq(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
+    Rc::new(Ast::StrEq(a1, a2))
+}
+
+pub fn reset(a: Rc<Ast>) -> Rc<Ast> {
+    Rc::new(Ast::Reset(a))
+}
+
+pub fn shift(a: Rc<Ast>) -> Rc<Ast> {
+    Rc::new(Ast::Shift(a))
+}
+
+pub fn global(i: usize) -> Rc<Ast> {
+    Rc::new(Ast::Global(i))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -49,4 +124,25 @@ mod tests {
     fn make_app() {
         assert_eq!(*app(lam(var(0)), var(2)), Ast::App(lam(var(0)), var(2)));
     }
+
+    #[test]
+    fn make_pair() {
+        assert_eq!(*fst(pair(int(1), var(0))), Ast::Fst(pair(int(1), var(0))));
+    }
+
+    #[test]
+    fn make_case() {
+        assert_eq!(
+            *case(inl(int(1)), var(0), int(2)),
+            Ast::Case(inl(int(1)), var(0), int(2))
+        );
+    }
+
+    #[test]
+    fn make_if() {
+        assert_eq!(
+            *if_(str_eq(str("a"), var(0)), int(1), int(2)),
+            Ast::If(str_eq(str("a"), var(0)), int(1), int(2))
+        );
+    }
 }
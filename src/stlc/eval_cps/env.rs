@@ -1,4 +1,6 @@
 use std::rc::Rc;
+use crate::stats;
+use crate::stats::Kind;
 
 #[derive(Eq, PartialEq, Debug)]
 pub enum Env<T> {
@@ -6,11 +8,15 @@ pub enum Env<T> {
     Cons(Rc<T>, Rc<Env<T>>)
 }
 
+stats::counted!(Env<T>);
+
 pub fn empty<T>() -> Rc<Env<T>> {
+    stats::alloc(Kind::Env);
     return Rc::new(Env::Nil)
 }
 
 pub fn cons<T>(elm: Rc<T>, env: Rc<Env<T>>) -> Rc<Env<T>> {
+    stats::alloc(Kind::Env);
     return Rc::new(Env::Cons(Rc::clone(&elm), Rc::clone(&env)))
 }
 
@@ -1,65 +1,401 @@
+use crate::stats;
 use crate::stlc::eval_cps::ast::Ast;
 use crate::stlc::eval_cps::env;
 use crate::stlc::eval_cps::env::Env;
 use crate::stlc::eval_cps::val;
-use crate::stlc::eval_cps::val::Val;
+use crate::stlc::eval_cps::val::{DelimCont, Kont, MetaCont, Val};
 use std::rc::Rc;
 
-pub fn eval(ast: Rc<Ast>, env: Rc<Env<Val>>, cont: Box<dyn FnOnce(Rc<Val>) -> Rc<Val>>) -> Rc<Val> {
+// Shift and reset in continuation-passing style with a meta-continuation,
+// after Danvy and Filinski's "Abstracting Control". `k` goes up to the
+// nearest reset and `mk` is everything after it. `eval_defunct1` is the
+// defunctionalized counterpart, with `Machine::meta` for `mk`.
+// Continuations are `Rc<dyn Fn>`, since a captured one may be resumed many
+// times.
+
+// The values of top-level definitions, shared by the continuations.
+pub type Globals = Rc<[Rc<Val>]>;
+
+// The continuation of a reset body: return to the meta-continuation.
+fn delimiter() -> DelimCont {
+    stats::shared_frame(|v: Rc<Val>, mk: MetaCont| mk(v))
+}
+
+// Evaluate `a` and then `b`, passing both values on.
+fn eval2(
+    globals: &Globals,
+    a: Rc<Ast>,
+    b: Rc<Ast>,
+    env: Rc<Env<Val>>,
+    k: impl Fn(Rc<Val>, Rc<Val>, MetaCont) -> Rc<Val> + 'static,
+    mk: MetaCont,
+) -> Rc<Val> {
+    let k = stats::shared_frame(k);
+    let (env2, globals2) = (env.clone(), globals.clone());
+    eval_with(
+        globals,
+        a,
+        env,
+        stats::shared_frame(move |av: Rc<Val>, mk: MetaCont| {
+            let k = k.clone();
+            eval_with(
+                &globals2,
+                b.clone(),
+                env2.clone(),
+                stats::shared_frame(move |bv: Rc<Val>, mk: MetaCont| k(av.clone(), bv, mk)),
+                mk,
+            )
+        }),
+        mk,
+    )
+}
+
+fn apply(globals: &Globals, funcv: Rc<Val>, argv: Rc<Val>, k: DelimCont, mk: MetaCont) -> Rc<Val> {
+    match &*funcv {
+        Val::Clos(cenv, body) => {
+            eval_with(globals, body.clone(), env::cons(argv, cenv.clone()), k, mk)
+        }
+        // resuming a captured continuation returns here, like a reset
+        Val::Delim(Kont(c)) => c(argv, stats::shared_frame(move |v| k(v, mk.clone()))),
+        _ => val::error(),
+    }
+}
+
+pub fn eval(ast: Rc<Ast>, env: Rc<Env<Val>>, k: DelimCont, mk: MetaCont) -> Rc<Val> {
+    eval_with(&Rc::from([]), ast, env, k, mk)
+}
+
+// Like `eval`, with `globals` holding the values of top-level definitions.
+pub fn eval_with(
+    globals: &Globals,
+    ast: Rc<Ast>,
+    env: Rc<Env<Val>>,
+    k: DelimCont,
+    mk: MetaCont,
+) -> Rc<Val> {
     match &*ast {
-        Ast::Int(i) => cont(Rc::new(Val::Int(*i))),
-        Ast::Var(idx) => env::lookup(env, *idx)
-            .map(|v| cont(v))
-            .unwrap_or(Rc::new(Val::Error)),
-        Ast::Lam(body) => cont(Rc::new(Val::Clos(env.clone(), body.clone()))),
+        Ast::Int(i) => k(val::int(*i), mk),
+        Ast::Var(idx) => match env::lookup(env, *idx) {
+            Some(v) => k(v, mk),
+            None => val::error(),
+        },
+        Ast::Lam(body) => k(val::clos(env, body.clone()), mk),
         Ast::App(func, arg) => {
-            let env2 = env.clone();
-            let arg2 = arg.clone();
-            eval(
+            let globals2 = globals.clone();
+            eval2(
+                globals,
                 func.clone(),
+                arg.clone(),
                 env,
-                Box::new(|funcv| match &*funcv {
-                    Val::Clos(cenv, body) => {
-                        let body2 = body.clone();
-                        let cenv2 = cenv.clone();
-                        eval(
-                            arg2,
-                            env2,
-                            Box::new(|argval| eval(body2, env::cons(argval, cenv2), cont)),
-                        )
+                move |funcv, argv, mk| apply(&globals2, funcv, argv, k.clone(), mk),
+                mk,
+            )
+        }
+        Ast::Pair(fst, snd) => eval2(
+            globals,
+            fst.clone(),
+            snd.clone(),
+            env,
+            move |fstv, sndv, mk| k(val::pair(fstv, sndv), mk),
+            mk,
+        ),
+        Ast::Fst(pair) => eval_with(
+            globals,
+            pair.clone(),
+            env,
+            stats::shared_frame(move |pairv: Rc<Val>, mk: MetaCont| match &*pairv {
+                Val::Pair(fstv, _) => k(fstv.clone(), mk),
+                _ => val::error(),
+            }),
+            mk,
+        ),
+        Ast::Snd(pair) => eval_with(
+            globals,
+            pair.clone(),
+            env,
+            stats::shared_frame(move |pairv: Rc<Val>, mk: MetaCont| match &*pairv {
+                Val::Pair(_, sndv) => k(sndv.clone(), mk),
+                _ => val::error(),
+            }),
+            mk,
+        ),
+        Ast::Inl(payload) => eval_with(
+            globals,
+            payload.clone(),
+            env,
+            stats::shared_frame(move |v: Rc<Val>, mk: MetaCont| k(val::inl(v), mk)),
+            mk,
+        ),
+        Ast::Inr(payload) => eval_with(
+            globals,
+            payload.clone(),
+            env,
+            stats::shared_frame(move |v: Rc<Val>, mk: MetaCont| k(val::inr(v), mk)),
+            mk,
+        ),
+        Ast::Case(scrut, left, right) => {
+            let (left, right, env2) = (left.clone(), right.clone(), env.clone());
+            let globals2 = globals.clone();
+            eval_with(
+                globals,
+                scrut.clone(),
+                env,
+                stats::shared_frame(move |scrutv: Rc<Val>, mk: MetaCont| match &*scrutv {
+                    Val::Inl(v) => eval_with(
+                        &globals2,
+                        left.clone(),
+                        env::cons(v.clone(), env2.clone()),
+                        k.clone(),
+                        mk,
+                    ),
+                    Val::Inr(v) => eval_with(
+                        &globals2,
+                        right.clone(),
+                        env::cons(v.clone(), env2.clone()),
+                        k.clone(),
+                        mk,
+                    ),
+                    _ => val::error(),
+                }),
+                mk,
+            )
+        }
+        Ast::Bool(b) => k(val::bool(*b), mk),
+        Ast::If(cond, then, els) => {
+            let (then, els, env2) = (then.clone(), els.clone(), env.clone());
+            let globals2 = globals.clone();
+            eval_with(
+                globals,
+                cond.clone(),
+                env,
+                stats::shared_frame(move |condv: Rc<Val>, mk: MetaCont| match &*condv {
+                    Val::Bool(true) => {
+                        eval_with(&globals2, then.clone(), env2.clone(), k.clone(), mk)
+                    }
+                    Val::Bool(false) => {
+                        eval_with(&globals2, els.clone(), env2.clone(), k.clone(), mk)
                     }
                     _ => val::error(),
                 }),
+                mk,
             )
         }
+        Ast::Str(s) => k(val::str(s), mk),
+        Ast::Concat(lhs, rhs) => eval2(
+            globals,
+            lhs.clone(),
+            rhs.clone(),
+            env,
+            move |lhsv, rhsv, mk| match (&*lhsv, &*rhsv) {
+                (Val::Str(s1), Val::Str(s2)) => k(val::str(&(s1.clone() + s2)), mk),
+                _ => val::error(),
+            },
+            mk,
+        ),
+        Ast::Length(s) => eval_with(
+            globals,
+            s.clone(),
+            env,
+            stats::shared_frame(move |sv: Rc<Val>, mk: MetaCont| match &*sv {
+                Val::Str(s) => k(val::int(s.chars().count() as i32), mk),
+                _ => val::error(),
+            }),
+            mk,
+        ),
+        Ast::StrEq(lhs, rhs) => eval2(
+            globals,
+            lhs.clone(),
+            rhs.clone(),
+            env,
+            move |lhsv, rhsv, mk| match (&*lhsv, &*rhsv) {
+                (Val::Str(s1), Val::Str(s2)) => k(val::bool(s1 == s2), mk),
+                _ => val::error(),
+            },
+            mk,
+        ),
+        // the current continuation moves into the meta-continuation
+        Ast::Reset(body) => eval_with(
+            globals,
+            body.clone(),
+            env,
+            delimiter(),
+            stats::shared_frame(move |v| k(v, mk.clone())),
+        ),
+        // the body runs in an empty continuation, so the reset stays
+        Ast::Shift(body) => eval_with(
+            globals,
+            body.clone(),
+            env::cons(val::delim(k), env),
+            delimiter(),
+            mk,
+        ),
+        Ast::Global(idx) => match globals.get(*idx) {
+            Some(v) => k(v.clone(), mk),
+            None => val::undefined(*idx),
+        },
     }
 }
 
+// Evaluate `ast` inside an outermost reset.
+pub fn run(ast: Rc<Ast>, env: Rc<Env<Val>>) -> Rc<Val> {
+    run_with(&Rc::from([]), ast, env)
+}
+
+pub fn run_with(globals: &Globals, ast: Rc<Ast>, env: Rc<Env<Val>>) -> Rc<Val> {
+    eval_with(globals, ast, env, delimiter(), stats::shared_frame(|v| v))
+}
+
+// Evaluate the top-level definitions of a program in order. A definition
+// can use later ones only under a lambda; fails with the index of one that
+// needs a later one.
+pub fn define_globals(defs: &[Rc<Ast>]) -> Result<Globals, usize> {
+    let mut globals = Vec::new();
+    for (i, def) in defs.iter().enumerate() {
+        let v = run_with(&Rc::from(globals.as_slice()), def.clone(), env::empty());
+        match &*v {
+            Val::Undefined(_) => return Err(i),
+            _ => globals.push(v),
+        }
+    }
+    Ok(Rc::from(globals))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::stlc::eval_cps::ast;
     use crate::stlc::eval_cps::env::{cons, empty, Env};
-    use crate::stlc::eval_cps::val;
+
+    // Examples of shift and reset, built with the constructors of `$ast`,
+    // paired with their results.
+    macro_rules! shift_examples {
+        ($ast:ident) => {{
+            use crate::stlc::$ast::ast::*;
+            // let x = bound in body
+            let let_ = |bound, body| app(lam(body), bound);
+            // flip = shift k. k "a" ^ k "b"
+            let flip = || shift(concat(app(var(0), str("a")), app(var(0), str("b"))));
+            // get = shift k. \s. k s s, and put v = shift k. \s. k v v
+            let get = || shift(lam(app(app(var(1), var(0)), var(0))));
+            let put = shift(lam(app(
+                app(var(1), concat(var(2), str("!"))),
+                concat(var(2), str("!")),
+            )));
+            vec![
+                // reset (1, shift k. "x")
+                (reset(pair(int(1), shift(str("x")))), "\"x\""),
+                // reset (1, shift k. k (k 2))
+                (
+                    reset(pair(int(1), shift(app(var(0), app(var(0), int(2)))))),
+                    "(1, (1, 2))",
+                ),
+                // "a" ^ shift k. "b": the outermost reset is implicit
+                (concat(str("a"), shift(str("b"))), "\"b\""),
+                // reset (flip ^ flip)
+                (reset(concat(flip(), flip())), "\"aaabbabb\""),
+                // (reset (let x = get in put (x ^ "!"); get; \s. result)) "hi"
+                (
+                    app(
+                        reset(let_(let_(get(), let_(put, get())), lam(var(1)))),
+                        str("hi"),
+                    ),
+                    "\"hi!\"",
+                ),
+            ]
+        }};
+    }
+
+    #[test]
+    fn run_matches_eval_defunct1() {
+        use crate::stlc::eval_defunct1;
+        use crate::stlc::eval_defunct1::eval::Cont;
+
+        let examples = shift_examples!(eval_cps)
+            .into_iter()
+            .zip(shift_examples!(eval_defunct1));
+        for ((expr, expected), (expr1, _)) in examples {
+            let v = run(expr, empty()).to_string();
+            let v1 = eval_defunct1::eval::eval(expr1, eval_defunct1::env::empty(), Cont::Cont0);
+            assert_eq!(v, v1.to_string());
+            assert_eq!(v, expected);
+        }
+    }
 
     #[test]
     fn eval_literal() {
         fn env1() -> Rc<Env<val::Val>> {
             cons(val::int(10), cons(val::int(20), empty()))
         }
-        assert_eq!(eval(ast::int(1), env1(), Box::new(|v| v)), val::int(1));
-        assert_eq!(eval(ast::var(0), env1(), Box::new(|v| v)), val::int(10));
-        assert_eq!(eval(ast::var(1), env1(), Box::new(|v| v)), val::int(20));
+        assert_eq!(run(ast::int(1), env1()), val::int(1));
+        assert_eq!(run(ast::var(0), env1()), val::int(10));
+        assert_eq!(run(ast::var(1), env1()), val::int(20));
         assert_eq!(
-            eval(
+            run(
                 ast::app(
                     ast::app(ast::lam(ast::lam(ast::var(1))), ast::int(33)),
                     ast::int(44)
                 ),
-                env1(),
-                Box::new(|v| v)
+                env1()
             ),
             val::int(33)
         )
     }
+
+    #[test]
+    fn eval_pairs() {
+        let swap = ast::lam(ast::pair(ast::snd(ast::var(0)), ast::fst(ast::var(0))));
+        assert_eq!(
+            run(ast::app(swap, ast::pair(ast::int(1), ast::int(2))), empty()),
+            val::pair(val::int(2), val::int(1))
+        );
+        assert_eq!(run(ast::fst(ast::int(1)), empty()), val::error());
+    }
+
+    #[test]
+    fn eval_bools_and_strings() {
+        let greet = ast::lam(ast::if_(
+            ast::str_eq(ast::var(0), ast::str("")),
+            ast::str("hello"),
+            ast::concat(ast::str("hello, "), ast::var(0)),
+        ));
+        assert_eq!(
+            run(ast::app(greet.clone(), ast::str("world")), empty()),
+            val::str("hello, world")
+        );
+        assert_eq!(
+            run(ast::app(greet, ast::str("")), empty()),
+            val::str("hello")
+        );
+        assert_eq!(run(ast::length(ast::str("héllo")), empty()), val::int(5));
+        assert_eq!(
+            run(ast::str_eq(ast::str("a"), ast::str("b")), empty()),
+            val::bool(false)
+        );
+        assert_eq!(
+            run(ast::if_(ast::int(1), ast::int(2), ast::int(3)), empty()),
+            val::error()
+        );
+        assert_eq!(run(ast::length(ast::int(1)), empty()), val::error());
+    }
+
+    #[test]
+    fn eval_sums() {
+        // case e of inl x => x | inr y => snd y
+        let get = ast::lam(ast::case(ast::var(0), ast::var(0), ast::snd(ast::var(0))));
+        assert_eq!(
+            run(ast::app(get.clone(), ast::inl(ast::int(1))), empty()),
+            val::int(1)
+        );
+        assert_eq!(
+            run(
+                ast::app(get, ast::inr(ast::pair(ast::int(2), ast::int(3)))),
+                empty()
+            ),
+            val::int(3)
+        );
+        assert_eq!(
+            run(ast::case(ast::int(1), ast::var(0), ast::var(0)), empty()),
+            val::error()
+        );
+    }
 }
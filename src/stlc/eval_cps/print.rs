@@ -0,0 +1,137 @@
+use crate::pretty;
+use crate::pretty::{Ctx, Prec, Term};
+use crate::stlc::eval_cps::ast::Ast;
+use crate::stlc::eval_cps::env::Env;
+use crate::stlc::eval_cps::val::Val;
+use std::fmt;
+
+impl Term for Ast {
+    fn prec(&self) -> Prec {
+        match self {
+            Ast::Int(_)
+            | Ast::Var(_)
+            | Ast::Pair(_, _)
+            | Ast::Bool(_)
+            | Ast::Str(_)
+            | Ast::Global(_) => Prec::Atom,
+            Ast::App(_, _)
+            | Ast::Fst(_)
+            | Ast::Snd(_)
+            | Ast::Inl(_)
+            | Ast::Inr(_)
+            | Ast::Length(_)
+            | Ast::Reset(_) => Prec::App,
+            _ => Prec::Open,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ast::Int(i) => write!(f, "{}", i),
+            Ast::Var(idx) => write!(f, "{}", ctx.var(*idx, lev)),
+            Ast::Lam(body) => pretty::lam(&**body, ctx, lev, f),
+            Ast::App(func, arg) => pretty::app(&**func, &**arg, ctx, lev, f),
+            Ast::Pair(fst, snd) => pretty::pair(&**fst, &**snd, ctx, lev, f),
+            Ast::Fst(pair) => pretty::keyword("fst", &**pair, ctx, lev, f),
+            Ast::Snd(pair) => pretty::keyword("snd", &**pair, ctx, lev, f),
+            Ast::Inl(payload) => pretty::keyword("inl", &**payload, ctx, lev, f),
+            Ast::Inr(payload) => pretty::keyword("inr", &**payload, ctx, lev, f),
+            Ast::Case(scrut, left, right) => pretty::case(&**scrut, &**left, &**right, ctx, lev, f),
+            Ast::Bool(b) => write!(f, "{}", b),
+            Ast::If(cond, then, els) => pretty::if_(&**cond, &**then, &**els, ctx, lev, f),
+            Ast::Str(s) => write!(f, "{:?}", s),
+            Ast::Concat(lhs, rhs) => pretty::infix(&**lhs, "^", &**rhs, ctx, lev, f),
+            Ast::Length(s) => pretty::keyword("length", &**s, ctx, lev, f),
+            Ast::StrEq(lhs, rhs) => pretty::infix(&**lhs, "==", &**rhs, ctx, lev, f),
+            Ast::Reset(body) => pretty::keyword("reset", &**body, ctx, lev, f),
+            Ast::Shift(body) => {
+                pretty::bind(|k, f| write!(f, "shift {}. ", k), &**body, ctx, lev, f)
+            }
+            Ast::Global(i) => write!(f, "g{}", i),
+        }
+    }
+}
+
+pretty::display!(Ast);
+
+fn entries<T>(mut env: &Env<T>) -> Vec<&T> {
+    let mut entries = vec![];
+    while let Env::Cons(head, tail) = env {
+        entries.push(&**head);
+        env = tail;
+    }
+    entries
+}
+
+impl<T: fmt::Display> fmt::Display for Env<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pretty::env(&entries(self), f)
+    }
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Val::Error => write!(f, "error"),
+            Val::Int(i) => write!(f, "{}", i),
+            Val::Clos(env, body) => {
+                pretty::closure(&entries(env), f, |ctx, f| pretty::lam(&**body, ctx, 0, f))
+            }
+            Val::Pair(fst, snd) => {
+                let d = pretty::depth(f);
+                write!(f, "({:.*}, {:.*})", d, fst, d, snd)
+            }
+            Val::Inl(payload) => write!(f, "inl {:.*}", pretty::depth(f), payload),
+            Val::Inr(payload) => write!(f, "inr {:.*}", pretty::depth(f), payload),
+            Val::Bool(b) => write!(f, "{}", b),
+            Val::Str(s) => write!(f, "{:?}", s),
+            Val::Delim(_) => write!(f, "<delimited continuation>"),
+            Val::Undefined(i) => write!(f, "error: undefined g{}", i),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stlc::eval_cps::ast::*;
+    use crate::stlc::eval_cps::{env, val};
+
+    #[test]
+    fn print_ast() {
+        let swap = lam(pair(snd(var(0)), fst(var(0))));
+        assert_eq!(swap.to_string(), "\\x0. (snd x0, fst x0)");
+        let get = lam(case(var(0), var(0), snd(var(0))));
+        assert_eq!(
+            get.to_string(),
+            "\\x0. case x0 of inl x1 -> x1 | inr x1 -> snd x1"
+        );
+        let greet = lam(if_(
+            str_eq(var(0), str("")),
+            str("hello"),
+            concat(str("hello, "), app(lam(var(0)), var(0))),
+        ));
+        assert_eq!(
+            greet.to_string(),
+            "\\x0. if x0 == \"\" then \"hello\" else \"hello, \" ^ (\\x1. x1) x0"
+        );
+        assert_eq!(
+            reset(app(int(1), shift(app(var(0), int(2))))).to_string(),
+            "reset (1 (shift x0. x0 2))"
+        );
+    }
+
+    #[test]
+    fn print_val() {
+        let env = env::cons(val::int(1), env::cons(val::int(2), env::empty()));
+        assert_eq!(env.to_string(), "[1, 2]");
+        let clos = val::clos(env.clone(), app(var(0), var(2)));
+        assert_eq!(clos.to_string(), "{\\x2. x2 x0 | x1 = 1, x0 = 2}");
+        let env = env::cons(clos, env);
+        assert_eq!(format!("{:.2}", env), "[{\\x2. x2 x0 | x1 = 1, ..}, 1, ..]");
+        assert_eq!(format!("{:.0}", env), "[..]");
+        assert_eq!(
+            val::pair(val::inl(val::str("a")), val::bool(true)).to_string(),
+            "(inl \"a\", true)"
+        );
+    }
+}
@@ -1,23 +1,92 @@
 use std::rc::Rc;
 use crate::stlc::eval_cps::ast::Ast;
 use crate::stlc::eval_cps::env::Env;
+use crate::stats;
+use crate::stats::Kind;
+use std::fmt;
+
+stats::counted!(Val);
 
 #[derive(Eq, PartialEq, Debug)]
 
 pub enum Val {
     Error,
     Int(i32),
-    Clos(Rc<Env<Val>>, Rc<Ast>)
+    Clos(Rc<Env<Val>>, Rc<Ast>),
+    Pair(Rc<Val>, Rc<Val>),
+    Inl(Rc<Val>),
+    Inr(Rc<Val>),
+    Bool(bool),
+    Str(String),
+    Delim(Kont), // captured by shift
+    Undefined(usize) // error: a global used before it is defined
+}
+
+// Continuations of `eval`: one up to the nearest reset, which is given
+// the meta-continuation to return to, and one for the rest.
+pub type DelimCont = Rc<dyn Fn(Rc<Val>, MetaCont) -> Rc<Val>>;
+pub type MetaCont = Rc<dyn Fn(Rc<Val>) -> Rc<Val>>;
+
+// Rust closures cannot be compared, so captured continuations are equal
+// only to themselves.
+#[derive(Clone)]
+pub struct Kont(pub DelimCont);
+
+impl PartialEq for Kont {
+    fn eq(&self, other: &Kont) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Kont {}
+
+impl fmt::Debug for Kont {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<continuation>")
+    }
+}
+
+fn alloc(v: Val) -> Rc<Val> {
+    stats::alloc(Kind::Val);
+    Rc::new(v)
 }
 
 pub fn error() -> Rc<Val> {
-    return Rc::new(Val::Error)
+    return alloc(Val::Error)
 }
 
 pub fn int(i: i32) -> Rc<Val> {
-    return Rc::new(Val::Int(i))
+    return alloc(Val::Int(i))
 }
 
 pub fn clos(env: Rc<Env<Val>>, body: Rc<Ast>) -> Rc<Val> {
-    return Rc::new(Val::Clos(env, body))
+    return alloc(Val::Clos(env, body))
+}
+
+pub fn pair(v1: Rc<Val>, v2: Rc<Val>) -> Rc<Val> {
+    alloc(Val::Pair(v1, v2))
+}
+
+pub fn inl(v: Rc<Val>) -> Rc<Val> {
+    alloc(Val::Inl(v))
+}
+
+pub fn inr(v: Rc<Val>) -> Rc<Val> {
+    alloc(Val::Inr(v))
+}
+
+pub fn bool(b: bool) -> Rc<Val> {
+    alloc(Val::Bool(b))
+}
+
+pub fn str(s: &str) -> Rc<Val> {
+    alloc(Val::Str(String::from(s)))
+}
+
+pub fn delim(k: DelimCont) -> Rc<Val> {
+    alloc(Val::Delim(Kont(k)))
+}
+
+pub fn undefined(idx: usize) -> Rc<Val> {
+    alloc(Val::Undefined(idx))
 }
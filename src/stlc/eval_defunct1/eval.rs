@@ -1,56 +1,576 @@
+use crate::stats;
 use crate::stlc::eval_defunct1::ast::*;
 use crate::stlc::eval_defunct1::env;
 use crate::stlc::eval_defunct1::env::Env;
 use crate::stlc::eval_defunct1::val;
-use crate::stlc::eval_defunct1::val::Val;
+use crate::stlc::eval_defunct1::val::{Thunk, Val};
 use std::rc::Rc;
 
+#[derive(Eq, PartialEq, Debug, Clone, Copy)]
+pub enum Strategy {
+    CallByValue,
+    CallByName,
+    CallByNeed,
+}
+
+pub struct Machine {
+    pub strategy: Strategy,
+    pub steps: usize,          // number of terms evaluated so far
+    pub fuel: Option<usize>,   // number of terms left to evaluate, if limited
+    pub globals: Vec<Rc<Val>>, // values of top-level definitions
+    pub store: Vec<Rc<Val>>,   // contents of the references, by location
+    pub meta: Vec<Cont>,       // continuations of the enclosing resets
+}
+
+impl Machine {
+    pub fn new(strategy: Strategy) -> Machine {
+        Machine {
+            strategy,
+            steps: 0,
+            fuel: None,
+            globals: Vec::new(),
+            store: Vec::new(),
+            meta: Vec::new(),
+        }
+    }
+
+    pub fn with_fuel(strategy: Strategy, fuel: usize) -> Machine {
+        Machine {
+            strategy,
+            steps: 0,
+            fuel: Some(fuel),
+            globals: Vec::new(),
+            store: Vec::new(),
+            meta: Vec::new(),
+        }
+    }
+}
+
+// Frames are shared through `Rc` so that `CallCC` can capture the stack
+// as a value and reinstate it any number of times.
+#[derive(Eq, PartialEq, Debug, Clone)]
 pub enum Cont {
     Cont0,
-    EvalArg(Rc<Ast>, Rc<Env<Val>>, Box<Cont>),
-    EvalClos(Rc<Ast>, Rc<Env<Val>>, Box<Cont>),
+    EvalArg(Rc<Ast>, Rc<Env<Val>>, Rc<Cont>),
+    EvalClos(Rc<Ast>, Rc<Env<Val>>, Rc<Cont>),
+    Update(Rc<Val>, Rc<Cont>), // overwrite the thunk with its value
+    EvalSnd(Rc<Ast>, Rc<Env<Val>>, Rc<Cont>),
+    MakePair(Rc<Val>, Rc<Cont>),
+    Fst(Rc<Cont>),
+    Snd(Rc<Cont>),
+    MakeInl(Rc<Cont>),
+    MakeInr(Rc<Cont>),
+    Case(Rc<Ast>, Rc<Ast>, Rc<Env<Val>>, Rc<Cont>), // branches for inl and inr
+    If(Rc<Ast>, Rc<Ast>, Rc<Env<Val>>, Rc<Cont>),
+    EvalConcat(Rc<Ast>, Rc<Env<Val>>, Rc<Cont>),
+    Concat(Rc<Val>, Rc<Cont>),
+    Length(Rc<Cont>),
+    EvalStrEq(Rc<Ast>, Rc<Env<Val>>, Rc<Cont>),
+    StrEq(Rc<Val>, Rc<Cont>),
+    MakeRef(Rc<Cont>),
+    Deref(Rc<Cont>),
+    EvalAssign(Rc<Ast>, Rc<Env<Val>>, Rc<Cont>),
+    Assign(Rc<Val>, Rc<Cont>), // the location to overwrite
+    Raise(Rc<Cont>),
+    Handler(Rc<Ast>, Rc<Env<Val>>, Rc<Cont>),
+    CallCC(Rc<Cont>),
+    Throw(Rc<Cont>, Rc<Vec<Cont>>, Rc<Cont>), // the continuation to reinstate
+    Resume(Rc<Cont>, Rc<Cont>),               // the continuation captured by `Shift`
+    Perform(String, Rc<Cont>),
+    EffHandler(Rc<Vec<(String, Rc<Ast>)>>, Rc<Ast>, Rc<Env<Val>>, Rc<Cont>), // clauses, return
+    Continue(Rc<Vec<Cont>>, Rc<Cont>), // the frames of a resumption
+}
+
+// Machine configurations. Each call of `eval_term` or `apply_cont` does a
+// single transition, and `run` iterates them, so evaluation does not grow
+// the Rust stack and can be interrupted at any point.
+pub enum State {
+    Eval(Rc<Ast>, Rc<Env<Val>>, Cont),
+    Apply(Cont, Rc<Val>),
+    Unwind(Cont, Rc<Val>), // an exception, popping frames up to a handler
+    Halt(Rc<Val>),
+    Unhandled(String, Rc<Val>), // an operation without a handler, and its argument
+    Undefined(usize),           // a global used before it is defined
+}
+
+pub enum Outcome {
+    Done(Rc<Val>),
+    Uncaught(Rc<Val>), // an exception reached `Cont0`
+    Unhandled(String, Rc<Val>),
+    Undefined(usize),
+    OutOfFuel(State), // resume with `run` after adding fuel
 }
 
-fn apply_cont(cont: Cont, v: Rc<Val>) -> Rc<Val> {
+// The rest of the stack, copying the frame only if it is still captured.
+fn take(cont: Rc<Cont>) -> Cont {
+    Rc::unwrap_or_clone(cont)
+}
+
+fn apply_cont(m: &mut Machine, cont: Cont, v: Rc<Val>) -> State {
     match cont {
-        Cont::Cont0 => v,
+        // the end of a delimited continuation returns to the enclosing reset
+        Cont::Cont0 => match m.meta.pop() {
+            Some(cont) => State::Apply(cont, v),
+            None => State::Halt(v),
+        },
         Cont::EvalArg(arg, env, cont) => match &*v {
-            Val::Clos(cenv, cbody) => {
-                eval(arg, env, Cont::EvalClos(cbody.clone(), cenv.clone(), cont))
+            Val::Clos(cenv, cbody) => match m.strategy {
+                Strategy::CallByValue => {
+                    State::Eval(arg, env, Cont::EvalClos(cbody.clone(), cenv.clone(), cont))
+                }
+                Strategy::CallByName | Strategy::CallByNeed => State::Eval(
+                    cbody.clone(),
+                    env::cons(val::thunk(arg, env), cenv.clone()),
+                    take(cont),
+                ),
+            },
+            // the argument is evaluated under every strategy, since
+            // continuations expect values; then the current stack is dropped
+            Val::Cont(k, meta) => State::Eval(arg, env, Cont::Throw(k.clone(), meta.clone(), cont)),
+            Val::Delim(k) => State::Eval(arg, env, Cont::Resume(k.clone(), cont)),
+            Val::Resumption(frames) => State::Eval(arg, env, Cont::Continue(frames.clone(), cont)),
+            _ => State::Halt(val::error()),
+        },
+        Cont::EvalClos(cbody, cenv, cont) => State::Eval(cbody, env::cons(v, cenv), take(cont)),
+        Cont::Update(thunk, cont) => {
+            if let Val::Thunk(state) = &*thunk {
+                *state.borrow_mut() = Thunk::Forced(v.clone());
+            }
+            State::Apply(take(cont), v)
+        }
+        Cont::EvalSnd(snd, env, cont) => State::Eval(snd, env, Cont::MakePair(v, cont)),
+        Cont::MakePair(fstv, cont) => State::Apply(take(cont), val::pair(fstv, v)),
+        Cont::Fst(cont) => match &*v {
+            Val::Pair(fstv, _) => force(m, fstv.clone(), take(cont)),
+            _ => State::Halt(val::error()),
+        },
+        Cont::Snd(cont) => match &*v {
+            Val::Pair(_, sndv) => force(m, sndv.clone(), take(cont)),
+            _ => State::Halt(val::error()),
+        },
+        Cont::MakeInl(cont) => State::Apply(take(cont), val::inl(v)),
+        Cont::MakeInr(cont) => State::Apply(take(cont), val::inr(v)),
+        Cont::Case(left, right, env, cont) => match &*v {
+            Val::Inl(payload) => State::Eval(left, env::cons(payload.clone(), env), take(cont)),
+            Val::Inr(payload) => State::Eval(right, env::cons(payload.clone(), env), take(cont)),
+            _ => State::Halt(val::error()),
+        },
+        Cont::If(then, els, env, cont) => match &*v {
+            Val::Bool(true) => State::Eval(then, env, take(cont)),
+            Val::Bool(false) => State::Eval(els, env, take(cont)),
+            _ => State::Halt(val::error()),
+        },
+        Cont::EvalConcat(rhs, env, cont) => State::Eval(rhs, env, Cont::Concat(v, cont)),
+        Cont::Concat(lhsv, cont) => match (&*lhsv, &*v) {
+            (Val::Str(s1), Val::Str(s2)) => State::Apply(take(cont), val::str(&(s1.clone() + s2))),
+            _ => State::Halt(val::error()),
+        },
+        Cont::Length(cont) => match &*v {
+            Val::Str(s) => State::Apply(take(cont), val::int(s.chars().count() as i32)),
+            _ => State::Halt(val::error()),
+        },
+        Cont::EvalStrEq(rhs, env, cont) => State::Eval(rhs, env, Cont::StrEq(v, cont)),
+        Cont::StrEq(lhsv, cont) => match (&*lhsv, &*v) {
+            (Val::Str(s1), Val::Str(s2)) => State::Apply(take(cont), val::bool(s1 == s2)),
+            _ => State::Halt(val::error()),
+        },
+        Cont::MakeRef(cont) => {
+            m.store.push(v);
+            State::Apply(take(cont), val::loc(m.store.len() - 1))
+        }
+        Cont::Deref(cont) => match &*v {
+            Val::Loc(l) if *l < m.store.len() => State::Apply(take(cont), m.store[*l].clone()),
+            _ => State::Halt(val::error()),
+        },
+        Cont::EvalAssign(rhs, env, cont) => State::Eval(rhs, env, Cont::Assign(v, cont)),
+        Cont::Assign(locv, cont) => match &*locv {
+            Val::Loc(l) if *l < m.store.len() => {
+                m.store[*l] = v.clone();
+                State::Apply(take(cont), v)
             }
-            _ => val::error(),
+            _ => State::Halt(val::error()),
         },
-        Cont::EvalClos(cbody, cenv, cont) => eval(cbody, env::cons(v, cenv), *cont),
+        Cont::Raise(cont) => State::Unwind(take(cont), v),
+        Cont::Handler(_, _, cont) => State::Apply(take(cont), v),
+        // the continuation extends past the enclosing resets, so they are
+        // captured and reinstated along with it
+        Cont::CallCC(cont) => match &*v {
+            Val::Clos(cenv, cbody) => State::Eval(
+                cbody.clone(),
+                env::cons(
+                    val::cont(cont.clone(), Rc::new(m.meta.clone())),
+                    cenv.clone(),
+                ),
+                take(cont),
+            ),
+            Val::Cont(k, meta) => {
+                let current = val::cont(cont, Rc::new(m.meta.clone()));
+                m.meta = (**meta).clone();
+                State::Apply(take(k.clone()), current)
+            }
+            _ => State::Halt(val::error()),
+        },
+        Cont::Throw(k, meta, _) => {
+            m.meta = Rc::unwrap_or_clone(meta);
+            State::Apply(take(k), v)
+        }
+        // a delimited continuation returns, so the current one is kept
+        Cont::Resume(k, cont) => {
+            m.meta.push(take(cont));
+            State::Apply(take(k), v)
+        }
+        Cont::Perform(op, cont) => perform(m, op, v, take(cont)),
+        Cont::EffHandler(_, ret, env, cont) => State::Eval(ret, env::cons(v, env), take(cont)),
+        Cont::Continue(frames, cont) => State::Apply(resume(m, &frames, take(cont)), v),
     }
 }
 
-pub fn eval(ast: Rc<Ast>, env: Rc<Env<Val>>, cont: Cont) -> Rc<Val> {
+impl Cont {
+    // The frame below this one, which is all of the stack but `Cont0`.
+    fn rest_mut(&mut self) -> Option<&mut Rc<Cont>> {
+        match self {
+            Cont::Cont0 => None,
+            Cont::EvalArg(_, _, cont)
+            | Cont::EvalClos(_, _, cont)
+            | Cont::Update(_, cont)
+            | Cont::EvalSnd(_, _, cont)
+            | Cont::MakePair(_, cont)
+            | Cont::Fst(cont)
+            | Cont::Snd(cont)
+            | Cont::MakeInl(cont)
+            | Cont::MakeInr(cont)
+            | Cont::Case(_, _, _, cont)
+            | Cont::If(_, _, _, cont)
+            | Cont::EvalConcat(_, _, cont)
+            | Cont::Concat(_, cont)
+            | Cont::Length(cont)
+            | Cont::EvalStrEq(_, _, cont)
+            | Cont::StrEq(_, cont)
+            | Cont::MakeRef(cont)
+            | Cont::Deref(cont)
+            | Cont::EvalAssign(_, _, cont)
+            | Cont::Assign(_, cont)
+            | Cont::Raise(cont)
+            | Cont::Handler(_, _, cont)
+            | Cont::CallCC(cont)
+            | Cont::Throw(_, _, cont)
+            | Cont::Resume(_, cont)
+            | Cont::Perform(_, cont)
+            | Cont::EffHandler(_, _, _, cont)
+            | Cont::Continue(_, cont) => Some(cont),
+        }
+    }
+}
+
+// Drop one frame of the stack, resuming at the innermost handler. A
+// handler runs outside of its own frame, so it can re-raise.
+fn unwind(cont: Cont, exn: Rc<Val>) -> State {
+    match cont {
+        Cont::Handler(handler, env, cont) => State::Eval(handler, env::cons(exn, env), take(cont)),
+        mut cont => match cont.rest_mut() {
+            Some(rest) => State::Unwind(take(rest.clone()), exn),
+            None => State::Unwind(cont, exn), // reported by `run`
+        },
+    }
+}
+
+fn handles(clauses: &[(String, Rc<Ast>)], op: &str) -> Option<Rc<Ast>> {
+    clauses
+        .iter()
+        .find(|(name, _)| name == op)
+        .map(|(_, clause)| clause.clone())
+}
+
+// Split the stack at the innermost handler for `op` and run its clause
+// outside of it. The frames up to and including the handler become the
+// resumption, so the handler is reinstalled when resuming (deep handlers).
+// Like exceptions, operations propagate through resets, which become part
+// of the resumption.
+fn perform(m: &mut Machine, op: String, arg: Rc<Val>, cont: Cont) -> State {
+    let mut frames = Vec::new();
+    let mut cont = cont;
+    loop {
+        if let Cont::EffHandler(clauses, _, env, rest) = &cont {
+            if let Some(clause) = handles(clauses, &op) {
+                let env = env::cons(arg, env.clone());
+                let rest = take(rest.clone());
+                frames.push(cont);
+                let resumption = val::resumption(Rc::new(frames));
+                return State::Eval(clause, env::cons(resumption, env), rest);
+            }
+        }
+        let rest = match cont.rest_mut() {
+            Some(rest) => take(rest.clone()),
+            None => match m.meta.pop() {
+                Some(rest) => rest,
+                None => return State::Unhandled(op, arg),
+            },
+        };
+        frames.push(cont);
+        cont = rest;
+    }
+}
+
+// Reinstate the frames of a resumption, innermost first, on `cont`. The
+// resets among them go back on the meta stack.
+fn resume(m: &mut Machine, frames: &[Cont], cont: Cont) -> Cont {
+    let mut segments = frames.rsplit(|frame| matches!(frame, Cont::Cont0));
+    let mut cont = push_frames(segments.next().unwrap_or_default(), cont);
+    for segment in segments {
+        m.meta.push(cont);
+        cont = push_frames(segment, Cont::Cont0);
+    }
+    cont
+}
+
+fn push_frames(frames: &[Cont], cont: Cont) -> Cont {
+    frames.iter().rev().fold(cont, |cont, frame| {
+        let mut frame = frame.clone();
+        if let Some(rest) = frame.rest_mut() {
+            *rest = stats::shared_frame(cont);
+        }
+        frame
+    })
+}
+
+// Variables may be bound to thunks, which are evaluated when looked up.
+fn force(m: &mut Machine, v: Rc<Val>, cont: Cont) -> State {
+    match &*v {
+        Val::Thunk(state) => {
+            let state = state.borrow().clone();
+            match state {
+                Thunk::Forced(forced) => State::Apply(cont, forced),
+                Thunk::Delayed(ast, env) => match m.strategy {
+                    Strategy::CallByNeed => {
+                        State::Eval(ast, env, Cont::Update(v, stats::shared_frame(cont)))
+                    }
+                    _ => State::Eval(ast, env, cont),
+                },
+            }
+        }
+        _ => State::Apply(cont, v),
+    }
+}
+
+fn eval_term(m: &mut Machine, ast: Rc<Ast>, env: Rc<Env<Val>>, cont: Cont) -> State {
     match &*ast {
-        Ast::Int(i) => apply_cont(cont, Rc::new(Val::Int(*i))),
+        Ast::Int(i) => State::Apply(cont, val::int(*i)),
         Ast::Var(idx) => env::lookup(env, *idx)
-            .map(|v| apply_cont(cont, v))
-            .unwrap_or(Rc::new(Val::Error)),
-        Ast::Lam(body) => apply_cont(cont, Rc::new(Val::Clos(env.clone(), body.clone()))),
-        Ast::App(func, arg) => eval(
+            .map(|v| force(m, v, cont))
+            .unwrap_or_else(|| State::Halt(val::error())),
+        Ast::Lam(body) => State::Apply(cont, val::clos(env.clone(), body.clone())),
+        Ast::App(func, arg) => State::Eval(
             func.clone(),
             env.clone(),
-            Cont::EvalArg(arg.clone(), env, Box::new(cont)),
+            Cont::EvalArg(arg.clone(), env, stats::shared_frame(cont)),
+        ),
+        // Constructors are lazy under call-by-name and call-by-need: the
+        // components are delayed like arguments, and forced by projections
+        // and by variables bound by `Case`.
+        Ast::Pair(fst, snd) => match m.strategy {
+            Strategy::CallByValue => State::Eval(
+                fst.clone(),
+                env.clone(),
+                Cont::EvalSnd(snd.clone(), env, stats::shared_frame(cont)),
+            ),
+            _ => State::Apply(
+                cont,
+                val::pair(
+                    val::thunk(fst.clone(), env.clone()),
+                    val::thunk(snd.clone(), env),
+                ),
+            ),
+        },
+        Ast::Fst(pair) => State::Eval(pair.clone(), env, Cont::Fst(stats::shared_frame(cont))),
+        Ast::Snd(pair) => State::Eval(pair.clone(), env, Cont::Snd(stats::shared_frame(cont))),
+        Ast::Inl(payload) => match m.strategy {
+            Strategy::CallByValue => State::Eval(
+                payload.clone(),
+                env,
+                Cont::MakeInl(stats::shared_frame(cont)),
+            ),
+            _ => State::Apply(cont, val::inl(val::thunk(payload.clone(), env))),
+        },
+        Ast::Inr(payload) => match m.strategy {
+            Strategy::CallByValue => State::Eval(
+                payload.clone(),
+                env,
+                Cont::MakeInr(stats::shared_frame(cont)),
+            ),
+            _ => State::Apply(cont, val::inr(val::thunk(payload.clone(), env))),
+        },
+        Ast::Case(scrut, left, right) => State::Eval(
+            scrut.clone(),
+            env.clone(),
+            Cont::Case(left.clone(), right.clone(), env, stats::shared_frame(cont)),
+        ),
+        Ast::Bool(b) => State::Apply(cont, val::bool(*b)),
+        Ast::If(cond, then, els) => State::Eval(
+            cond.clone(),
+            env.clone(),
+            Cont::If(then.clone(), els.clone(), env, stats::shared_frame(cont)),
+        ),
+        Ast::Str(s) => State::Apply(cont, val::str(s)),
+        Ast::Concat(lhs, rhs) => State::Eval(
+            lhs.clone(),
+            env.clone(),
+            Cont::EvalConcat(rhs.clone(), env, stats::shared_frame(cont)),
+        ),
+        Ast::Length(s) => State::Eval(s.clone(), env, Cont::Length(stats::shared_frame(cont))),
+        Ast::StrEq(lhs, rhs) => State::Eval(
+            lhs.clone(),
+            env.clone(),
+            Cont::EvalStrEq(rhs.clone(), env, stats::shared_frame(cont)),
+        ),
+        Ast::Global(idx) => match m.globals.get(*idx) {
+            Some(v) => force(m, v.clone(), cont),
+            None => State::Undefined(*idx),
+        },
+        // References hold evaluated values under every strategy; the store
+        // only grows, and an assignment yields the value assigned.
+        Ast::Ref(init) => State::Eval(init.clone(), env, Cont::MakeRef(stats::shared_frame(cont))),
+        Ast::Deref(r) => State::Eval(r.clone(), env, Cont::Deref(stats::shared_frame(cont))),
+        Ast::Assign(r, rhs) => State::Eval(
+            r.clone(),
+            env.clone(),
+            Cont::EvalAssign(rhs.clone(), env, stats::shared_frame(cont)),
+        ),
+        Ast::Raise(exn) => State::Eval(exn.clone(), env, Cont::Raise(stats::shared_frame(cont))),
+        Ast::CallCC(func) => {
+            State::Eval(func.clone(), env, Cont::CallCC(stats::shared_frame(cont)))
+        }
+        Ast::Perform(op, arg) => State::Eval(
+            arg.clone(),
+            env,
+            Cont::Perform(op.clone(), stats::shared_frame(cont)),
+        ),
+        Ast::HandleEff(body, clauses, ret) => State::Eval(
+            body.clone(),
+            env.clone(),
+            Cont::EffHandler(clauses.clone(), ret.clone(), env, stats::shared_frame(cont)),
+        ),
+        // `Reset` saves the continuation and starts an empty one; `Shift`
+        // captures the continuation up to there and runs its body in the
+        // empty one, so the reset stays in place.
+        Ast::Reset(body) => {
+            m.meta.push(cont);
+            State::Eval(body.clone(), env, Cont::Cont0)
+        }
+        Ast::Shift(body) => State::Eval(
+            body.clone(),
+            env::cons(val::delim(stats::shared_frame(cont)), env),
+            Cont::Cont0,
+        ),
+        Ast::Handle(body, handler) => State::Eval(
+            body.clone(),
+            env.clone(),
+            Cont::Handler(handler.clone(), env, stats::shared_frame(cont)),
         ),
     }
 }
 
+pub fn run(m: &mut Machine, state: State) -> Outcome {
+    let mut state = state;
+    loop {
+        state = match state {
+            State::Halt(v) => {
+                m.meta.clear();
+                return Outcome::Done(v);
+            }
+            State::Unhandled(op, arg) => {
+                m.meta.clear();
+                return Outcome::Unhandled(op, arg);
+            }
+            State::Undefined(idx) => {
+                m.meta.clear();
+                return Outcome::Undefined(idx);
+            }
+            State::Eval(..) if m.fuel == Some(0) => return Outcome::OutOfFuel(state),
+            State::Eval(ast, env, cont) => {
+                m.steps += 1;
+                m.fuel = m.fuel.map(|fuel| fuel - 1);
+                eval_term(m, ast, env, cont)
+            }
+            State::Apply(cont, v) => apply_cont(m, cont, v),
+            // exceptions propagate through resets
+            State::Unwind(Cont::Cont0, exn) => match m.meta.pop() {
+                Some(cont) => State::Unwind(cont, exn),
+                None => {
+                    m.meta.clear();
+                    return Outcome::Uncaught(exn);
+                }
+            },
+            State::Unwind(cont, exn) => unwind(cont, exn),
+        }
+    }
+}
+
+// Running out of fuel, uncaught exceptions, unhandled operations and
+// undefined globals are reported as errors; use `run` to tell them apart.
+pub fn eval_with(m: &mut Machine, ast: Rc<Ast>, env: Rc<Env<Val>>, cont: Cont) -> Rc<Val> {
+    match run(m, State::Eval(ast, env, cont)) {
+        Outcome::Done(v) => v,
+        Outcome::Uncaught(_)
+        | Outcome::Unhandled(_, _)
+        | Outcome::Undefined(_)
+        | Outcome::OutOfFuel(_) => val::error(),
+    }
+}
+
+pub fn eval(ast: Rc<Ast>, env: Rc<Env<Val>>, cont: Cont) -> Rc<Val> {
+    eval_with(&mut Machine::new(Strategy::CallByValue), ast, env, cont)
+}
+
+// Install the top-level definitions of a program, in order. Under
+// call-by-value a definition is evaluated right away, so it can only use
+// later ones under a lambda; otherwise it is delayed like an argument.
+// Fails with the index of a definition that needs a later one.
+pub fn define_globals(m: &mut Machine, defs: &[Rc<Ast>]) -> Result<(), usize> {
+    m.globals.clear();
+    for (i, def) in defs.iter().enumerate() {
+        let v = match m.strategy {
+            Strategy::CallByValue => {
+                match run(m, State::Eval(def.clone(), env::empty(), Cont::Cont0)) {
+                    Outcome::Undefined(_) => return Err(i),
+                    Outcome::Done(v) => v,
+                    Outcome::Uncaught(_) | Outcome::Unhandled(_, _) | Outcome::OutOfFuel(_) => {
+                        val::error()
+                    }
+                }
+            }
+            _ => val::thunk(def.clone(), env::empty()),
+        };
+        m.globals.push(v);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::stats::Stats;
     use crate::stlc::eval_defunct1::ast;
     use crate::stlc::eval_defunct1::env::{cons, empty, Env};
     use crate::stlc::eval_defunct1::val;
 
+    const STRATEGIES: [Strategy; 3] = [
+        Strategy::CallByValue,
+        Strategy::CallByName,
+        Strategy::CallByNeed,
+    ];
+
+    fn env1() -> Rc<Env<val::Val>> {
+        cons(val::int(10), cons(val::int(20), empty()))
+    }
+
+    // (\x. x x) (\x. x x)
+    fn omega() -> Rc<Ast> {
+        let w = ast::lam(ast::app(ast::var(0), ast::var(0)));
+        ast::app(w.clone(), w)
+    }
+
     #[test]
     fn eval_literal() {
-        fn env1() -> Rc<Env<val::Val>> {
-            cons(val::int(10), cons(val::int(20), empty()))
-        }
         assert_eq!(eval(ast::int(1), env1(), Cont::Cont0), val::int(1));
         assert_eq!(eval(ast::var(0), env1(), Cont::Cont0), val::int(10));
         assert_eq!(eval(ast::var(1), env1(), Cont::Cont0), val::int(20));
@@ -66,4 +586,699 @@ mod tests {
             val::int(33)
         )
     }
+
+    #[test]
+    fn eval_strategies() {
+        for strategy in STRATEGIES {
+            let mut m = Machine::new(strategy);
+            assert_eq!(
+                eval_with(&mut m, ast::var(1), env1(), Cont::Cont0),
+                val::int(20)
+            );
+            assert_eq!(
+                eval_with(
+                    &mut m,
+                    ast::app(
+                        ast::app(ast::lam(ast::lam(ast::var(1))), ast::int(33)),
+                        ast::int(44)
+                    ),
+                    env1(),
+                    Cont::Cont0
+                ),
+                val::int(33)
+            );
+        }
+    }
+
+    #[test]
+    fn eval_unused_divergent_argument() {
+        // diverges under call-by-value, which evaluates omega first
+        let expr = ast::app(ast::lam(ast::int(7)), omega());
+        for strategy in [Strategy::CallByName, Strategy::CallByNeed] {
+            let mut m = Machine::new(strategy);
+            assert_eq!(
+                eval_with(&mut m, expr.clone(), empty(), Cont::Cont0),
+                val::int(7)
+            );
+        }
+    }
+
+    #[test]
+    fn eval_need_shares_work() {
+        // (\x. x (x 5)) ((\y. y) (\z. z)): the argument is used twice
+        let expr = ast::app(
+            ast::lam(ast::app(ast::var(0), ast::app(ast::var(0), ast::int(5)))),
+            ast::app(ast::lam(ast::var(0)), ast::lam(ast::var(0))),
+        );
+        let mut by_name = Machine::new(Strategy::CallByName);
+        let mut by_need = Machine::new(Strategy::CallByNeed);
+        assert_eq!(
+            eval_with(&mut by_name, expr.clone(), empty(), Cont::Cont0),
+            val::int(5)
+        );
+        assert_eq!(
+            eval_with(&mut by_need, expr.clone(), empty(), Cont::Cont0),
+            val::int(5)
+        );
+        assert!(by_need.steps < by_name.steps);
+
+        // without repeated uses there is nothing to share
+        let expr = ast::app(
+            ast::lam(ast::app(ast::var(0), ast::int(5))),
+            ast::lam(ast::var(0)),
+        );
+        let mut by_name = Machine::new(Strategy::CallByName);
+        let mut by_need = Machine::new(Strategy::CallByNeed);
+        assert_eq!(
+            eval_with(&mut by_name, expr.clone(), empty(), Cont::Cont0),
+            val::int(5)
+        );
+        assert_eq!(
+            eval_with(&mut by_need, expr.clone(), empty(), Cont::Cont0),
+            val::int(5)
+        );
+        assert_eq!(by_need.steps, by_name.steps);
+    }
+
+    #[test]
+    fn eval_stats_strategies() {
+        // (\x. x ^ x) ("a" ^ "b"): call-by-name concatenates "a" and "b" twice
+        let expr = ast::app(
+            ast::lam(ast::concat(ast::var(0), ast::var(0))),
+            ast::concat(ast::str("a"), ast::str("b")),
+        );
+        let env = empty();
+        let stats: Vec<Stats> = STRATEGIES
+            .iter()
+            .map(|strategy| {
+                let mut m = Machine::new(*strategy);
+                let (v, stats) =
+                    stats::measure(|| eval_with(&mut m, expr.clone(), env.clone(), Cont::Cont0));
+                assert_eq!(v, val::str("abab"));
+                stats
+            })
+            .collect();
+        // the closure, "a", "b", "ab" and "abab"
+        assert_eq!(stats[0].vals, 5);
+        // "a", "b" and "ab" twice, and the thunk
+        assert_eq!(stats[1].vals, 9);
+        // "a", "b" and "ab" once, and the thunk
+        assert_eq!(stats[2].vals, 6);
+        for stats in &stats {
+            assert_eq!(stats.envs, 1);
+            assert_eq!(stats.code, 0);
+        }
+        // call-by-need concatenates once less, but pushes a frame to update
+        // the thunk
+        assert_eq!(stats[1].conts, stats[2].conts);
+        assert!(stats[0].peak_live < stats[2].peak_live);
+    }
+
+    #[test]
+    fn eval_pairs() {
+        let swap = ast::lam(ast::pair(ast::snd(ast::var(0)), ast::fst(ast::var(0))));
+        let expr = ast::fst(ast::app(swap, ast::pair(ast::var(0), ast::var(1))));
+        for strategy in STRATEGIES {
+            let mut m = Machine::new(strategy);
+            assert_eq!(
+                eval_with(&mut m, expr.clone(), env1(), Cont::Cont0),
+                val::int(20)
+            );
+            assert_eq!(
+                eval_with(&mut m, ast::snd(ast::int(1)), env1(), Cont::Cont0),
+                val::error()
+            );
+        }
+        assert_eq!(
+            eval(ast::pair(ast::int(1), ast::var(0)), env1(), Cont::Cont0),
+            val::pair(val::int(1), val::int(10))
+        );
+    }
+
+    #[test]
+    fn eval_sums() {
+        // case e of inl x => x | inr y => snd y
+        let get = ast::lam(ast::case(ast::var(0), ast::var(0), ast::snd(ast::var(0))));
+        for strategy in STRATEGIES {
+            let mut m = Machine::new(strategy);
+            assert_eq!(
+                eval_with(
+                    &mut m,
+                    ast::app(get.clone(), ast::inl(ast::var(1))),
+                    env1(),
+                    Cont::Cont0
+                ),
+                val::int(20)
+            );
+            assert_eq!(
+                eval_with(
+                    &mut m,
+                    ast::app(get.clone(), ast::inr(ast::pair(ast::int(2), ast::int(3)))),
+                    env1(),
+                    Cont::Cont0
+                ),
+                val::int(3)
+            );
+            assert_eq!(
+                eval_with(
+                    &mut m,
+                    ast::case(ast::int(1), ast::var(0), ast::var(0)),
+                    env1(),
+                    Cont::Cont0
+                ),
+                val::error()
+            );
+        }
+    }
+
+    #[test]
+    fn eval_bools_and_strings() {
+        let greet = ast::lam(ast::if_(
+            ast::str_eq(ast::var(0), ast::str("")),
+            ast::str("hello"),
+            ast::concat(ast::str("hello, "), ast::var(0)),
+        ));
+        for strategy in STRATEGIES {
+            let mut m = Machine::new(strategy);
+            assert_eq!(
+                eval_with(
+                    &mut m,
+                    ast::app(greet.clone(), ast::str("world")),
+                    empty(),
+                    Cont::Cont0
+                ),
+                val::str("hello, world")
+            );
+            assert_eq!(
+                eval_with(
+                    &mut m,
+                    ast::app(greet.clone(), ast::str("")),
+                    empty(),
+                    Cont::Cont0
+                ),
+                val::str("hello")
+            );
+            assert_eq!(
+                eval_with(&mut m, ast::length(ast::str("héllo")), empty(), Cont::Cont0),
+                val::int(5)
+            );
+            assert_eq!(
+                eval_with(
+                    &mut m,
+                    ast::if_(ast::int(1), ast::int(2), ast::int(3)),
+                    empty(),
+                    Cont::Cont0
+                ),
+                val::error()
+            );
+            assert_eq!(
+                eval_with(
+                    &mut m,
+                    ast::str_eq(ast::str("a"), ast::int(1)),
+                    empty(),
+                    Cont::Cont0
+                ),
+                val::error()
+            );
+        }
+        // only the branch taken is evaluated
+        let expr = ast::if_(ast::bool(true), ast::str("yes"), omega());
+        assert_eq!(eval(expr, empty(), Cont::Cont0), val::str("yes"));
+    }
+
+    #[test]
+    fn eval_lazy_constructors() {
+        // fst (1, omega) and case inl 2 of ... only diverge under call-by-value
+        let expr = ast::fst(ast::pair(ast::int(1), omega()));
+        let expr2 = ast::case(ast::inr(ast::int(2)), omega(), ast::var(0));
+        let expr3 = ast::case(ast::inl(omega()), ast::int(3), ast::int(4));
+        for strategy in [Strategy::CallByName, Strategy::CallByNeed] {
+            let mut m = Machine::new(strategy);
+            assert_eq!(
+                eval_with(&mut m, expr.clone(), empty(), Cont::Cont0),
+                val::int(1)
+            );
+            assert_eq!(
+                eval_with(&mut m, expr2.clone(), empty(), Cont::Cont0),
+                val::int(2)
+            );
+            assert_eq!(
+                eval_with(&mut m, expr3.clone(), empty(), Cont::Cont0),
+                val::int(3)
+            );
+        }
+        let mut m = Machine::with_fuel(Strategy::CallByValue, 1000);
+        assert_eq!(eval_with(&mut m, expr, empty(), Cont::Cont0), val::error());
+    }
+
+    #[test]
+    fn eval_out_of_fuel() {
+        for strategy in STRATEGIES {
+            let mut m = Machine::with_fuel(strategy, 1000);
+            assert!(matches!(
+                run(&mut m, State::Eval(omega(), empty(), Cont::Cont0)),
+                Outcome::OutOfFuel(State::Eval(_, _, _))
+            ));
+            assert_eq!(m.steps, 1000);
+            assert_eq!(m.fuel, Some(0));
+        }
+
+        // under call-by-value, the unused divergent argument exhausts the fuel
+        let expr = ast::app(ast::lam(ast::int(7)), omega());
+        let mut m = Machine::with_fuel(Strategy::CallByValue, 1000);
+        assert!(matches!(
+            run(&mut m, State::Eval(expr.clone(), empty(), Cont::Cont0)),
+            Outcome::OutOfFuel(_)
+        ));
+        assert_eq!(
+            eval_with(&mut m, expr.clone(), empty(), Cont::Cont0),
+            val::error()
+        );
+        let mut m = Machine::with_fuel(Strategy::CallByName, 1000);
+        assert_eq!(eval_with(&mut m, expr, empty(), Cont::Cont0), val::int(7));
+    }
+
+    #[test]
+    fn eval_resume() {
+        let expr = ast::app(
+            ast::app(ast::lam(ast::lam(ast::var(1))), ast::int(33)),
+            ast::int(44),
+        );
+        let mut m = Machine::with_fuel(Strategy::CallByValue, 2);
+        let state = match run(&mut m, State::Eval(expr, env1(), Cont::Cont0)) {
+            Outcome::OutOfFuel(state) => state,
+            _ => panic!("should run out of fuel"),
+        };
+        m.fuel = Some(100);
+        assert!(matches!(run(&mut m, state), Outcome::Done(v) if v == val::int(33)));
+        assert_eq!(m.steps, 7);
+    }
+
+    #[test]
+    fn eval_refs() {
+        // (\r. (\_. !r) (r := 2)) (ref 1)
+        let expr = ast::app(
+            ast::lam(ast::app(
+                ast::lam(ast::deref(ast::var(1))),
+                ast::assign(ast::var(0), ast::int(2)),
+            )),
+            ast::ref_(ast::int(1)),
+        );
+        let mut m = Machine::new(Strategy::CallByValue);
+        assert_eq!(
+            eval_with(&mut m, expr.clone(), empty(), Cont::Cont0),
+            val::int(2)
+        );
+        assert_eq!(m.store, vec![val::int(2)]);
+        // the assignment is an unused argument, so it never happens lazily
+        for strategy in [Strategy::CallByName, Strategy::CallByNeed] {
+            let mut m = Machine::new(strategy);
+            assert_eq!(
+                eval_with(&mut m, expr.clone(), empty(), Cont::Cont0),
+                val::int(1)
+            );
+            assert_eq!(m.store, vec![val::int(1)]);
+        }
+
+        // aliases share the location: (\r. (\s. (\_. !r) (s := 5)) r) (ref 1)
+        let expr = ast::app(
+            ast::lam(ast::app(
+                ast::lam(ast::app(
+                    ast::lam(ast::deref(ast::var(2))),
+                    ast::assign(ast::var(0), ast::int(5)),
+                )),
+                ast::var(0),
+            )),
+            ast::ref_(ast::int(1)),
+        );
+        assert_eq!(eval(expr, empty(), Cont::Cont0), val::int(5));
+
+        assert_eq!(
+            eval(ast::deref(ast::int(1)), empty(), Cont::Cont0),
+            val::error()
+        );
+        assert_eq!(
+            eval(ast::assign(ast::int(1), ast::int(2)), empty(), Cont::Cont0),
+            val::error()
+        );
+    }
+
+    fn uncaught(expr: Rc<Ast>) -> Option<Rc<val::Val>> {
+        match run(
+            &mut Machine::new(Strategy::CallByValue),
+            State::Eval(expr, empty(), Cont::Cont0),
+        ) {
+            Outcome::Uncaught(exn) => Some(exn),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn eval_raise_handle() {
+        // handle (raise 1) with \x. x, under every strategy
+        let expr = ast::handle(ast::raise(ast::int(1)), ast::var(0));
+        for strategy in STRATEGIES {
+            let mut m = Machine::new(strategy);
+            assert_eq!(
+                eval_with(&mut m, expr.clone(), empty(), Cont::Cont0),
+                val::int(1)
+            );
+        }
+        // the handler is skipped on normal return
+        assert_eq!(
+            eval(ast::handle(ast::int(3), ast::int(4)), empty(), Cont::Cont0),
+            val::int(3)
+        );
+        // raising unwinds through the pending frames: handle ((raise 1) 2, 3) with ...
+        let expr = ast::handle(
+            ast::pair(ast::app(ast::raise(ast::int(1)), ast::int(2)), ast::int(3)),
+            ast::pair(ast::var(0), ast::var(0)),
+        );
+        assert_eq!(
+            eval(expr, empty(), Cont::Cont0),
+            val::pair(val::int(1), val::int(1))
+        );
+
+        assert_eq!(uncaught(ast::raise(ast::int(5))), Some(val::int(5)));
+        assert_eq!(
+            eval(ast::raise(ast::int(5)), empty(), Cont::Cont0),
+            val::error()
+        );
+    }
+
+    #[test]
+    fn eval_nested_handlers() {
+        // the innermost handler catches: handle (1, handle (raise 2) with x) with 9
+        let expr = ast::handle(
+            ast::pair(
+                ast::int(1),
+                ast::handle(ast::raise(ast::int(2)), ast::var(0)),
+            ),
+            ast::int(9),
+        );
+        assert_eq!(
+            eval(expr, empty(), Cont::Cont0),
+            val::pair(val::int(1), val::int(2))
+        );
+
+        // re-raising from a handler goes to the enclosing one:
+        // handle (handle (raise 1) with raise (x, x)) with inl x
+        let expr = ast::handle(
+            ast::handle(
+                ast::raise(ast::int(1)),
+                ast::raise(ast::pair(ast::var(0), ast::var(0))),
+            ),
+            ast::inl(ast::var(0)),
+        );
+        assert_eq!(
+            eval(expr, empty(), Cont::Cont0),
+            val::inl(val::pair(val::int(1), val::int(1)))
+        );
+
+        // and escapes if there is none
+        let expr = ast::handle(ast::raise(ast::int(1)), ast::raise(ast::str("again")));
+        assert_eq!(uncaught(expr), Some(val::str("again")));
+    }
+
+    // let x = bound in body
+    fn let_(bound: Rc<Ast>, body: Rc<Ast>) -> Rc<Ast> {
+        ast::app(ast::lam(body), bound)
+    }
+
+    #[test]
+    fn eval_callcc_early_exit() {
+        // (1, callcc (\k. (2, k 3))): throwing drops the pending pair
+        let expr = ast::pair(
+            ast::int(1),
+            ast::callcc(ast::lam(ast::pair(
+                ast::int(2),
+                ast::app(ast::var(0), ast::int(3)),
+            ))),
+        );
+        assert_eq!(
+            eval(expr, empty(), Cont::Cont0),
+            val::pair(val::int(1), val::int(3))
+        );
+        // returning normally from the body is not an exit
+        let expr = ast::callcc(ast::lam(ast::int(4)));
+        for strategy in STRATEGIES {
+            let mut m = Machine::new(strategy);
+            assert_eq!(
+                eval_with(&mut m, expr.clone(), empty(), Cont::Cont0),
+                val::int(4)
+            );
+        }
+        // the argument of k still runs under the inner handler, the value does not
+        let expr = ast::handle(
+            ast::callcc(ast::lam(ast::handle(
+                ast::app(ast::var(0), ast::raise(ast::int(5))),
+                ast::int(6),
+            ))),
+            ast::int(7),
+        );
+        assert_eq!(eval(expr, empty(), Cont::Cont0), val::int(6));
+        let expr = ast::handle(
+            ast::callcc(ast::lam(ast::handle(
+                ast::app(ast::var(0), ast::int(5)),
+                ast::int(6),
+            ))),
+            ast::int(7),
+        );
+        assert_eq!(eval(expr, empty(), Cont::Cont0), val::int(5));
+    }
+
+    #[test]
+    fn eval_callcc_reentry() {
+        // let acc = ref "" in
+        // let kr = ref 0 in
+        // let x = callcc (\k. kr := k; "a") in
+        // acc := !acc ^ x;
+        // if !acc = "aaa" then !acc else !kr "a"
+        let expr = let_(
+            ast::ref_(ast::str("")),
+            let_(
+                ast::ref_(ast::int(0)),
+                let_(
+                    ast::callcc(ast::lam(let_(
+                        ast::assign(ast::var(1), ast::var(0)),
+                        ast::str("a"),
+                    ))),
+                    let_(
+                        ast::assign(
+                            ast::var(2),
+                            ast::concat(ast::deref(ast::var(2)), ast::var(0)),
+                        ),
+                        ast::if_(
+                            ast::str_eq(ast::deref(ast::var(3)), ast::str("aaa")),
+                            ast::deref(ast::var(3)),
+                            ast::app(ast::deref(ast::var(2)), ast::str("a")),
+                        ),
+                    ),
+                ),
+            ),
+        );
+        let mut m = Machine::new(Strategy::CallByValue);
+        assert_eq!(
+            eval_with(&mut m, expr, empty(), Cont::Cont0),
+            val::str("aaa")
+        );
+        assert!(matches!(&*m.store[1], val::Val::Cont(_, _)));
+    }
+
+    #[test]
+    fn eval_shift_reset() {
+        // reset (1, shift k. "x"): the body replaces the whole reset
+        let expr = ast::reset(ast::pair(ast::int(1), ast::shift(ast::str("x"))));
+        assert_eq!(eval(expr, empty(), Cont::Cont0), val::str("x"));
+        // reset (1, shift k. k (k 2)): the continuation returns, so composes
+        let expr = ast::reset(ast::pair(
+            ast::int(1),
+            ast::shift(ast::app(ast::var(0), ast::app(ast::var(0), ast::int(2)))),
+        ));
+        assert_eq!(
+            eval(expr, empty(), Cont::Cont0),
+            val::pair(val::int(1), val::pair(val::int(1), val::int(2)))
+        );
+        // exceptions cross the delimiter
+        let expr = ast::handle(ast::reset(ast::raise(ast::int(3))), ast::var(0));
+        let mut m = Machine::new(Strategy::CallByValue);
+        assert_eq!(eval_with(&mut m, expr, empty(), Cont::Cont0), val::int(3));
+        assert!(m.meta.is_empty());
+    }
+
+    #[test]
+    fn eval_callcc_across_reset() {
+        // callcc (\k. (2, reset (k 1))): the throw leaves the reset as well
+        let expr = ast::callcc(ast::lam(ast::pair(
+            ast::int(2),
+            ast::reset(ast::app(ast::var(0), ast::int(1))),
+        )));
+        let mut m = Machine::new(Strategy::CallByValue);
+        assert_eq!(eval_with(&mut m, expr, empty(), Cont::Cont0), val::int(1));
+        assert!(m.meta.is_empty());
+        // (3, reset (4, callcc (\k. k 5))): a throw within the reset stays in it
+        let expr = ast::pair(
+            ast::int(3),
+            ast::reset(ast::pair(
+                ast::int(4),
+                ast::callcc(ast::lam(ast::app(ast::var(0), ast::int(5)))),
+            )),
+        );
+        assert_eq!(
+            eval(expr, empty(), Cont::Cont0),
+            val::pair(val::int(3), val::pair(val::int(4), val::int(5)))
+        );
+    }
+
+    #[test]
+    fn eval_shift_all_results() {
+        // flip = shift k. k "a" ^ k "b"; all results of flip ^ flip
+        let flip = ast::shift(ast::concat(
+            ast::app(ast::var(0), ast::str("a")),
+            ast::app(ast::var(0), ast::str("b")),
+        ));
+        let expr = ast::reset(ast::concat(flip.clone(), flip));
+        assert_eq!(eval(expr, empty(), Cont::Cont0), val::str("aaabbabb"));
+    }
+
+    #[test]
+    fn eval_shift_state() {
+        // get = shift k. \s. k s s, and put v = shift k. \s. k v v
+        let get = ast::shift(ast::lam(ast::app(
+            ast::app(ast::var(1), ast::var(0)),
+            ast::var(0),
+        )));
+        // (reset (let x = get in put (x ^ "!"); get; \s. result)) "hi"
+        let put = ast::shift(ast::lam(ast::app(
+            ast::app(ast::var(1), ast::concat(ast::var(2), ast::str("!"))),
+            ast::concat(ast::var(2), ast::str("!")),
+        )));
+        let body = let_(get.clone(), let_(put, get));
+        let expr = ast::app(
+            ast::reset(let_(body, ast::lam(ast::var(1)))),
+            ast::str("hi"),
+        );
+        assert_eq!(eval(expr, empty(), Cont::Cont0), val::str("hi!"));
+    }
+
+    #[test]
+    fn eval_effects_exceptions() {
+        // handle (1, perform fail 2) with fail x k -> x | return v -> inl v
+        let catch =
+            |body| ast::handle_eff(body, vec![("fail", ast::var(1))], ast::inl(ast::var(0)));
+        let expr = catch(ast::pair(ast::int(1), ast::perform("fail", ast::int(2))));
+        assert_eq!(eval(expr, empty(), Cont::Cont0), val::int(2));
+        let expr = catch(ast::pair(ast::int(1), ast::int(2)));
+        assert_eq!(
+            eval(expr, empty(), Cont::Cont0),
+            val::inl(val::pair(val::int(1), val::int(2)))
+        );
+        // unhandled operations are reported with their argument
+        let mut m = Machine::new(Strategy::CallByValue);
+        let expr = catch(ast::reset(ast::perform("get", ast::int(0))));
+        assert!(matches!(
+            run(&mut m, State::Eval(expr.clone(), empty(), Cont::Cont0)),
+            Outcome::Unhandled(op, arg) if op == "get" && arg == val::int(0)
+        ));
+        assert!(m.meta.is_empty());
+        assert_eq!(eval(expr, empty(), Cont::Cont0), val::error());
+    }
+
+    #[test]
+    fn eval_effects_across_reset() {
+        // handle (2, reset (1, (perform get 0; shift k. 7))) with get x k -> k 5:
+        // the resumption reinstates the reset, which the shift then finds
+        let expr = ast::handle_eff(
+            ast::pair(
+                ast::int(2),
+                ast::reset(ast::pair(
+                    ast::int(1),
+                    let_(ast::perform("get", ast::int(0)), ast::shift(ast::int(7))),
+                )),
+            ),
+            vec![("get", ast::app(ast::var(0), ast::int(5)))],
+            ast::var(0),
+        );
+        let mut m = Machine::new(Strategy::CallByValue);
+        assert_eq!(
+            eval_with(&mut m, expr, empty(), Cont::Cont0),
+            val::pair(val::int(2), val::int(7))
+        );
+        assert!(m.meta.is_empty());
+        // handle (reset (1, perform get 0)) with get x k -> k 5
+        let expr = ast::handle_eff(
+            ast::reset(ast::pair(ast::int(1), ast::perform("get", ast::int(0)))),
+            vec![("get", ast::app(ast::var(0), ast::int(5)))],
+            ast::var(0),
+        );
+        assert_eq!(
+            eval(expr, empty(), Cont::Cont0),
+            val::pair(val::int(1), val::int(5))
+        );
+    }
+
+    #[test]
+    fn eval_effects_state() {
+        // return x -> \s. x | get _ k -> \s. k s s | put v k -> \s. k v v
+        let run_state = |body| {
+            ast::handle_eff(
+                body,
+                vec![
+                    (
+                        "get",
+                        ast::lam(ast::app(ast::app(ast::var(1), ast::var(0)), ast::var(0))),
+                    ),
+                    (
+                        "put",
+                        ast::lam(ast::app(ast::app(ast::var(1), ast::var(2)), ast::var(2))),
+                    ),
+                ],
+                ast::lam(ast::var(1)),
+            )
+        };
+        // let x = get () in put (x ^ "!"); get ()
+        let body = let_(
+            ast::perform("get", ast::int(0)),
+            let_(
+                ast::perform("put", ast::concat(ast::var(0), ast::str("!"))),
+                ast::perform("get", ast::int(0)),
+            ),
+        );
+        let expr = ast::app(run_state(body), ast::str("hi"));
+        assert_eq!(eval(expr, empty(), Cont::Cont0), val::str("hi!"));
+    }
+
+    #[test]
+    fn eval_effects_nondeterminism() {
+        // flip _ k -> k "a" ^ k "b": all results of flip () ^ flip ()
+        let all = |body| {
+            ast::handle_eff(
+                body,
+                vec![(
+                    "flip",
+                    ast::concat(
+                        ast::app(ast::var(0), ast::str("a")),
+                        ast::app(ast::var(0), ast::str("b")),
+                    ),
+                )],
+                ast::var(0),
+            )
+        };
+        let flip = ast::perform("flip", ast::int(0));
+        let expr = all(ast::concat(flip.clone(), flip.clone()));
+        assert_eq!(eval(expr, empty(), Cont::Cont0), val::str("aaabbabb"));
+
+        // operations the inner handler does not know go to the outer one:
+        // the inner one fails on "b", the outer one chooses
+        let expr = all(ast::handle_eff(
+            ast::if_(
+                ast::str_eq(flip, ast::str("b")),
+                ast::perform("fail", ast::int(0)),
+                ast::str("a"),
+            ),
+            vec![("fail", ast::str("-"))],
+            ast::var(0),
+        ));
+        assert_eq!(eval(expr, empty(), Cont::Cont0), val::str("a-"));
+    }
 }
@@ -2,3 +2,4 @@ pub mod ast;
 pub mod val;
 pub mod env;
 pub mod eval;
+pub mod print;
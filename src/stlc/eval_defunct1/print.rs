@@ -0,0 +1,200 @@
+use crate::pretty;
+use crate::pretty::{Ctx, Prec, Term};
+use crate::stlc::eval_defunct1::ast::Ast;
+use crate::stlc::eval_defunct1::env::Env;
+use crate::stlc::eval_defunct1::val::{Thunk, Val};
+use std::fmt;
+
+impl Term for Ast {
+    fn prec(&self) -> Prec {
+        match self {
+            Ast::Int(_)
+            | Ast::Var(_)
+            | Ast::Pair(_, _)
+            | Ast::Bool(_)
+            | Ast::Str(_)
+            | Ast::Global(_)
+            | Ast::Deref(_) => Prec::Atom,
+            Ast::App(_, _)
+            | Ast::Fst(_)
+            | Ast::Snd(_)
+            | Ast::Inl(_)
+            | Ast::Inr(_)
+            | Ast::Length(_)
+            | Ast::Ref(_)
+            | Ast::Raise(_)
+            | Ast::CallCC(_)
+            | Ast::Reset(_)
+            | Ast::Perform(_, _) => Prec::App,
+            _ => Prec::Open,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ast::Int(i) => write!(f, "{}", i),
+            Ast::Var(idx) => write!(f, "{}", ctx.var(*idx, lev)),
+            Ast::Lam(body) => pretty::lam(&**body, ctx, lev, f),
+            Ast::App(func, arg) => pretty::app(&**func, &**arg, ctx, lev, f),
+            Ast::Pair(fst, snd) => pretty::pair(&**fst, &**snd, ctx, lev, f),
+            Ast::Fst(pair) => pretty::keyword("fst", &**pair, ctx, lev, f),
+            Ast::Snd(pair) => pretty::keyword("snd", &**pair, ctx, lev, f),
+            Ast::Inl(payload) => pretty::keyword("inl", &**payload, ctx, lev, f),
+            Ast::Inr(payload) => pretty::keyword("inr", &**payload, ctx, lev, f),
+            Ast::Case(scrut, left, right) => pretty::case(&**scrut, &**left, &**right, ctx, lev, f),
+            Ast::Bool(b) => write!(f, "{}", b),
+            Ast::If(cond, then, els) => pretty::if_(&**cond, &**then, &**els, ctx, lev, f),
+            Ast::Str(s) => write!(f, "{:?}", s),
+            Ast::Concat(lhs, rhs) => pretty::infix(&**lhs, "^", &**rhs, ctx, lev, f),
+            Ast::Length(s) => pretty::keyword("length", &**s, ctx, lev, f),
+            Ast::StrEq(lhs, rhs) => pretty::infix(&**lhs, "==", &**rhs, ctx, lev, f),
+            Ast::Global(i) => write!(f, "g{}", i),
+            Ast::Ref(init) => pretty::keyword("ref", &**init, ctx, lev, f),
+            Ast::Deref(loc) => {
+                write!(f, "!")?;
+                pretty::fmt_at(&**loc, Prec::Atom, ctx, lev, f)
+            }
+            Ast::Assign(loc, v) => pretty::infix(&**loc, ":=", &**v, ctx, lev, f),
+            Ast::Raise(exn) => pretty::keyword("raise", &**exn, ctx, lev, f),
+            Ast::Handle(body, handler) => {
+                write!(f, "try ")?;
+                body.fmt_in(ctx, lev, f)?;
+                pretty::bind(|x, f| write!(f, " with {} -> ", x), &**handler, ctx, lev, f)
+            }
+            Ast::CallCC(func) => pretty::keyword("callcc", &**func, ctx, lev, f),
+            Ast::Reset(body) => pretty::keyword("reset", &**body, ctx, lev, f),
+            Ast::Shift(body) => {
+                pretty::bind(|k, f| write!(f, "shift {}. ", k), &**body, ctx, lev, f)
+            }
+            Ast::Perform(op, arg) => {
+                pretty::keyword(&format!("perform {}", op), &**arg, ctx, lev, f)
+            }
+            Ast::HandleEff(body, clauses, ret) => {
+                write!(f, "handle ")?;
+                body.fmt_in(ctx, lev, f)?;
+                write!(f, " with ")?;
+                for (op, clause) in clauses.iter() {
+                    let arg = ctx.push(lev);
+                    let k = ctx.push(lev);
+                    let result = write!(f, "{} {} {} -> ", op, arg, k)
+                        .and_then(|_| clause.fmt_in(ctx, lev, f));
+                    ctx.pop();
+                    ctx.pop();
+                    result?;
+                    write!(f, " | ")?;
+                }
+                pretty::bind(|x, f| write!(f, "return {} -> ", x), &**ret, ctx, lev, f)
+            }
+        }
+    }
+}
+
+pretty::display!(Ast);
+
+// Also the environments of `eval_krivine`, which hold closures.
+pub fn entries<T>(mut env: &Env<T>) -> Vec<&T> {
+    let mut entries = vec![];
+    while let Env::Cons(head, tail) = env {
+        entries.push(&**head);
+        env = tail;
+    }
+    entries
+}
+
+impl<T: fmt::Display> fmt::Display for Env<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pretty::env(&entries(self), f)
+    }
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Val::Error => write!(f, "error"),
+            Val::Int(i) => write!(f, "{}", i),
+            Val::Clos(env, body) => {
+                pretty::closure(&entries(env), f, |ctx, f| pretty::lam(&**body, ctx, 0, f))
+            }
+            Val::Pair(fst, snd) => {
+                let d = pretty::depth(f);
+                write!(f, "({:.*}, {:.*})", d, fst, d, snd)
+            }
+            Val::Inl(payload) => write!(f, "inl {:.*}", pretty::depth(f), payload),
+            Val::Inr(payload) => write!(f, "inr {:.*}", pretty::depth(f), payload),
+            Val::Bool(b) => write!(f, "{}", b),
+            Val::Str(s) => write!(f, "{:?}", s),
+            Val::Loc(l) => write!(f, "ref@{}", l),
+            Val::Cont(_, _) => write!(f, "<continuation>"),
+            Val::Delim(_) => write!(f, "<delimited continuation>"),
+            Val::Resumption(_) => write!(f, "<resumption>"),
+            Val::Thunk(state) => match &*state.borrow() {
+                // the code of a thunk is not under a binder
+                Thunk::Delayed(ast, env) => {
+                    pretty::closure(&entries(env), f, |ctx, f| ast.fmt_in(ctx, 0, f))
+                }
+                Thunk::Forced(v) => v.fmt(f),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stlc::eval_defunct1::ast::*;
+    use crate::stlc::eval_defunct1::{env, val};
+
+    #[test]
+    fn print_ast() {
+        let counter = app(
+            lam(handle(
+                assign(var(0), app(global(0), deref(var(0)))),
+                raise(var(0)),
+            )),
+            ref_(int(0)),
+        );
+        assert_eq!(
+            counter.to_string(),
+            "(\\x0. try x0 := g0 !x0 with x1 -> raise x1) (ref 0)"
+        );
+        let eff = handle_eff(
+            app(int(1), perform("get", int(0))),
+            vec![("get", app(var(0), var(1)))],
+            pair(var(0), callcc(lam(var(0)))),
+        );
+        assert_eq!(
+            eff.to_string(),
+            "handle 1 (perform get 0) with get x0 x1 -> x1 x0 | return x0 -> (x0, callcc (\\x1. x1))"
+        );
+    }
+
+    #[test]
+    fn print_val() {
+        let env = env::cons(val::loc(3), env::empty());
+        let thunk = val::thunk(app(var(0), int(1)), env.clone());
+        assert_eq!(thunk.to_string(), "{x0 1 | x0 = ref@3}");
+        assert_eq!(
+            val::clos(env::cons(thunk, env), var(1)).to_string(),
+            "{\\x2. x1 | x1 = {x0 1 | x0 = ref@3}, x0 = ref@3}"
+        );
+    }
+
+    #[test]
+    fn print_nested_pairs() {
+        // the depth passes through pairs and sums to the closures inside
+        let env = env::cons(val::int(1), env::cons(val::int(2), env::empty()));
+        let clos = val::clos(env, var(0));
+        let v = val::pair(val::inl(clos.clone()), val::pair(val::int(3), clos));
+        assert_eq!(
+            v.to_string(),
+            "(inl {\\x2. x2 | x1 = 1, x0 = 2}, (3, {\\x2. x2 | x1 = 1, x0 = 2}))"
+        );
+        assert_eq!(
+            format!("{:.1}", v),
+            "(inl {\\x2. x2 | x1 = 1, ..}, (3, {\\x2. x2 | x1 = 1, ..}))"
+        );
+        assert_eq!(
+            format!("{:.0}", v),
+            "(inl {\\x2. x2 | ..}, (3, {\\x2. x2 | ..}))"
+        );
+    }
+}
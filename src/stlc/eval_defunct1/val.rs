@@ -1,23 +1,90 @@
+use std::cell::RefCell;
 use std::rc::Rc;
 use crate::stlc::eval_defunct1::ast::Ast;
 use crate::stlc::eval_defunct1::env::Env;
+use crate::stlc::eval_defunct1::eval::Cont;
+use crate::stats;
+use crate::stats::Kind;
+
+stats::counted!(Val);
 
 #[derive(Eq, PartialEq, Debug)]
 
 pub enum Val {
     Error,
     Int(i32),
-    Clos(Rc<Env<Val>>, Rc<Ast>)
+    Clos(Rc<Env<Val>>, Rc<Ast>),
+    Pair(Rc<Val>, Rc<Val>),
+    Inl(Rc<Val>),
+    Inr(Rc<Val>),
+    Bool(bool),
+    Str(String),
+    Loc(usize), // index into the store of the machine
+    Cont(Rc<Cont>, Rc<Vec<Cont>>), // captured by call/cc, with the enclosing resets
+    Delim(Rc<Cont>), // captured by shift, up to the nearest reset
+    Resumption(Rc<Vec<Cont>>), // frames up to an effect handler, innermost first
+    Thunk(RefCell<Thunk>) // delayed argument, only under call-by-name/need
+}
+
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Thunk {
+    Delayed(Rc<Ast>, Rc<Env<Val>>),
+    Forced(Rc<Val>) // memoized result, only under call-by-need
+}
+
+fn alloc(v: Val) -> Rc<Val> {
+    stats::alloc(Kind::Val);
+    Rc::new(v)
 }
 
 pub fn error() -> Rc<Val> {
-    return Rc::new(Val::Error)
+    return alloc(Val::Error)
 }
 
 pub fn int(i: i32) -> Rc<Val> {
-    return Rc::new(Val::Int(i))
+    return alloc(Val::Int(i))
 }
 
 pub fn clos(env: Rc<Env<Val>>, body: Rc<Ast>) -> Rc<Val> {
-    return Rc::new(Val::Clos(env, body))
+    return alloc(Val::Clos(env, body))
+}
+
+pub fn pair(v1: Rc<Val>, v2: Rc<Val>) -> Rc<Val> {
+    alloc(Val::Pair(v1, v2))
+}
+
+pub fn inl(v: Rc<Val>) -> Rc<Val> {
+    alloc(Val::Inl(v))
+}
+
+pub fn inr(v: Rc<Val>) -> Rc<Val> {
+    alloc(Val::Inr(v))
+}
+
+pub fn bool(b: bool) -> Rc<Val> {
+    alloc(Val::Bool(b))
+}
+
+pub fn str(s: &str) -> Rc<Val> {
+    alloc(Val::Str(String::from(s)))
+}
+
+pub fn loc(l: usize) -> Rc<Val> {
+    alloc(Val::Loc(l))
+}
+
+pub fn cont(k: Rc<Cont>, meta: Rc<Vec<Cont>>) -> Rc<Val> {
+    alloc(Val::Cont(k, meta))
+}
+
+pub fn delim(k: Rc<Cont>) -> Rc<Val> {
+    alloc(Val::Delim(k))
+}
+
+pub fn resumption(frames: Rc<Vec<Cont>>) -> Rc<Val> {
+    alloc(Val::Resumption(frames))
+}
+
+pub fn thunk(ast: Rc<Ast>, env: Rc<Env<Val>>) -> Rc<Val> {
+    alloc(Val::Thunk(RefCell::new(Thunk::Delayed(ast, env))))
 }
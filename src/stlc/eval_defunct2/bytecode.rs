@@ -0,0 +1,521 @@
+use crate::stats;
+use crate::stats::Kind;
+use crate::stlc::eval_defunct2::data::{Ast, Compt};
+use crate::stlc::eval_defunct2::eval::eval;
+use std::rc::Rc;
+
+// Flat code for the defunctionalized machine. The main program starts at
+// address 0 and ends with `Halt`; each lambda body and branch follows it,
+// ending with `Return`.
+#[derive(Eq, PartialEq, Debug, Clone)]
+pub enum Instr {
+    Lit(i32),
+    Access(usize),
+    Close(usize), // address of the body
+    Apply,        // pop an argument and a closure, and call it
+    Return,
+    Halt,
+    Pair, // pop two components
+    Fst,
+    Snd,
+    Inl,
+    Inr,
+    Case(usize, usize), // pop a sum and call the branch for it with the payload
+    Bool(bool),
+    If(usize, usize), // pop a boolean and call the branch for it
+    Str(String),
+    Concat,
+    Length,
+    StrEq,
+    Global(usize),
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Val {
+    Error,
+    Int(i32),
+    Clos(usize, Rc<Env>), // code address and environment
+    Pair(Rc<Val>, Rc<Val>),
+    Inl(Rc<Val>),
+    Inr(Rc<Val>),
+    Bool(bool),
+    Str(String),
+    Undefined(usize), // error: a global used before it is defined
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Env {
+    Nil,
+    Cons(Rc<Val>, Rc<Env>),
+}
+
+stats::counted!(Instr, Val, Env);
+
+pub mod val {
+    use super::*;
+
+    fn alloc(v: Val) -> Rc<Val> {
+        stats::alloc(Kind::Val);
+        Rc::new(v)
+    }
+
+    pub fn error() -> Rc<Val> {
+        alloc(Val::Error)
+    }
+
+    pub fn int(i: i32) -> Rc<Val> {
+        alloc(Val::Int(i))
+    }
+
+    pub fn clos(addr: usize, env: Rc<Env>) -> Rc<Val> {
+        alloc(Val::Clos(addr, env))
+    }
+
+    pub fn pair(v1: Rc<Val>, v2: Rc<Val>) -> Rc<Val> {
+        alloc(Val::Pair(v1, v2))
+    }
+
+    pub fn inl(v: Rc<Val>) -> Rc<Val> {
+        alloc(Val::Inl(v))
+    }
+
+    pub fn inr(v: Rc<Val>) -> Rc<Val> {
+        alloc(Val::Inr(v))
+    }
+
+    pub fn bool(b: bool) -> Rc<Val> {
+        alloc(Val::Bool(b))
+    }
+
+    pub fn str(s: &str) -> Rc<Val> {
+        alloc(Val::Str(String::from(s)))
+    }
+
+    pub fn undefined(idx: usize) -> Rc<Val> {
+        alloc(Val::Undefined(idx))
+    }
+}
+
+pub mod env {
+    use super::*;
+
+    fn alloc(env: Env) -> Rc<Env> {
+        stats::alloc(Kind::Env);
+        Rc::new(env)
+    }
+
+    pub fn empty() -> Rc<Env> {
+        alloc(Env::Nil)
+    }
+
+    pub fn cons(elm: Rc<Val>, env: Rc<Env>) -> Rc<Env> {
+        alloc(Env::Cons(elm, env))
+    }
+
+    pub fn lookup(env: &Rc<Env>, idx: usize) -> Option<Rc<Val>> {
+        let mut env = env;
+        let mut idx = idx;
+        loop {
+            match &**env {
+                Env::Nil => return None,
+                Env::Cons(head, _) if idx == 0 => return Some(head.clone()),
+                Env::Cons(_, tail) => {
+                    env = tail;
+                    idx -= 1;
+                }
+            }
+        }
+    }
+}
+
+// A body waiting to be emitted: the position of the instruction that
+// refers to it, which of its addresses to patch, and the code.
+type Pending = (usize, usize, Rc<Compt>);
+
+// Emit `compt` in evaluation order: `Push(f, a)` becomes f, a, Apply.
+fn emit(compt: &Compt, code: &mut Vec<Instr>, bodies: &mut Vec<Pending>) {
+    match compt {
+        Compt::Lit(i) => code.push(Instr::Lit(*i)),
+        Compt::Access(idx) => code.push(Instr::Access(*idx)),
+        Compt::Close(body) => {
+            bodies.push((code.len(), 0, body.clone()));
+            code.push(Instr::Close(0));
+        }
+        Compt::Push(func, arg) => {
+            emit(func, code, bodies);
+            emit(arg, code, bodies);
+            code.push(Instr::Apply);
+        }
+        Compt::Pair(fst, snd) => {
+            emit(fst, code, bodies);
+            emit(snd, code, bodies);
+            code.push(Instr::Pair);
+        }
+        Compt::Fst(pair) => {
+            emit(pair, code, bodies);
+            code.push(Instr::Fst);
+        }
+        Compt::Snd(pair) => {
+            emit(pair, code, bodies);
+            code.push(Instr::Snd);
+        }
+        Compt::Inl(payload) => {
+            emit(payload, code, bodies);
+            code.push(Instr::Inl);
+        }
+        Compt::Inr(payload) => {
+            emit(payload, code, bodies);
+            code.push(Instr::Inr);
+        }
+        Compt::Case(scrut, left, right) => {
+            emit(scrut, code, bodies);
+            bodies.push((code.len(), 0, left.clone()));
+            bodies.push((code.len(), 1, right.clone()));
+            code.push(Instr::Case(0, 0));
+        }
+        Compt::Bool(b) => code.push(Instr::Bool(*b)),
+        Compt::If(cond, then, els) => {
+            emit(cond, code, bodies);
+            bodies.push((code.len(), 0, then.clone()));
+            bodies.push((code.len(), 1, els.clone()));
+            code.push(Instr::If(0, 0));
+        }
+        Compt::Str(s) => code.push(Instr::Str(s.clone())),
+        Compt::Concat(lhs, rhs) => {
+            emit(lhs, code, bodies);
+            emit(rhs, code, bodies);
+            code.push(Instr::Concat);
+        }
+        Compt::Length(s) => {
+            emit(s, code, bodies);
+            code.push(Instr::Length);
+        }
+        Compt::StrEq(lhs, rhs) => {
+            emit(lhs, code, bodies);
+            emit(rhs, code, bodies);
+            code.push(Instr::StrEq);
+        }
+        Compt::Global(idx) => code.push(Instr::Global(*idx)),
+    }
+}
+
+fn patch(instr: &mut Instr, slot: usize, addr: usize) {
+    match (instr, slot) {
+        (Instr::Close(a), _)
+        | (Instr::Case(a, _) | Instr::If(a, _), 0)
+        | (Instr::Case(_, a) | Instr::If(_, a), _) => *a = addr,
+        _ => unreachable!(),
+    }
+}
+
+pub fn lower(compt: &Compt) -> Vec<Instr> {
+    let mut code = vec![];
+    lower_into(compt, &mut code);
+    code
+}
+
+// Append the code for `compt` to `code` and return its start address.
+fn lower_into(compt: &Compt, code: &mut Vec<Instr>) -> usize {
+    let start = code.len();
+    let mut bodies = vec![];
+    emit(compt, code, &mut bodies);
+    code.push(Instr::Halt);
+    while let Some((at, slot, body)) = bodies.pop() {
+        let addr = code.len();
+        patch(&mut code[at], slot, addr);
+        emit(&body, code, &mut bodies);
+        code.push(Instr::Return);
+    }
+    for _ in &code[start..] {
+        stats::alloc(Kind::Code); // one per instruction
+    }
+    start
+}
+
+// The operand stack holds intermediate values, the return stack the
+// caller's address and environment. Return entries count as continuation
+// frames in the statistics.
+pub fn run(code: &[Instr], env: Rc<Env>) -> Rc<Val> {
+    run_with(&[], code, 0, env)
+}
+
+// Like `run`, starting at `pc`, with `globals` holding the values of
+// top-level definitions.
+pub fn run_with(globals: &[Rc<Val>], code: &[Instr], pc: usize, env: Rc<Env>) -> Rc<Val> {
+    let mut pc = pc;
+    let mut env = env;
+    let mut stack: Vec<Rc<Val>> = vec![];
+    let mut rstack: Vec<(usize, Rc<Env>)> = vec![];
+    loop {
+        match code.get(pc) {
+            Some(Instr::Lit(i)) => stack.push(val::int(*i)),
+            Some(Instr::Access(idx)) => match env::lookup(&env, *idx) {
+                Some(v) => stack.push(v),
+                None => return val::error(),
+            },
+            Some(Instr::Close(addr)) => stack.push(val::clos(*addr, env.clone())),
+            Some(Instr::Apply) => {
+                let (Some(arg), Some(func)) = (stack.pop(), stack.pop()) else {
+                    return val::error();
+                };
+                let Val::Clos(addr, cenv) = &*func else {
+                    return val::error();
+                };
+                stats::count(Kind::Cont);
+                rstack.push((pc + 1, env));
+                env = env::cons(arg, cenv.clone());
+                pc = *addr;
+                continue;
+            }
+            Some(Instr::Return) => match rstack.pop() {
+                Some((ret, renv)) => {
+                    pc = ret;
+                    env = renv;
+                    continue;
+                }
+                None => return val::error(),
+            },
+            Some(Instr::Halt) => return stack.pop().unwrap_or_else(val::error),
+            Some(Instr::Pair) => {
+                let (Some(sndv), Some(fstv)) = (stack.pop(), stack.pop()) else {
+                    return val::error();
+                };
+                stack.push(val::pair(fstv, sndv));
+            }
+            Some(Instr::Fst) => match stack.pop().as_deref() {
+                Some(Val::Pair(fstv, _)) => stack.push(fstv.clone()),
+                _ => return val::error(),
+            },
+            Some(Instr::Snd) => match stack.pop().as_deref() {
+                Some(Val::Pair(_, sndv)) => stack.push(sndv.clone()),
+                _ => return val::error(),
+            },
+            Some(Instr::Inl) => match stack.pop() {
+                Some(v) => stack.push(val::inl(v)),
+                None => return val::error(),
+            },
+            Some(Instr::Inr) => match stack.pop() {
+                Some(v) => stack.push(val::inr(v)),
+                None => return val::error(),
+            },
+            Some(Instr::Case(left, right)) => {
+                let (addr, payload) = match stack.pop().as_deref() {
+                    Some(Val::Inl(payload)) => (*left, payload.clone()),
+                    Some(Val::Inr(payload)) => (*right, payload.clone()),
+                    _ => return val::error(),
+                };
+                stats::count(Kind::Cont);
+                rstack.push((pc + 1, env.clone()));
+                env = env::cons(payload, env);
+                pc = addr;
+                continue;
+            }
+            Some(Instr::Bool(b)) => stack.push(val::bool(*b)),
+            Some(Instr::If(then, els)) => {
+                let addr = match stack.pop().as_deref() {
+                    Some(Val::Bool(true)) => *then,
+                    Some(Val::Bool(false)) => *els,
+                    _ => return val::error(),
+                };
+                stats::count(Kind::Cont);
+                rstack.push((pc + 1, env.clone()));
+                pc = addr;
+                continue;
+            }
+            Some(Instr::Str(s)) => stack.push(val::str(s)),
+            Some(Instr::Concat) => match (stack.pop().as_deref(), stack.pop().as_deref()) {
+                (Some(Val::Str(s2)), Some(Val::Str(s1))) => {
+                    stack.push(val::str(&(s1.clone() + s2)))
+                }
+                _ => return val::error(),
+            },
+            Some(Instr::Length) => match stack.pop().as_deref() {
+                Some(Val::Str(s)) => stack.push(val::int(s.chars().count() as i32)),
+                _ => return val::error(),
+            },
+            Some(Instr::StrEq) => match (stack.pop().as_deref(), stack.pop().as_deref()) {
+                (Some(Val::Str(s2)), Some(Val::Str(s1))) => stack.push(val::bool(s1 == s2)),
+                _ => return val::error(),
+            },
+            Some(Instr::Global(idx)) => match globals.get(*idx) {
+                Some(v) => stack.push(v.clone()),
+                None => return val::undefined(*idx),
+            },
+            None => return val::error(),
+        }
+        pc += 1;
+    }
+}
+
+// Compile and run the top-level definitions of a program in order. A
+// definition can use later ones only under a lambda; fails with the index
+// of one that needs a later one. All definitions share one code area, as a
+// global closure refers to its body by address; it is returned with them.
+pub fn define_globals(defs: &[Rc<Ast>]) -> Result<(Vec<Instr>, Vec<Rc<Val>>), usize> {
+    let mut code = vec![];
+    let mut globals = Vec::new();
+    for (i, def) in defs.iter().enumerate() {
+        let start = lower_into(&eval(def.clone()), &mut code);
+        let v = run_with(&globals, &code, start, env::empty());
+        match &*v {
+            Val::Undefined(_) => return Err(i),
+            _ => globals.push(v),
+        }
+    }
+    Ok((code, globals))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stlc::eval_defunct2::data::{self as d2, ast};
+    use crate::stlc::eval_defunct2::eval::{apply_compt, eval};
+    use crate::termgen::build::build;
+    use crate::termgen::shrink::check_agreement;
+
+    fn env1() -> Rc<Env> {
+        env::cons(val::int(10), env::cons(val::int(20), env::empty()))
+    }
+
+    fn run_ast(a: Rc<d2::Ast>, env: Rc<Env>) -> Rc<Val> {
+        run(&lower(&eval(a)), env)
+    }
+
+    #[test]
+    fn lower_app() {
+        // (\x. x) 1
+        let code = lower(&eval(ast::app(ast::lam(ast::var(0)), ast::int(1))));
+        assert_eq!(
+            code,
+            vec![
+                Instr::Close(4),
+                Instr::Lit(1),
+                Instr::Apply,
+                Instr::Halt,
+                Instr::Access(0),
+                Instr::Return
+            ]
+        );
+    }
+
+    #[test]
+    fn run_literal() {
+        assert_eq!(run_ast(ast::int(1), env1()), val::int(1));
+        assert_eq!(run_ast(ast::var(0), env1()), val::int(10));
+        assert_eq!(run_ast(ast::var(1), env1()), val::int(20));
+        assert_eq!(
+            run_ast(
+                ast::app(
+                    ast::app(ast::lam(ast::lam(ast::var(1))), ast::int(33)),
+                    ast::int(44)
+                ),
+                env1()
+            ),
+            val::int(33)
+        );
+    }
+
+    #[test]
+    fn run_closure() {
+        // a closure result points to the code of its body
+        assert_eq!(
+            run_ast(
+                ast::app(ast::lam(ast::lam(ast::var(1))), ast::int(1)),
+                env::empty()
+            ),
+            val::clos(6, env::cons(val::int(1), env::empty()))
+        );
+    }
+
+    #[test]
+    fn lower_case() {
+        // case v0 of inl x => x | inr y => 1
+        let code = lower(&eval(ast::case(ast::var(0), ast::var(0), ast::int(1))));
+        assert_eq!(
+            code,
+            vec![
+                Instr::Access(0),
+                Instr::Case(5, 3),
+                Instr::Halt,
+                Instr::Lit(1),
+                Instr::Return,
+                Instr::Access(0),
+                Instr::Return
+            ]
+        );
+    }
+
+    #[test]
+    fn run_pairs_and_sums() {
+        let swap = ast::lam(ast::pair(ast::snd(ast::var(0)), ast::fst(ast::var(0))));
+        assert_eq!(
+            run_ast(ast::app(swap, ast::pair(ast::var(0), ast::var(1))), env1()),
+            val::pair(val::int(20), val::int(10))
+        );
+        // case e of inl x => x | inr y => snd y, applied under a binder
+        let get = ast::lam(ast::case(ast::var(0), ast::var(0), ast::snd(ast::var(0))));
+        assert_eq!(
+            run_ast(ast::app(get.clone(), ast::inl(ast::var(1))), env1()),
+            val::int(20)
+        );
+        assert_eq!(
+            run_ast(
+                ast::app(get, ast::inr(ast::pair(ast::int(2), ast::var(0)))),
+                env1()
+            ),
+            val::int(10)
+        );
+        assert_eq!(
+            run_ast(ast::case(ast::int(1), ast::var(0), ast::var(0)), env1()),
+            val::error()
+        );
+    }
+
+    #[test]
+    fn run_bools_and_strings() {
+        let greet = ast::lam(ast::if_(
+            ast::str_eq(ast::var(0), ast::str("")),
+            ast::str("hello"),
+            ast::concat(ast::str("hello, "), ast::var(0)),
+        ));
+        assert_eq!(
+            run_ast(ast::app(greet.clone(), ast::str("world")), env1()),
+            val::str("hello, world")
+        );
+        assert_eq!(
+            run_ast(ast::app(greet, ast::str("")), env1()),
+            val::str("hello")
+        );
+        assert_eq!(run_ast(ast::length(ast::str("héllo")), env1()), val::int(5));
+        assert_eq!(
+            run_ast(ast::if_(ast::var(0), ast::int(1), ast::int(2)), env1()),
+            val::error()
+        );
+    }
+
+    #[test]
+    fn run_stuck() {
+        assert_eq!(
+            run_ast(ast::app(ast::int(1), ast::int(2)), env::empty()),
+            val::error()
+        );
+        assert_eq!(run(&[Instr::Return], env::empty()), val::error());
+        assert_eq!(
+            run(&[Instr::Apply, Instr::Halt], env::empty()),
+            val::error()
+        );
+    }
+
+    #[test]
+    fn run_matches_apply_compt() {
+        check_agreement(false, 500, |t| {
+            let compt = eval(build(t));
+            let expected = apply_compt(compt.clone(), d2::env::empty(), d2::Cont::Cont0);
+            match (&*expected, &*run(&lower(&compt), env::empty())) {
+                (d2::Val::Int(i), Val::Int(j)) => i == j,
+                (d2::Val::Clos(_, _), Val::Clos(_, _)) => true,
+                _ => false,
+            }
+        });
+    }
+}
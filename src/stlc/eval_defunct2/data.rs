@@ -1,3 +1,5 @@
+use crate::stats;
+use crate::stats::Kind;
 use std::rc::Rc;
 
 #[derive(Eq, PartialEq, Debug)]
@@ -6,6 +8,19 @@ pub enum Ast {
     Var(usize),
     Lam(Rc<Ast>),
     App(Rc<Ast>, Rc<Ast>),
+    Pair(Rc<Ast>, Rc<Ast>),
+    Fst(Rc<Ast>),
+    Snd(Rc<Ast>),
+    Inl(Rc<Ast>),
+    Inr(Rc<Ast>),
+    Case(Rc<Ast>, Rc<Ast>, Rc<Ast>), // both branches bind the payload as variable 0
+    Bool(bool),
+    If(Rc<Ast>, Rc<Ast>, Rc<Ast>),
+    Str(String),
+    Concat(Rc<Ast>, Rc<Ast>),
+    Length(Rc<Ast>),
+    StrEq(Rc<Ast>, Rc<Ast>),
+    Global(usize), // top-level definition, looked up in the globals table
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -14,6 +29,12 @@ pub enum Val {
     Error,
     Int(i32),
     Clos(Rc<Env>, Rc<Compt>),
+    Pair(Rc<Val>, Rc<Val>),
+    Inl(Rc<Val>),
+    Inr(Rc<Val>),
+    Bool(bool),
+    Str(String),
+    Undefined(usize), // error: a global used before it is defined
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -22,6 +43,19 @@ pub enum Compt {
     Access(usize),              // var
     Close(Rc<Compt>),           // lam
     Push(Rc<Compt>, Rc<Compt>), // app
+    Pair(Rc<Compt>, Rc<Compt>),
+    Fst(Rc<Compt>),
+    Snd(Rc<Compt>),
+    Inl(Rc<Compt>),
+    Inr(Rc<Compt>),
+    Case(Rc<Compt>, Rc<Compt>, Rc<Compt>),
+    Bool(bool),
+    If(Rc<Compt>, Rc<Compt>, Rc<Compt>),
+    Str(String),
+    Concat(Rc<Compt>, Rc<Compt>),
+    Length(Rc<Compt>),
+    StrEq(Rc<Compt>, Rc<Compt>),
+    Global(usize),
 }
 
 #[derive(Eq, PartialEq, Debug)]
@@ -30,10 +64,25 @@ pub enum Env {
     Cons(Rc<Val>, Rc<Env>),
 }
 
+stats::counted!(Val, Env, Compt);
+
 pub enum Cont {
     Cont0,
     EvalArg(Rc<Compt>, Rc<Env>, Box<Cont>),
     EvalClos(Rc<Compt>, Rc<Env>, Box<Cont>),
+    EvalSnd(Rc<Compt>, Rc<Env>, Box<Cont>),
+    MakePair(Rc<Val>, Box<Cont>),
+    Fst(Box<Cont>),
+    Snd(Box<Cont>),
+    MakeInl(Box<Cont>),
+    MakeInr(Box<Cont>),
+    Case(Rc<Compt>, Rc<Compt>, Rc<Env>, Box<Cont>), // branches for inl and inr
+    If(Rc<Compt>, Rc<Compt>, Rc<Env>, Box<Cont>),
+    EvalConcat(Rc<Compt>, Rc<Env>, Box<Cont>),
+    Concat(Rc<Val>, Box<Cont>),
+    Length(Box<Cont>),
+    EvalStrEq(Rc<Compt>, Rc<Env>, Box<Cont>),
+    StrEq(Rc<Val>, Box<Cont>),
 }
 
 pub mod ast {
@@ -54,33 +103,129 @@ pub mod ast {
     pub fn app(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
         Rc::new(Ast::App(Rc::clone(&a1), Rc::clone(&a2)))
     }
+
+    pub fn pair(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Pair(a1, a2))
+    }
+
+    pub fn fst(a: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Fst(a))
+    }
+
+    pub fn snd(a: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Snd(a))
+    }
+
+    pub fn inl(a: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Inl(a))
+    }
+
+    pub fn inr(a: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Inr(a))
+    }
+
+    pub fn case(scrut: Rc<Ast>, left: Rc<Ast>, right: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Case(scrut, left, right))
+    }
+
+    pub fn bool(b: bool) -> Rc<Ast> {
+        Rc::new(Ast::Bool(b))
+    }
+
+    pub fn if_(cond: Rc<Ast>, then: Rc<Ast>, els: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::If(cond, then, els))
+    }
+
+    pub fn str(s: &str) -> Rc<Ast> {
+        Rc::new(Ast::Str(String::from(s)))
+    }
+
+    pub fn concat(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Concat(a1, a2))
+    }
+
+    pub fn length(a: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Length(a))
+    }
+
+    pub fn str_eq(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::StrEq(a1, a2))
+    }
+
+    pub fn global(idx: usize) -> Rc<Ast> {
+        Rc::new(Ast::Global(idx))
+    }
+}
+
+pub mod compt {
+    use super::*;
+
+    // Nodes built by compiling, optimizing or decoding.
+    pub fn node(c: Compt) -> Rc<Compt> {
+        stats::alloc(Kind::Code);
+        Rc::new(c)
+    }
 }
 
 pub mod val {
     use super::*;
 
+    fn alloc(v: Val) -> Rc<Val> {
+        stats::alloc(Kind::Val);
+        Rc::new(v)
+    }
+
     pub fn error() -> Rc<Val> {
-        return Rc::new(Val::Error);
+        return alloc(Val::Error);
     }
 
     pub fn int(i: i32) -> Rc<Val> {
-        return Rc::new(Val::Int(i));
+        return alloc(Val::Int(i));
     }
 
     pub fn clos(env: Rc<Env>, body: Rc<Compt>) -> Rc<Val> {
-        return Rc::new(Val::Clos(env, body));
+        return alloc(Val::Clos(env, body));
+    }
+
+    pub fn pair(v1: Rc<Val>, v2: Rc<Val>) -> Rc<Val> {
+        alloc(Val::Pair(v1, v2))
+    }
+
+    pub fn inl(v: Rc<Val>) -> Rc<Val> {
+        alloc(Val::Inl(v))
+    }
+
+    pub fn inr(v: Rc<Val>) -> Rc<Val> {
+        alloc(Val::Inr(v))
+    }
+
+    pub fn bool(b: bool) -> Rc<Val> {
+        alloc(Val::Bool(b))
+    }
+
+    pub fn str(s: &str) -> Rc<Val> {
+        alloc(Val::Str(String::from(s)))
+    }
+
+    pub fn undefined(idx: usize) -> Rc<Val> {
+        alloc(Val::Undefined(idx))
     }
 }
 
 pub mod env {
     use super::*;
 
+    fn alloc(env: Env) -> Rc<Env> {
+        stats::alloc(Kind::Env);
+        Rc::new(env)
+    }
+
     pub fn empty() -> Rc<Env> {
-        return Rc::new(Env::Nil);
+        return alloc(Env::Nil);
     }
 
     pub fn cons(elm: Rc<Val>, env: Rc<Env>) -> Rc<Env> {
-        return Rc::new(Env::Cons(elm.clone(), env.clone()));
+        return alloc(Env::Cons(elm.clone(), env.clone()));
     }
 
     pub fn lookup(env: Rc<Env>, idx: usize) -> Option<Rc<Val>> {
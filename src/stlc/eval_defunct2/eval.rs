@@ -1,41 +1,240 @@
+use crate::stats;
 use crate::stlc::eval_defunct2::data::*;
 use std::rc::Rc;
 
-fn apply_cont(cont: Cont, v: Rc<Val>) -> Rc<Val> {
+fn apply_cont(globals: &[Rc<Val>], cont: Cont, v: Rc<Val>, steps: &mut usize) -> Rc<Val> {
     match cont {
         Cont::Cont0 => v,
         Cont::EvalArg(arg, env, cont) => match &*v {
-            Val::Clos(cenv, cbody) => {
-                (apply_compt(arg, env, Cont::EvalClos(cbody.clone(), cenv.clone(), cont))).clone()
+            Val::Clos(cenv, cbody) => apply_compt_with(
+                globals,
+                arg,
+                env,
+                Cont::EvalClos(cbody.clone(), cenv.clone(), cont),
+                steps,
+            ),
+            _ => val::error(),
+        },
+        Cont::EvalClos(cbody, cenv, cont) => {
+            apply_compt_with(globals, cbody, env::cons(v, cenv), *cont, steps)
+        }
+        Cont::EvalSnd(snd, env, cont) => {
+            apply_compt_with(globals, snd, env, Cont::MakePair(v, cont), steps)
+        }
+        Cont::MakePair(fstv, cont) => apply_cont(globals, *cont, val::pair(fstv, v), steps),
+        Cont::Fst(cont) => match &*v {
+            Val::Pair(fstv, _) => apply_cont(globals, *cont, fstv.clone(), steps),
+            _ => val::error(),
+        },
+        Cont::Snd(cont) => match &*v {
+            Val::Pair(_, sndv) => apply_cont(globals, *cont, sndv.clone(), steps),
+            _ => val::error(),
+        },
+        Cont::MakeInl(cont) => apply_cont(globals, *cont, val::inl(v), steps),
+        Cont::MakeInr(cont) => apply_cont(globals, *cont, val::inr(v), steps),
+        Cont::Case(left, right, env, cont) => match &*v {
+            Val::Inl(payload) => {
+                apply_compt_with(globals, left, env::cons(payload.clone(), env), *cont, steps)
             }
+            Val::Inr(payload) => apply_compt_with(
+                globals,
+                right,
+                env::cons(payload.clone(), env),
+                *cont,
+                steps,
+            ),
+            _ => val::error(),
+        },
+        Cont::If(then, els, env, cont) => match &*v {
+            Val::Bool(true) => apply_compt_with(globals, then, env, *cont, steps),
+            Val::Bool(false) => apply_compt_with(globals, els, env, *cont, steps),
+            _ => val::error(),
+        },
+        Cont::EvalConcat(rhs, env, cont) => {
+            apply_compt_with(globals, rhs, env, Cont::Concat(v, cont), steps)
+        }
+        Cont::Concat(lhsv, cont) => match (&*lhsv, &*v) {
+            (Val::Str(s1), Val::Str(s2)) => {
+                apply_cont(globals, *cont, val::str(&(s1.clone() + s2)), steps)
+            }
+            _ => val::error(),
+        },
+        Cont::Length(cont) => match &*v {
+            Val::Str(s) => apply_cont(globals, *cont, val::int(s.chars().count() as i32), steps),
+            _ => val::error(),
+        },
+        Cont::EvalStrEq(rhs, env, cont) => {
+            apply_compt_with(globals, rhs, env, Cont::StrEq(v, cont), steps)
+        }
+        Cont::StrEq(lhsv, cont) => match (&*lhsv, &*v) {
+            (Val::Str(s1), Val::Str(s2)) => apply_cont(globals, *cont, val::bool(s1 == s2), steps),
             _ => val::error(),
         },
-        Cont::EvalClos(cbody, cenv, cont) => apply_compt(cbody, env::cons(v, cenv), *cont),
     }
 }
 
-fn apply_compt(compt: Rc<Compt>, env: Rc<Env>, cont: Cont) -> Rc<Val> {
+// Like `apply_compt`, adding the number of instructions executed to `steps`.
+pub fn apply_compt_steps(compt: Rc<Compt>, env: Rc<Env>, cont: Cont, steps: &mut usize) -> Rc<Val> {
+    apply_compt_with(&[], compt, env, cont, steps)
+}
+
+// Like `apply_compt_steps`, with `globals` holding the values of top-level
+// definitions.
+pub fn apply_compt_with(
+    globals: &[Rc<Val>],
+    compt: Rc<Compt>,
+    env: Rc<Env>,
+    cont: Cont,
+    steps: &mut usize,
+) -> Rc<Val> {
+    *steps += 1;
     match &*compt {
-        Compt::Lit(i) => apply_cont(cont, val::int(*i)),
+        Compt::Lit(i) => apply_cont(globals, cont, val::int(*i), steps),
         Compt::Access(idx) => env::lookup(env, *idx)
-            .map(|v| apply_cont(cont, v))
-            .unwrap_or(val::error()),
-        Compt::Close(body) => apply_cont(cont, val::clos(env.clone(), body.clone())),
-        Compt::Push(func, arg) => {
-            apply_compt(func.clone(), env.clone(), Cont::EvalArg(arg.clone(), env, Box::new(cont)))
+            .map(|v| apply_cont(globals, cont, v, steps))
+            .unwrap_or_else(val::error),
+        Compt::Close(body) => {
+            apply_cont(globals, cont, val::clos(env.clone(), body.clone()), steps)
         }
+        Compt::Push(func, arg) => apply_compt_with(
+            globals,
+            func.clone(),
+            env.clone(),
+            Cont::EvalArg(arg.clone(), env, stats::frame(cont)),
+            steps,
+        ),
+        Compt::Pair(fst, snd) => apply_compt_with(
+            globals,
+            fst.clone(),
+            env.clone(),
+            Cont::EvalSnd(snd.clone(), env, stats::frame(cont)),
+            steps,
+        ),
+        Compt::Fst(pair) => apply_compt_with(
+            globals,
+            pair.clone(),
+            env,
+            Cont::Fst(stats::frame(cont)),
+            steps,
+        ),
+        Compt::Snd(pair) => apply_compt_with(
+            globals,
+            pair.clone(),
+            env,
+            Cont::Snd(stats::frame(cont)),
+            steps,
+        ),
+        Compt::Inl(payload) => apply_compt_with(
+            globals,
+            payload.clone(),
+            env,
+            Cont::MakeInl(stats::frame(cont)),
+            steps,
+        ),
+        Compt::Inr(payload) => apply_compt_with(
+            globals,
+            payload.clone(),
+            env,
+            Cont::MakeInr(stats::frame(cont)),
+            steps,
+        ),
+        Compt::Case(scrut, left, right) => apply_compt_with(
+            globals,
+            scrut.clone(),
+            env.clone(),
+            Cont::Case(left.clone(), right.clone(), env, stats::frame(cont)),
+            steps,
+        ),
+        Compt::Bool(b) => apply_cont(globals, cont, val::bool(*b), steps),
+        Compt::If(cond, then, els) => apply_compt_with(
+            globals,
+            cond.clone(),
+            env.clone(),
+            Cont::If(then.clone(), els.clone(), env, stats::frame(cont)),
+            steps,
+        ),
+        Compt::Str(s) => apply_cont(globals, cont, val::str(s), steps),
+        Compt::Concat(lhs, rhs) => apply_compt_with(
+            globals,
+            lhs.clone(),
+            env.clone(),
+            Cont::EvalConcat(rhs.clone(), env, stats::frame(cont)),
+            steps,
+        ),
+        Compt::Length(s) => apply_compt_with(
+            globals,
+            s.clone(),
+            env,
+            Cont::Length(stats::frame(cont)),
+            steps,
+        ),
+        Compt::StrEq(lhs, rhs) => apply_compt_with(
+            globals,
+            lhs.clone(),
+            env.clone(),
+            Cont::EvalStrEq(rhs.clone(), env, stats::frame(cont)),
+            steps,
+        ),
+        Compt::Global(idx) => match globals.get(*idx) {
+            Some(v) => apply_cont(globals, cont, v.clone(), steps),
+            None => val::undefined(*idx),
+        },
     }
 }
 
+pub fn apply_compt(compt: Rc<Compt>, env: Rc<Env>, cont: Cont) -> Rc<Val> {
+    apply_compt_steps(compt, env, cont, &mut 0)
+}
+
 pub fn eval(ast: Rc<Ast>) -> Rc<Compt> {
     match &*ast {
-        Ast::Int(i) => Rc::new(Compt::Lit(*i)),
-        Ast::Var(idx) => Rc::new(Compt::Access(*idx)),
-        Ast::Lam(body) => Rc::new(Compt::Close(eval(body.clone()))),
-        Ast::App(func, arg) => {
-            Rc::new(Compt::Push(eval(func.clone()), eval(arg.clone())))
+        Ast::Int(i) => compt::node(Compt::Lit(*i)),
+        Ast::Var(idx) => compt::node(Compt::Access(*idx)),
+        Ast::Lam(body) => compt::node(Compt::Close(eval(body.clone()))),
+        Ast::App(func, arg) => compt::node(Compt::Push(eval(func.clone()), eval(arg.clone()))),
+        Ast::Pair(fst, snd) => compt::node(Compt::Pair(eval(fst.clone()), eval(snd.clone()))),
+        Ast::Fst(pair) => compt::node(Compt::Fst(eval(pair.clone()))),
+        Ast::Snd(pair) => compt::node(Compt::Snd(eval(pair.clone()))),
+        Ast::Inl(payload) => compt::node(Compt::Inl(eval(payload.clone()))),
+        Ast::Inr(payload) => compt::node(Compt::Inr(eval(payload.clone()))),
+        Ast::Case(scrut, left, right) => compt::node(Compt::Case(
+            eval(scrut.clone()),
+            eval(left.clone()),
+            eval(right.clone()),
+        )),
+        Ast::Bool(b) => compt::node(Compt::Bool(*b)),
+        Ast::If(cond, then, els) => compt::node(Compt::If(
+            eval(cond.clone()),
+            eval(then.clone()),
+            eval(els.clone()),
+        )),
+        Ast::Str(s) => compt::node(Compt::Str(s.clone())),
+        Ast::Concat(lhs, rhs) => compt::node(Compt::Concat(eval(lhs.clone()), eval(rhs.clone()))),
+        Ast::Length(s) => compt::node(Compt::Length(eval(s.clone()))),
+        Ast::StrEq(lhs, rhs) => compt::node(Compt::StrEq(eval(lhs.clone()), eval(rhs.clone()))),
+        Ast::Global(idx) => compt::node(Compt::Global(*idx)),
+    }
+}
+
+// Compile and run the top-level definitions of a program in order. A
+// definition can use later ones only under a lambda; fails with the index
+// of one that needs a later one.
+pub fn define_globals(defs: &[Rc<Ast>]) -> Result<Vec<Rc<Val>>, usize> {
+    let mut globals = Vec::new();
+    for (i, def) in defs.iter().enumerate() {
+        let v = apply_compt_with(
+            &globals,
+            eval(def.clone()),
+            env::empty(),
+            Cont::Cont0,
+            &mut 0,
+        );
+        match &*v {
+            Val::Undefined(_) => return Err(i),
+            _ => globals.push(v),
         }
     }
+    Ok(globals)
 }
 
 #[cfg(test)]
@@ -74,4 +273,91 @@ mod tests {
             val::int(33)
         )
     }
+
+    #[test]
+    fn eval_pairs_and_sums() {
+        let swap = ast::lam(ast::pair(ast::snd(ast::var(0)), ast::fst(ast::var(0))));
+        assert_eq!(
+            apply_compt(
+                eval(ast::app(swap, ast::pair(ast::int(1), ast::int(2)))),
+                empty(),
+                Cont::Cont0
+            ),
+            val::pair(val::int(2), val::int(1))
+        );
+        // case e of inl x => x | inr y => snd y
+        let get = ast::lam(ast::case(ast::var(0), ast::var(0), ast::snd(ast::var(0))));
+        assert_eq!(
+            apply_compt(
+                eval(ast::app(get.clone(), ast::inl(ast::int(1)))),
+                empty(),
+                Cont::Cont0
+            ),
+            val::int(1)
+        );
+        assert_eq!(
+            apply_compt(
+                eval(ast::app(get, ast::inr(ast::pair(ast::int(2), ast::int(3))))),
+                empty(),
+                Cont::Cont0
+            ),
+            val::int(3)
+        );
+        assert_eq!(
+            apply_compt(eval(ast::fst(ast::inl(ast::int(1)))), empty(), Cont::Cont0),
+            val::error()
+        );
+    }
+
+    #[test]
+    fn eval_bools_and_strings() {
+        let greet = ast::lam(ast::if_(
+            ast::str_eq(ast::var(0), ast::str("")),
+            ast::str("hello"),
+            ast::concat(ast::str("hello, "), ast::var(0)),
+        ));
+        assert_eq!(
+            apply_compt(
+                eval(ast::app(greet.clone(), ast::str("world"))),
+                empty(),
+                Cont::Cont0
+            ),
+            val::str("hello, world")
+        );
+        assert_eq!(
+            apply_compt(eval(ast::app(greet, ast::str(""))), empty(), Cont::Cont0),
+            val::str("hello")
+        );
+        assert_eq!(
+            apply_compt(eval(ast::length(ast::str("héllo"))), empty(), Cont::Cont0),
+            val::int(5)
+        );
+        assert_eq!(
+            apply_compt(
+                eval(ast::if_(ast::int(1), ast::int(2), ast::int(3))),
+                empty(),
+                Cont::Cont0
+            ),
+            val::error()
+        );
+    }
+
+    #[test]
+    fn eval_stats() {
+        // (\x. (x, x)) 1
+        let expr = ast::app(ast::lam(ast::pair(ast::var(0), ast::var(0))), ast::int(1));
+        let (code, stats) = stats::measure(|| eval(expr));
+        assert_eq!(stats.code, 6);
+        assert_eq!(stats.peak_live, 6);
+
+        let env = empty();
+        let (v, stats) = stats::measure(|| apply_compt(code, env, Cont::Cont0));
+        assert_eq!(v, val::pair(val::int(1), val::int(1)));
+        // the closure, 1 and the pair, which holds 1 twice
+        assert_eq!(stats.vals, 3);
+        assert_eq!(stats.envs, 1);
+        // a frame for the argument and one for the second component, as
+        // the others reuse the frame they replace
+        assert_eq!(stats.conts, 2);
+    }
 }
@@ -1,2 +1,6 @@
 pub mod data;
 pub mod eval;
+pub mod opt;
+pub mod bytecode;
+pub mod serial;
+pub mod print;
@@ -0,0 +1,294 @@
+use crate::stlc::eval_defunct2::data::*;
+use std::rc::Rc;
+
+// Subterms of `compt`, each with the number of variables it binds.
+fn children(compt: &Compt) -> Vec<(usize, &Rc<Compt>)> {
+    match compt {
+        Compt::Lit(_) | Compt::Access(_) | Compt::Bool(_) | Compt::Str(_) | Compt::Global(_) => {
+            vec![]
+        }
+        Compt::Close(body) => vec![(1, body)],
+        Compt::Push(c1, c2)
+        | Compt::Pair(c1, c2)
+        | Compt::Concat(c1, c2)
+        | Compt::StrEq(c1, c2) => vec![(0, c1), (0, c2)],
+        Compt::Fst(c) | Compt::Snd(c) | Compt::Inl(c) | Compt::Inr(c) | Compt::Length(c) => {
+            vec![(0, c)]
+        }
+        Compt::Case(scrut, left, right) => vec![(0, scrut), (1, left), (1, right)],
+        Compt::If(cond, then, els) => vec![(0, cond), (0, then), (0, els)],
+    }
+}
+
+// `compt` with its subterms replaced, in the order of `children`.
+fn rebuild(compt: &Rc<Compt>, cs: Vec<Rc<Compt>>) -> Rc<Compt> {
+    let mut cs = cs.into_iter();
+    let mut next = || cs.next().unwrap();
+    compt::node(match &**compt {
+        Compt::Lit(_) | Compt::Access(_) | Compt::Bool(_) | Compt::Str(_) | Compt::Global(_) => {
+            return compt.clone()
+        }
+        Compt::Close(_) => Compt::Close(next()),
+        Compt::Push(_, _) => Compt::Push(next(), next()),
+        Compt::Pair(_, _) => Compt::Pair(next(), next()),
+        Compt::Fst(_) => Compt::Fst(next()),
+        Compt::Snd(_) => Compt::Snd(next()),
+        Compt::Inl(_) => Compt::Inl(next()),
+        Compt::Inr(_) => Compt::Inr(next()),
+        Compt::Case(_, _, _) => Compt::Case(next(), next(), next()),
+        Compt::If(_, _, _) => Compt::If(next(), next(), next()),
+        Compt::Concat(_, _) => Compt::Concat(next(), next()),
+        Compt::Length(_) => Compt::Length(next()),
+        Compt::StrEq(_, _) => Compt::StrEq(next(), next()),
+    })
+}
+
+// Add `d` to every variable index >= `cutoff`.
+fn shift(d: isize, cutoff: usize, compt: &Rc<Compt>) -> Rc<Compt> {
+    match &**compt {
+        Compt::Access(idx) if *idx >= cutoff => {
+            compt::node(Compt::Access((*idx as isize + d) as usize))
+        }
+        _ => rebuild(
+            compt,
+            children(compt)
+                .into_iter()
+                .map(|(b, c)| shift(d, cutoff + b, c))
+                .collect(),
+        ),
+    }
+}
+
+// Replace variable `j` by `s` in `compt`.
+fn subst(j: usize, s: &Rc<Compt>, compt: &Rc<Compt>) -> Rc<Compt> {
+    match &**compt {
+        Compt::Access(idx) if *idx == j => s.clone(),
+        _ => rebuild(
+            compt,
+            children(compt)
+                .into_iter()
+                .map(|(b, c)| subst(j + b, &shift(b as isize, 0, s), c))
+                .collect(),
+        ),
+    }
+}
+
+// Number of occurrences of variable `j`.
+fn uses(j: usize, compt: &Compt) -> usize {
+    match compt {
+        Compt::Access(idx) => (*idx == j) as usize,
+        _ => children(compt)
+            .into_iter()
+            .map(|(b, c)| uses(j + b, c))
+            .sum(),
+    }
+}
+
+// Evaluating a value can neither diverge nor fail on a well-scoped term,
+// so it may be dropped, duplicated or moved under a binder.
+fn is_value(compt: &Compt) -> bool {
+    matches!(
+        compt,
+        Compt::Lit(_) | Compt::Access(_) | Compt::Close(_) | Compt::Bool(_) | Compt::Str(_)
+    )
+}
+
+// (\x. body) v, where x does not occur in body => body
+fn dead_binding(func: &Compt, arg: &Compt) -> Option<Rc<Compt>> {
+    match func {
+        Compt::Close(body) if is_value(arg) && uses(0, body) == 0 => Some(shift(-1, 0, body)),
+        _ => None,
+    }
+}
+
+// (\x. body) v => body[v/x]. A closure or string argument is only inlined
+// into a single occurrence, so that code does not grow.
+fn beta(func: &Compt, arg: &Rc<Compt>) -> Option<Rc<Compt>> {
+    match func {
+        Compt::Close(body) if is_value(arg) => {
+            if matches!(**arg, Compt::Close(_) | Compt::Str(_)) && uses(0, body) > 1 {
+                return None;
+            }
+            Some(shift(-1, 0, &subst(0, &shift(1, 0, arg), body)))
+        }
+        _ => None,
+    }
+}
+
+// \x. f x, where f is a function without x => f. Only a closure is
+// known to be one; a variable applied to x is one in a well-typed term.
+// Other values would turn the closure into themselves.
+fn eta(body: &Compt) -> Option<Rc<Compt>> {
+    match body {
+        Compt::Push(func, arg)
+            if **arg == Compt::Access(0)
+                && matches!(**func, Compt::Close(_) | Compt::Access(_))
+                && uses(0, func) == 0 =>
+        {
+            Some(shift(-1, 0, func))
+        }
+        _ => None,
+    }
+}
+
+// One bottom-up pass of all rewrites.
+fn simplify(compt: &Rc<Compt>) -> Rc<Compt> {
+    match &**compt {
+        Compt::Lit(_) | Compt::Access(_) => compt.clone(),
+        Compt::Close(body) => {
+            let body = simplify(body);
+            eta(&body).unwrap_or_else(|| compt::node(Compt::Close(body)))
+        }
+        Compt::Push(func, arg) => {
+            let func = simplify(func);
+            let arg = simplify(arg);
+            dead_binding(&func, &arg)
+                .or_else(|| beta(&func, &arg))
+                .unwrap_or_else(|| compt::node(Compt::Push(func, arg)))
+        }
+        _ => rebuild(
+            compt,
+            children(compt)
+                .into_iter()
+                .map(|(_, c)| simplify(c))
+                .collect(),
+        ),
+    }
+}
+
+// Every rewrite makes the code smaller, so iterating to a fixed point
+// terminates.
+pub fn optimize(compt: Rc<Compt>) -> Rc<Compt> {
+    let mut compt = compt;
+    loop {
+        let next = simplify(&compt);
+        if next == compt {
+            return compt;
+        }
+        compt = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stlc::eval_defunct2::data::env::{cons, empty};
+    use crate::stlc::eval_defunct2::eval::{apply_compt_steps, eval};
+    use crate::termgen::build::build;
+    use crate::termgen::generate::Gen;
+
+    fn run(compt: Rc<Compt>) -> (Rc<Val>, usize) {
+        let env1 = cons(val::int(10), cons(val::int(20), empty()));
+        let mut steps = 0;
+        let v = apply_compt_steps(compt, env1, Cont::Cont0, &mut steps);
+        (v, steps)
+    }
+
+    #[test]
+    fn optimize_beta() {
+        // (\x. x) 1 => 1
+        let c = eval(ast::app(ast::lam(ast::var(0)), ast::int(1)));
+        assert_eq!(optimize(c), Rc::new(Compt::Lit(1)));
+        // (\x. \y. x y) v0 => \y. v0 y => v0
+        let c = eval(ast::app(
+            ast::lam(ast::lam(ast::app(ast::var(1), ast::var(0)))),
+            ast::var(0),
+        ));
+        assert_eq!(optimize(c), Rc::new(Compt::Access(0)));
+    }
+
+    #[test]
+    fn optimize_dead_binding() {
+        // (\x. v0) (\y. y) => v0, and the free variable is shifted back
+        let c = eval(ast::app(ast::lam(ast::var(1)), ast::lam(ast::var(0))));
+        assert_eq!(optimize(c), Rc::new(Compt::Access(0)));
+        // a non-value argument may diverge, so it is kept
+        let c = eval(ast::app(
+            ast::lam(ast::int(1)),
+            ast::app(ast::var(0), ast::int(2)),
+        ));
+        assert_eq!(optimize(c.clone()), c);
+    }
+
+    #[test]
+    fn optimize_eta() {
+        // \x. v0 x => v0
+        let c = eval(ast::lam(ast::app(ast::var(1), ast::var(0))));
+        assert_eq!(optimize(c), Rc::new(Compt::Access(0)));
+        // \x. x x has x free in the function part
+        let c = eval(ast::lam(ast::app(ast::var(0), ast::var(0))));
+        assert_eq!(optimize(c.clone()), c);
+        // \x. 3 x stays a closure
+        let c = Rc::new(Compt::Close(Rc::new(Compt::Push(
+            Rc::new(Compt::Lit(3)),
+            Rc::new(Compt::Access(0)),
+        ))));
+        assert_eq!(optimize(c.clone()), c);
+        // \x. (\y. y) x => \y. y
+        let c = eval(ast::lam(ast::app(ast::lam(ast::var(0)), ast::var(0))));
+        assert_eq!(optimize(c), eval(ast::lam(ast::var(0))));
+    }
+
+    #[test]
+    fn optimize_keeps_shared_closure() {
+        // (\f. f (f 1)) (\x. x) keeps the binding of f
+        let c = eval(ast::app(
+            ast::lam(ast::app(ast::var(0), ast::app(ast::var(0), ast::int(1)))),
+            ast::lam(ast::var(0)),
+        ));
+        assert_eq!(optimize(c.clone()), c);
+    }
+
+    #[test]
+    fn optimize_under_case() {
+        // case v0 of inl x => (\y. y) x | inr z => v1 z
+        let c = eval(ast::case(
+            ast::var(0),
+            ast::app(ast::lam(ast::var(0)), ast::var(0)),
+            ast::app(ast::var(2), ast::var(0)),
+        ));
+        assert_eq!(
+            optimize(c),
+            Rc::new(Compt::Case(
+                Rc::new(Compt::Access(0)),
+                Rc::new(Compt::Access(0)),
+                Rc::new(Compt::Push(
+                    Rc::new(Compt::Access(2)),
+                    Rc::new(Compt::Access(0))
+                ))
+            ))
+        );
+        // (\x. case v0 of inl y => x | inr z => z) 7
+        let c = eval(ast::app(
+            ast::lam(ast::case(ast::var(1), ast::var(1), ast::var(0))),
+            ast::int(7),
+        ));
+        assert_eq!(
+            optimize(c),
+            Rc::new(Compt::Case(
+                Rc::new(Compt::Access(0)),
+                Rc::new(Compt::Lit(7)),
+                Rc::new(Compt::Access(0))
+            ))
+        );
+    }
+
+    #[test]
+    fn optimize_preserves_semantics() {
+        use crate::termgen::data::ty;
+
+        let mut reduced = 0;
+        for seed in 0..500 {
+            let t = Gen::new(seed, 6, false).term(&ty::int());
+            let c = eval(build(&t));
+            let (v1, steps1) = run(c.clone());
+            let (v2, steps2) = run(optimize(c));
+            assert_eq!(v1, v2, "seed {}: {:?}", seed, t);
+            assert!(steps2 <= steps1, "seed {}: {:?}", seed, t);
+            if steps2 < steps1 {
+                reduced += 1;
+            }
+        }
+        assert!(reduced > 250);
+    }
+}
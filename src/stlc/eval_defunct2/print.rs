@@ -0,0 +1,228 @@
+use crate::pretty;
+use crate::pretty::{Ctx, Prec, Term};
+use crate::stlc::eval_defunct2::bytecode;
+use crate::stlc::eval_defunct2::bytecode::Instr;
+use crate::stlc::eval_defunct2::data::{Ast, Compt, Env, Val};
+use std::fmt;
+
+impl Term for Ast {
+    fn prec(&self) -> Prec {
+        match self {
+            Ast::Int(_)
+            | Ast::Var(_)
+            | Ast::Pair(_, _)
+            | Ast::Bool(_)
+            | Ast::Str(_)
+            | Ast::Global(_) => Prec::Atom,
+            Ast::App(_, _)
+            | Ast::Fst(_)
+            | Ast::Snd(_)
+            | Ast::Inl(_)
+            | Ast::Inr(_)
+            | Ast::Length(_) => Prec::App,
+            _ => Prec::Open,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ast::Int(i) => write!(f, "{}", i),
+            Ast::Var(idx) => write!(f, "{}", ctx.var(*idx, lev)),
+            Ast::Lam(body) => pretty::lam(&**body, ctx, lev, f),
+            Ast::App(func, arg) => pretty::app(&**func, &**arg, ctx, lev, f),
+            Ast::Pair(fst, snd) => pretty::pair(&**fst, &**snd, ctx, lev, f),
+            Ast::Fst(pair) => pretty::keyword("fst", &**pair, ctx, lev, f),
+            Ast::Snd(pair) => pretty::keyword("snd", &**pair, ctx, lev, f),
+            Ast::Inl(payload) => pretty::keyword("inl", &**payload, ctx, lev, f),
+            Ast::Inr(payload) => pretty::keyword("inr", &**payload, ctx, lev, f),
+            Ast::Case(scrut, left, right) => pretty::case(&**scrut, &**left, &**right, ctx, lev, f),
+            Ast::Bool(b) => write!(f, "{}", b),
+            Ast::If(cond, then, els) => pretty::if_(&**cond, &**then, &**els, ctx, lev, f),
+            Ast::Str(s) => write!(f, "{:?}", s),
+            Ast::Concat(lhs, rhs) => pretty::infix(&**lhs, "^", &**rhs, ctx, lev, f),
+            Ast::Length(s) => pretty::keyword("length", &**s, ctx, lev, f),
+            Ast::StrEq(lhs, rhs) => pretty::infix(&**lhs, "==", &**rhs, ctx, lev, f),
+            Ast::Global(i) => write!(f, "g{}", i),
+        }
+    }
+}
+
+// Compiled code has the shape of the term it came from, so it is printed
+// the same way.
+impl Term for Compt {
+    fn prec(&self) -> Prec {
+        match self {
+            Compt::Lit(_)
+            | Compt::Access(_)
+            | Compt::Pair(_, _)
+            | Compt::Bool(_)
+            | Compt::Str(_)
+            | Compt::Global(_) => Prec::Atom,
+            Compt::Push(_, _)
+            | Compt::Fst(_)
+            | Compt::Snd(_)
+            | Compt::Inl(_)
+            | Compt::Inr(_)
+            | Compt::Length(_) => Prec::App,
+            _ => Prec::Open,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Compt::Lit(i) => write!(f, "{}", i),
+            Compt::Access(idx) => write!(f, "{}", ctx.var(*idx, lev)),
+            Compt::Close(body) => pretty::lam(&**body, ctx, lev, f),
+            Compt::Push(func, arg) => pretty::app(&**func, &**arg, ctx, lev, f),
+            Compt::Pair(fst, snd) => pretty::pair(&**fst, &**snd, ctx, lev, f),
+            Compt::Fst(pair) => pretty::keyword("fst", &**pair, ctx, lev, f),
+            Compt::Snd(pair) => pretty::keyword("snd", &**pair, ctx, lev, f),
+            Compt::Inl(payload) => pretty::keyword("inl", &**payload, ctx, lev, f),
+            Compt::Inr(payload) => pretty::keyword("inr", &**payload, ctx, lev, f),
+            Compt::Case(scrut, left, right) => {
+                pretty::case(&**scrut, &**left, &**right, ctx, lev, f)
+            }
+            Compt::Bool(b) => write!(f, "{}", b),
+            Compt::If(cond, then, els) => pretty::if_(&**cond, &**then, &**els, ctx, lev, f),
+            Compt::Str(s) => write!(f, "{:?}", s),
+            Compt::Concat(lhs, rhs) => pretty::infix(&**lhs, "^", &**rhs, ctx, lev, f),
+            Compt::Length(s) => pretty::keyword("length", &**s, ctx, lev, f),
+            Compt::StrEq(lhs, rhs) => pretty::infix(&**lhs, "==", &**rhs, ctx, lev, f),
+            Compt::Global(i) => write!(f, "g{}", i),
+        }
+    }
+}
+
+pretty::display!(Ast, Compt);
+
+fn entries(mut env: &Env) -> Vec<&Val> {
+    let mut entries = vec![];
+    while let Env::Cons(head, tail) = env {
+        entries.push(&**head);
+        env = tail;
+    }
+    entries
+}
+
+impl fmt::Display for Env {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pretty::env(&entries(self), f)
+    }
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Val::Error => write!(f, "error"),
+            Val::Int(i) => write!(f, "{}", i),
+            Val::Clos(env, body) => {
+                pretty::closure(&entries(env), f, |ctx, f| pretty::lam(&**body, ctx, 0, f))
+            }
+            Val::Pair(fst, snd) => {
+                let d = pretty::depth(f);
+                write!(f, "({:.*}, {:.*})", d, fst, d, snd)
+            }
+            Val::Inl(payload) => write!(f, "inl {:.*}", pretty::depth(f), payload),
+            Val::Inr(payload) => write!(f, "inr {:.*}", pretty::depth(f), payload),
+            Val::Bool(b) => write!(f, "{}", b),
+            Val::Str(s) => write!(f, "{:?}", s),
+            Val::Undefined(i) => write!(f, "error: undefined g{}", i),
+        }
+    }
+}
+
+// Bytecode refers to code by address, so variables keep their indices.
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instr::Lit(i) => write!(f, "lit {}", i),
+            Instr::Access(idx) => write!(f, "access {}", idx),
+            Instr::Close(addr) => write!(f, "close @{}", addr),
+            Instr::Apply => write!(f, "apply"),
+            Instr::Return => write!(f, "return"),
+            Instr::Halt => write!(f, "halt"),
+            Instr::Pair => write!(f, "pair"),
+            Instr::Fst => write!(f, "fst"),
+            Instr::Snd => write!(f, "snd"),
+            Instr::Inl => write!(f, "inl"),
+            Instr::Inr => write!(f, "inr"),
+            Instr::Case(left, right) => write!(f, "case @{} @{}", left, right),
+            Instr::Bool(b) => write!(f, "bool {}", b),
+            Instr::If(then, els) => write!(f, "if @{} @{}", then, els),
+            Instr::Str(s) => write!(f, "str {:?}", s),
+            Instr::Concat => write!(f, "concat"),
+            Instr::Length => write!(f, "length"),
+            Instr::StrEq => write!(f, "streq"),
+            Instr::Global(idx) => write!(f, "global g{}", idx),
+        }
+    }
+}
+
+fn bytecode_entries(mut env: &bytecode::Env) -> Vec<&bytecode::Val> {
+    let mut entries = vec![];
+    while let bytecode::Env::Cons(head, tail) = env {
+        entries.push(&**head);
+        env = tail;
+    }
+    entries
+}
+
+impl fmt::Display for bytecode::Env {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pretty::env(&bytecode_entries(self), f)
+    }
+}
+
+impl fmt::Display for bytecode::Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            bytecode::Val::Error => write!(f, "error"),
+            bytecode::Val::Int(i) => write!(f, "{}", i),
+            bytecode::Val::Clos(addr, env) => {
+                pretty::closure(&bytecode_entries(env), f, |_, f| write!(f, "@{}", addr))
+            }
+            bytecode::Val::Pair(fst, snd) => {
+                let d = pretty::depth(f);
+                write!(f, "({:.*}, {:.*})", d, fst, d, snd)
+            }
+            bytecode::Val::Inl(payload) => write!(f, "inl {:.*}", pretty::depth(f), payload),
+            bytecode::Val::Inr(payload) => write!(f, "inr {:.*}", pretty::depth(f), payload),
+            bytecode::Val::Bool(b) => write!(f, "{}", b),
+            bytecode::Val::Str(s) => write!(f, "{:?}", s),
+            bytecode::Val::Undefined(i) => write!(f, "error: undefined g{}", i),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stlc::eval_defunct2::bytecode;
+    use crate::stlc::eval_defunct2::data::{ast, env, val};
+    use crate::stlc::eval_defunct2::eval::eval;
+
+    #[test]
+    fn print_compt() {
+        let expr = ast::app(
+            ast::lam(ast::pair(ast::var(0), ast::length(ast::str("ab")))),
+            ast::int(1),
+        );
+        assert_eq!(expr.to_string(), "(\\x0. (x0, length \"ab\")) 1");
+        let code = eval(expr);
+        assert_eq!(code.to_string(), "(\\x0. (x0, length \"ab\")) 1");
+        let env = env::cons(val::int(2), env::empty());
+        let v = val::clos(env, eval(ast::pair(ast::var(0), ast::var(1))));
+        assert_eq!(v.to_string(), "{\\x1. (x1, x0) | x0 = 2}");
+    }
+
+    #[test]
+    fn print_bytecode() {
+        let code = bytecode::lower(&eval(ast::app(ast::lam(ast::var(0)), ast::int(1))));
+        let lines: Vec<String> = code.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            lines,
+            ["close @4", "lit 1", "apply", "halt", "access 0", "return"]
+        );
+        let env = bytecode::env::cons(bytecode::val::int(1), bytecode::env::empty());
+        assert_eq!(bytecode::val::clos(4, env).to_string(), "{@4 | x0 = 1}");
+    }
+}
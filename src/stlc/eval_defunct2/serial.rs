@@ -0,0 +1,440 @@
+use crate::stlc::eval_defunct2::data::{compt, Compt};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// Layout: magic, version, node count, then the nodes. Each node is a tag
+// byte followed by its fields; children are referred to by the position
+// of an earlier node, so a shared subtree is written once. The last node
+// is the root. Integers are LEB128 varints, literals zigzag-encoded, and
+// strings are a length followed by UTF-8 bytes.
+const MAGIC: &[u8; 4] = b"CMPT";
+const VERSION: u8 = 4;
+
+const TAG_LIT: u8 = 0;
+const TAG_ACCESS: u8 = 1;
+const TAG_CLOSE: u8 = 2;
+const TAG_PUSH: u8 = 3;
+const TAG_PAIR: u8 = 4;
+const TAG_FST: u8 = 5;
+const TAG_SND: u8 = 6;
+const TAG_INL: u8 = 7;
+const TAG_INR: u8 = 8;
+const TAG_CASE: u8 = 9;
+const TAG_BOOL: u8 = 10;
+const TAG_IF: u8 = 11;
+const TAG_STR: u8 = 12;
+const TAG_CONCAT: u8 = 13;
+const TAG_LENGTH: u8 = 14;
+const TAG_STR_EQ: u8 = 15;
+const TAG_GLOBAL: u8 = 16;
+
+// Each version only adds tags, so older programs are still read; the
+// version is bumped whenever tags are added, so that an older decoder
+// rejects a newer program up front.
+fn last_tag(version: u8) -> Option<u8> {
+    match version {
+        1 => Some(TAG_PUSH),
+        2 => Some(TAG_CASE),
+        3 => Some(TAG_STR_EQ),
+        4 => Some(TAG_GLOBAL),
+        _ => None,
+    }
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum DecodeError {
+    BadMagic,
+    BadVersion(u8),
+    UnexpectedEnd,
+    BadVarint,
+    BadTag(u8),
+    BadRef(usize), // reference to a node that is not before this one
+    LitOutOfRange,
+    BadBool(u8),
+    BadString, // not UTF-8
+    NoNodes,
+    TrailingBytes,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::BadMagic => write!(f, "not a compiled program"),
+            DecodeError::BadVersion(v) => write!(f, "unsupported format version {}", v),
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            DecodeError::BadVarint => write!(f, "malformed integer"),
+            DecodeError::BadTag(t) => write!(f, "unknown node tag {}", t),
+            DecodeError::BadRef(r) => write!(f, "reference to undefined node {}", r),
+            DecodeError::LitOutOfRange => write!(f, "literal out of range"),
+            DecodeError::BadBool(b) => write!(f, "invalid boolean {}", b),
+            DecodeError::BadString => write!(f, "string is not UTF-8"),
+            DecodeError::NoNodes => write!(f, "program has no nodes"),
+            DecodeError::TrailingBytes => write!(f, "trailing bytes after program"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+fn write_varint(out: &mut Vec<u8>, n: u64) {
+    let mut n = n;
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+struct Encoder {
+    nodes: Vec<u8>,
+    count: usize,
+    ids: HashMap<*const Compt, usize>,
+}
+
+impl Encoder {
+    fn node(&mut self, compt: &Rc<Compt>) -> usize {
+        if let Some(id) = self.ids.get(&Rc::as_ptr(compt)) {
+            return *id;
+        }
+        match &**compt {
+            Compt::Lit(i) => {
+                self.nodes.push(TAG_LIT);
+                let zigzag = ((*i as i64) << 1) ^ ((*i as i64) >> 63);
+                write_varint(&mut self.nodes, zigzag as u64);
+            }
+            Compt::Access(idx) => {
+                self.nodes.push(TAG_ACCESS);
+                write_varint(&mut self.nodes, *idx as u64);
+            }
+            Compt::Close(body) => self.inner(TAG_CLOSE, &[body]),
+            Compt::Push(func, arg) => self.inner(TAG_PUSH, &[func, arg]),
+            Compt::Pair(fst, snd) => self.inner(TAG_PAIR, &[fst, snd]),
+            Compt::Fst(pair) => self.inner(TAG_FST, &[pair]),
+            Compt::Snd(pair) => self.inner(TAG_SND, &[pair]),
+            Compt::Inl(payload) => self.inner(TAG_INL, &[payload]),
+            Compt::Inr(payload) => self.inner(TAG_INR, &[payload]),
+            Compt::Case(scrut, left, right) => self.inner(TAG_CASE, &[scrut, left, right]),
+            Compt::Bool(b) => {
+                self.nodes.push(TAG_BOOL);
+                self.nodes.push(*b as u8);
+            }
+            Compt::If(cond, then, els) => self.inner(TAG_IF, &[cond, then, els]),
+            Compt::Str(s) => {
+                self.nodes.push(TAG_STR);
+                write_varint(&mut self.nodes, s.len() as u64);
+                self.nodes.extend(s.as_bytes());
+            }
+            Compt::Concat(lhs, rhs) => self.inner(TAG_CONCAT, &[lhs, rhs]),
+            Compt::Length(s) => self.inner(TAG_LENGTH, &[s]),
+            Compt::StrEq(lhs, rhs) => self.inner(TAG_STR_EQ, &[lhs, rhs]),
+            Compt::Global(idx) => {
+                self.nodes.push(TAG_GLOBAL);
+                write_varint(&mut self.nodes, *idx as u64);
+            }
+        }
+        self.ids.insert(Rc::as_ptr(compt), self.count);
+        self.count += 1;
+        self.count - 1
+    }
+
+    // A node with children: the children first, then the tag and their ids.
+    fn inner(&mut self, tag: u8, children: &[&Rc<Compt>]) {
+        let ids: Vec<usize> = children.iter().map(|c| self.node(c)).collect();
+        self.nodes.push(tag);
+        for id in ids {
+            write_varint(&mut self.nodes, id as u64);
+        }
+    }
+}
+
+pub fn encode(compt: &Rc<Compt>) -> Vec<u8> {
+    let mut enc = Encoder {
+        nodes: vec![],
+        count: 0,
+        ids: HashMap::new(),
+    };
+    enc.node(compt);
+    let mut out = MAGIC.to_vec();
+    out.push(VERSION);
+    write_varint(&mut out, enc.count as u64);
+    out.extend(enc.nodes);
+    out
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let b = *self.bytes.get(self.pos).ok_or(DecodeError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn varint(&mut self) -> Result<u64, DecodeError> {
+        let mut n: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            let bits = (b & 0x7f) as u64;
+            if shift == 63 && bits > 1 {
+                return Err(DecodeError::BadVarint);
+            }
+            n |= bits << shift;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(DecodeError::BadVarint)
+    }
+
+    fn string(&mut self) -> Result<String, DecodeError> {
+        let len = self.varint()?;
+        let rest = &self.bytes[self.pos..];
+        let len = usize::try_from(len)
+            .ok()
+            .filter(|len| *len <= rest.len())
+            .ok_or(DecodeError::UnexpectedEnd)?;
+        self.pos += len;
+        String::from_utf8(rest[..len].to_vec()).map_err(|_| DecodeError::BadString)
+    }
+
+    fn node_ref(&mut self, nodes: &[Rc<Compt>]) -> Result<Rc<Compt>, DecodeError> {
+        let id = self.varint()?;
+        usize::try_from(id)
+            .ok()
+            .and_then(|id| nodes.get(id).cloned())
+            .ok_or(DecodeError::BadRef(id as usize))
+    }
+}
+
+// Never panics: every malformed input is reported as a `DecodeError`.
+pub fn decode(bytes: &[u8]) -> Result<Rc<Compt>, DecodeError> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+    let mut r = Reader {
+        bytes,
+        pos: MAGIC.len(),
+    };
+    let version = r.byte()?;
+    let last_tag = last_tag(version).ok_or(DecodeError::BadVersion(version))?;
+    let count = r.varint()?;
+    // the count is untrusted, so it does not size any allocation
+    let mut nodes: Vec<Rc<Compt>> = vec![];
+    for _ in 0..count {
+        let node = match r.byte()? {
+            tag if tag > last_tag => return Err(DecodeError::BadTag(tag)),
+            TAG_LIT => {
+                let zigzag = r.varint()?;
+                let n = ((zigzag >> 1) as i64) ^ -((zigzag & 1) as i64);
+                Compt::Lit(i32::try_from(n).map_err(|_| DecodeError::LitOutOfRange)?)
+            }
+            TAG_ACCESS => {
+                let idx = r.varint()?;
+                Compt::Access(usize::try_from(idx).map_err(|_| DecodeError::BadVarint)?)
+            }
+            TAG_CLOSE => Compt::Close(r.node_ref(&nodes)?),
+            TAG_PUSH => {
+                let func = r.node_ref(&nodes)?;
+                Compt::Push(func, r.node_ref(&nodes)?)
+            }
+            TAG_PAIR => {
+                let fst = r.node_ref(&nodes)?;
+                Compt::Pair(fst, r.node_ref(&nodes)?)
+            }
+            TAG_FST => Compt::Fst(r.node_ref(&nodes)?),
+            TAG_SND => Compt::Snd(r.node_ref(&nodes)?),
+            TAG_INL => Compt::Inl(r.node_ref(&nodes)?),
+            TAG_INR => Compt::Inr(r.node_ref(&nodes)?),
+            TAG_CASE => {
+                let scrut = r.node_ref(&nodes)?;
+                let left = r.node_ref(&nodes)?;
+                Compt::Case(scrut, left, r.node_ref(&nodes)?)
+            }
+            TAG_BOOL => match r.byte()? {
+                0 => Compt::Bool(false),
+                1 => Compt::Bool(true),
+                b => return Err(DecodeError::BadBool(b)),
+            },
+            TAG_IF => {
+                let cond = r.node_ref(&nodes)?;
+                let then = r.node_ref(&nodes)?;
+                Compt::If(cond, then, r.node_ref(&nodes)?)
+            }
+            TAG_STR => Compt::Str(r.string()?),
+            TAG_CONCAT => {
+                let lhs = r.node_ref(&nodes)?;
+                Compt::Concat(lhs, r.node_ref(&nodes)?)
+            }
+            TAG_LENGTH => Compt::Length(r.node_ref(&nodes)?),
+            TAG_STR_EQ => {
+                let lhs = r.node_ref(&nodes)?;
+                Compt::StrEq(lhs, r.node_ref(&nodes)?)
+            }
+            TAG_GLOBAL => {
+                let idx = r.varint()?;
+                Compt::Global(usize::try_from(idx).map_err(|_| DecodeError::BadVarint)?)
+            }
+            tag => return Err(DecodeError::BadTag(tag)),
+        };
+        nodes.push(compt::node(node));
+    }
+    if r.pos != bytes.len() {
+        return Err(DecodeError::TrailingBytes);
+    }
+    nodes.pop().ok_or(DecodeError::NoNodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stlc::eval_defunct2::data::{ast, env, Cont, Val};
+    use crate::stlc::eval_defunct2::eval::{apply_compt, eval};
+    use crate::termgen::build::build;
+    use crate::termgen::generate::Gen;
+    use crate::termgen::rng::Rng;
+
+    #[test]
+    fn encode_literal() {
+        assert_eq!(
+            encode(&Rc::new(Compt::Lit(-1))),
+            vec![b'C', b'M', b'P', b'T', VERSION, 1, TAG_LIT, 1]
+        );
+        let c = eval(ast::app(ast::lam(ast::var(0)), ast::int(300)));
+        assert_eq!(decode(&encode(&c)), Ok(c));
+        let c = eval(ast::case(
+            ast::inl(ast::pair(ast::int(1), ast::int(2))),
+            ast::fst(ast::var(0)),
+            ast::snd(ast::inr(ast::var(0))),
+        ));
+        assert_eq!(decode(&encode(&c)), Ok(c));
+        let c = eval(ast::if_(
+            ast::str_eq(ast::str("héllo"), ast::str("")),
+            ast::length(ast::str("a")),
+            ast::concat(ast::str("b"), ast::bool(false)),
+        ));
+        assert_eq!(decode(&encode(&c)), Ok(c));
+        let c = eval(ast::app(ast::global(0), ast::global(300)));
+        assert_eq!(decode(&encode(&c)), Ok(c));
+    }
+
+    #[test]
+    fn encode_shares_subtrees() {
+        let id = Rc::new(Compt::Close(Rc::new(Compt::Access(0))));
+        let c = Rc::new(Compt::Push(id.clone(), id));
+        let bytes = encode(&c);
+        // Access, Close, Push
+        assert_eq!(bytes[5], 3);
+        match &*decode(&bytes).unwrap() {
+            Compt::Push(func, arg) => assert!(Rc::ptr_eq(func, arg)),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn decode_older_versions() {
+        // programs of the first version, before pairs and sums, of the second,
+        // and of the third, before globals
+        assert_eq!(decode(b"CMPT\x01\x01\x00\x02"), Ok(Rc::new(Compt::Lit(1))));
+        assert_eq!(
+            decode(b"CMPT\x02\x02\x00\x02\x07\x00"),
+            Ok(Rc::new(Compt::Inl(Rc::new(Compt::Lit(1)))))
+        );
+        assert_eq!(
+            decode(b"CMPT\x03\x01\x0c\x01a"),
+            Ok(Rc::new(Compt::Str(String::from("a"))))
+        );
+        // but only with the tags of their version
+        assert_eq!(decode(b"CMPT\x01\x01\x07\x00"), Err(DecodeError::BadTag(7)));
+        assert_eq!(
+            decode(b"CMPT\x02\x01\x0a\x01"),
+            Err(DecodeError::BadTag(10))
+        );
+        assert_eq!(
+            decode(b"CMPT\x03\x01\x10\x00"),
+            Err(DecodeError::BadTag(16))
+        );
+        assert_eq!(encode(&Rc::new(Compt::Lit(1)))[4], 4);
+    }
+
+    #[test]
+    fn decode_errors() {
+        let ok = encode(&Rc::new(Compt::Access(2)));
+        assert_eq!(decode(b""), Err(DecodeError::BadMagic));
+        assert_eq!(decode(b"CMPT"), Err(DecodeError::UnexpectedEnd));
+        assert_eq!(decode(b"CMPT\x05"), Err(DecodeError::BadVersion(5)));
+        assert_eq!(decode(b"CMPT\x03\x00"), Err(DecodeError::NoNodes));
+        assert_eq!(decode(b"CMPT\x04\x01\x11"), Err(DecodeError::BadTag(17)));
+        assert_eq!(
+            decode(b"CMPT\x03\x01\x0a\x02"),
+            Err(DecodeError::BadBool(2))
+        );
+        assert_eq!(
+            decode(b"CMPT\x03\x01\x0c\x05ab"),
+            Err(DecodeError::UnexpectedEnd)
+        );
+        assert_eq!(
+            decode(b"CMPT\x03\x01\x0c\x01\xff"),
+            Err(DecodeError::BadString)
+        );
+        assert_eq!(decode(b"CMPT\x03\x01\x02\x00"), Err(DecodeError::BadRef(0)));
+        assert_eq!(
+            decode(b"CMPT\x03\x01\x00\xff\xff\xff\xff\x7f"),
+            Err(DecodeError::LitOutOfRange)
+        );
+        assert_eq!(
+            decode(&[b"CMPT\x03\x01\x01".as_slice(), &[0xff; 10], &[0x01]].concat()),
+            Err(DecodeError::BadVarint)
+        );
+        assert_eq!(decode(&ok[..ok.len() - 1]), Err(DecodeError::UnexpectedEnd));
+        assert_eq!(
+            decode(&[ok.as_slice(), &[0]].concat()),
+            Err(DecodeError::TrailingBytes)
+        );
+    }
+
+    #[test]
+    fn round_trip_preserves_results() {
+        for seed in 0..500 {
+            let t = Gen::new(seed, 6, false).closed();
+            let c = eval(build(&t));
+            let c2 = decode(&encode(&c)).unwrap();
+            assert_eq!(c2, c, "seed {}: {:?}", seed, t);
+            let v1 = apply_compt(c, env::empty(), Cont::Cont0);
+            let v2 = apply_compt(c2, env::empty(), Cont::Cont0);
+            match (&*v1, &*v2) {
+                (Val::Int(i), Val::Int(j)) => assert_eq!(i, j, "seed {}: {:?}", seed, t),
+                (Val::Clos(_, b1), Val::Clos(_, b2)) => assert_eq!(b1, b2),
+                _ => panic!("seed {}: {:?}", seed, t),
+            }
+        }
+    }
+
+    #[test]
+    fn decode_garbage_never_panics() {
+        let mut rng = Rng::new(0);
+        for seed in 0..2000 {
+            // random bytes behind a valid header, or a corrupted encoding
+            let mut bytes = if rng.chance(1, 2) {
+                let mut bytes = b"CMPT\x04".to_vec();
+                for _ in 0..rng.below(40) {
+                    bytes.push(rng.next_u64() as u8);
+                }
+                bytes
+            } else {
+                encode(&eval(build(&Gen::new(seed, 5, false).closed())))
+            };
+            for _ in 0..rng.below(4) {
+                let at = rng.below(bytes.len());
+                bytes[at] ^= 1 << rng.below(8);
+            }
+            if rng.chance(1, 4) {
+                bytes.truncate(rng.below(bytes.len() + 1));
+            }
+            let _ = decode(&bytes);
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::stats;
+use crate::stats::Kind;
+use crate::stlc::eval_defunct1::ast::Ast;
+use crate::stlc::eval_defunct1::env::Env;
+use std::rc::Rc;
+
+// An unevaluated argument together with the environment it was pushed in.
+#[derive(Eq, PartialEq, Debug)]
+pub struct Closure {
+    pub code: Rc<Ast>,
+    pub env: Rc<Env<Closure>>,
+}
+
+// Weak head normal forms: the machine stops at a lambda with no arguments
+// left on the stack.
+#[derive(Eq, PartialEq, Debug)]
+pub enum Val {
+    Error,
+    Int(i32),
+    Clos(Rc<Ast>, Rc<Env<Closure>>), // body of the lambda and its environment
+}
+
+// Code for the compiled machine. A lambda is `Grab` followed by its body,
+// and an application pushes the code of its argument before running the
+// function, so there is no separate return instruction.
+#[derive(Eq, PartialEq, Debug)]
+pub enum Instr {
+    Lit(i32),
+    Access(usize),
+    Grab,
+    Push(Rc<Vec<Instr>>),
+    Fail, // a construct of the language the machine does not have
+}
+
+// Code pointer: the instruction sequence and a position in it.
+#[derive(Eq, PartialEq, Debug)]
+pub struct ZClosure {
+    pub code: Rc<Vec<Instr>>,
+    pub pc: usize,
+    pub env: Rc<Env<ZClosure>>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum ZVal {
+    Error,
+    Int(i32),
+    Clos(Rc<ZClosure>), // points just after the `Grab`
+}
+
+stats::counted!(Closure, Val, Instr, ZClosure, ZVal);
+
+pub mod closure {
+    use super::*;
+
+    pub fn new(code: Rc<Ast>, env: Rc<Env<Closure>>) -> Rc<Closure> {
+        stats::alloc(Kind::Val);
+        Rc::new(Closure { code, env })
+    }
+
+    pub fn zam(code: Rc<Vec<Instr>>, pc: usize, env: Rc<Env<ZClosure>>) -> Rc<ZClosure> {
+        stats::alloc(Kind::Val);
+        Rc::new(ZClosure { code, pc, env })
+    }
+}
+
+pub mod val {
+    use super::*;
+
+    fn alloc(v: Val) -> Rc<Val> {
+        stats::alloc(Kind::Val);
+        Rc::new(v)
+    }
+
+    pub fn error() -> Rc<Val> {
+        alloc(Val::Error)
+    }
+
+    pub fn int(i: i32) -> Rc<Val> {
+        alloc(Val::Int(i))
+    }
+
+    pub fn clos(body: Rc<Ast>, env: Rc<Env<Closure>>) -> Rc<Val> {
+        alloc(Val::Clos(body, env))
+    }
+}
+
+pub mod zval {
+    use super::*;
+
+    fn alloc(z: ZVal) -> Rc<ZVal> {
+        stats::alloc(Kind::Val);
+        Rc::new(z)
+    }
+
+    pub fn error() -> Rc<ZVal> {
+        alloc(ZVal::Error)
+    }
+
+    pub fn int(i: i32) -> Rc<ZVal> {
+        alloc(ZVal::Int(i))
+    }
+
+    pub fn clos(c: Rc<ZClosure>) -> Rc<ZVal> {
+        alloc(ZVal::Clos(c))
+    }
+}
@@ -0,0 +1,102 @@
+use crate::stlc::eval_defunct1::ast::Ast;
+use crate::stlc::eval_defunct1::env;
+use crate::stlc::eval_defunct1::env::Env;
+use crate::stlc::eval_krivine::data::*;
+use std::rc::Rc;
+
+// Krivine's machine: call-by-name evaluation to weak head normal form.
+// An application pushes its argument unevaluated, a lambda pops one, and a
+// variable jumps into the closure bound to it.
+pub fn eval(ast: Rc<Ast>, env: Rc<Env<Closure>>) -> Rc<Val> {
+    let mut ast = ast;
+    let mut env = env;
+    let mut stack: Vec<Rc<Closure>> = vec![];
+    loop {
+        match &*ast.clone() {
+            Ast::Int(i) if stack.is_empty() => return val::int(*i),
+            Ast::Int(_) => return val::error(),
+            Ast::Var(idx) => match env::lookup(env, *idx) {
+                Some(c) => {
+                    ast = c.code.clone();
+                    env = c.env.clone();
+                }
+                None => return val::error(),
+            },
+            Ast::Lam(body) => match stack.pop() {
+                Some(c) => {
+                    ast = body.clone();
+                    env = env::cons(c, env);
+                }
+                None => return val::clos(body.clone(), env),
+            },
+            Ast::App(func, arg) => {
+                stack.push(closure::new(arg.clone(), env.clone()));
+                ast = func.clone();
+            }
+            // only the pure lambda calculus is covered
+            _ => return val::error(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stlc::eval_defunct1::ast;
+    use crate::stlc::eval_defunct1::env::{cons, empty};
+
+    fn env1() -> Rc<Env<Closure>> {
+        cons(
+            closure::new(ast::int(10), empty()),
+            cons(closure::new(ast::int(20), empty()), empty()),
+        )
+    }
+
+    fn omega() -> Rc<Ast> {
+        let w = ast::lam(ast::app(ast::var(0), ast::var(0)));
+        ast::app(w.clone(), w)
+    }
+
+    #[test]
+    fn eval_literal() {
+        assert_eq!(eval(ast::int(1), env1()), val::int(1));
+        assert_eq!(eval(ast::var(0), env1()), val::int(10));
+        assert_eq!(eval(ast::var(1), env1()), val::int(20));
+        assert_eq!(
+            eval(
+                ast::app(
+                    ast::app(ast::lam(ast::lam(ast::var(1))), ast::int(33)),
+                    ast::int(44)
+                ),
+                env1()
+            ),
+            val::int(33)
+        )
+    }
+
+    #[test]
+    fn eval_whnf() {
+        // stops at the lambda without looking inside
+        let expr = ast::app(ast::lam(ast::lam(omega())), ast::int(1));
+        assert_eq!(
+            eval(expr, empty()),
+            val::clos(omega(), cons(closure::new(ast::int(1), empty()), empty()))
+        );
+    }
+
+    #[test]
+    fn eval_unused_divergent_argument() {
+        let expr = ast::app(ast::lam(ast::int(7)), omega());
+        assert_eq!(eval(expr, empty()), val::int(7));
+    }
+
+    #[test]
+    fn eval_stuck() {
+        assert_eq!(
+            eval(ast::app(ast::int(1), ast::int(2)), empty()),
+            val::error()
+        );
+        assert_eq!(eval(ast::var(2), env1()), val::error());
+        assert_eq!(eval(ast::fst(ast::var(0)), env1()), val::error());
+    }
+}
@@ -0,0 +1,96 @@
+use crate::pretty;
+use crate::pretty::Ctx;
+use crate::stlc::eval_defunct1::print::entries;
+use crate::stlc::eval_krivine::data::{Closure, Instr, Val, ZClosure, ZVal};
+use std::fmt;
+use std::slice;
+
+impl fmt::Display for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pretty::closure(&entries(&self.env), f, |ctx, f| {
+            pretty::Term::fmt_in(&*self.code, ctx, 0, f)
+        })
+    }
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Val::Error => write!(f, "error"),
+            Val::Int(i) => write!(f, "{}", i),
+            Val::Clos(body, env) => {
+                pretty::closure(&entries(env), f, |ctx, f| pretty::lam(&**body, ctx, 0, f))
+            }
+        }
+    }
+}
+
+// `Grab` binds a variable for the rest of its sequence.
+fn fmt_code(code: &[Instr], ctx: &mut Ctx, f: &mut fmt::Formatter) -> fmt::Result {
+    let mut grabs = 0;
+    let result = pretty::seq(code, ctx, f, |instr, ctx, f| match instr {
+        Instr::Lit(i) => write!(f, "lit {}", i),
+        Instr::Access(idx) => write!(f, "access {}", ctx.var(*idx, 0)),
+        Instr::Grab => {
+            grabs += 1;
+            write!(f, "grab {}", ctx.push(0))
+        }
+        Instr::Push(arg) => {
+            write!(f, "push {{")?;
+            fmt_code(arg, ctx, f)?;
+            write!(f, "}}")
+        }
+        Instr::Fail => write!(f, "fail"),
+    });
+    for _ in 0..grabs {
+        ctx.pop();
+    }
+    result
+}
+
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_code(slice::from_ref(self), &mut Ctx::new(), f)
+    }
+}
+
+// The code from the position on, in the environment.
+impl fmt::Display for ZClosure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pretty::closure(&entries(&self.env), f, |ctx, f| {
+            fmt_code(&self.code[self.pc..], ctx, f)
+        })
+    }
+}
+
+impl fmt::Display for ZVal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ZVal::Error => write!(f, "error"),
+            ZVal::Int(i) => write!(f, "{}", i),
+            // shown from the `Grab` that binds the argument
+            ZVal::Clos(c) => pretty::closure(&entries(&c.env), f, |ctx, f| {
+                fmt_code(&c.code[c.pc - 1..], ctx, f)
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stlc::eval_defunct1::ast::*;
+    use crate::stlc::eval_defunct1::env::{cons, empty};
+    use crate::stlc::eval_krivine::data::{closure, zval};
+    use crate::stlc::eval_krivine::zam::compile;
+
+    #[test]
+    fn print_zam() {
+        // (\x. \y. x) 1
+        let code = compile(&app(lam(lam(var(1))), int(1)));
+        let text: Vec<String> = code.iter().map(|i| i.to_string()).collect();
+        assert_eq!(text, ["push {lit 1}", "grab x0", "grab x0", "access #1"]);
+        let env = cons(closure::zam(compile(&int(1)), 0, empty()), empty());
+        let clos = zval::clos(closure::zam(code, 3, env));
+        assert_eq!(clos.to_string(), "{grab x1; access x0 | x0 = {lit 1}}");
+    }
+}
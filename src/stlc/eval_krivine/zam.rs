@@ -0,0 +1,170 @@
+use crate::stats;
+use crate::stats::Kind;
+use crate::stlc::eval_defunct1::ast::Ast;
+use crate::stlc::eval_defunct1::env;
+use crate::stlc::eval_defunct1::env::Env;
+use crate::stlc::eval_krivine::data::*;
+use std::rc::Rc;
+
+// Every instruction is counted as code in the statistics.
+fn emit(code: &mut Vec<Instr>, instr: Instr) {
+    stats::alloc(Kind::Code);
+    code.push(instr);
+}
+
+fn compile_into(ast: &Ast, code: &mut Vec<Instr>) {
+    match ast {
+        Ast::Int(i) => emit(code, Instr::Lit(*i)),
+        Ast::Var(idx) => emit(code, Instr::Access(*idx)),
+        Ast::Lam(body) => {
+            emit(code, Instr::Grab);
+            compile_into(body, code);
+        }
+        Ast::App(func, arg) => {
+            emit(code, Instr::Push(compile(arg)));
+            compile_into(func, code);
+        }
+        // an error when reached, as in the interpreted machine
+        _ => emit(code, Instr::Fail),
+    }
+}
+
+pub fn compile(ast: &Ast) -> Rc<Vec<Instr>> {
+    let mut code = vec![];
+    compile_into(ast, &mut code);
+    Rc::new(code)
+}
+
+// The Krivine machine over compiled code, in the style of the ZAM (Leroy,
+// "The ZINC experiment"): every sequence ends with `Lit`, `Access`,
+// `Fail` or a `Grab` that finds no argument, so falling off the end cannot
+// happen.
+pub fn run(code: Rc<Vec<Instr>>, env: Rc<Env<ZClosure>>) -> Rc<ZVal> {
+    let mut code = code;
+    let mut pc = 0;
+    let mut env = env;
+    let mut stack: Vec<Rc<ZClosure>> = vec![];
+    loop {
+        match code.get(pc) {
+            Some(Instr::Lit(i)) if stack.is_empty() => return zval::int(*i),
+            Some(Instr::Access(idx)) => match env::lookup(env, *idx) {
+                Some(c) => {
+                    code = c.code.clone();
+                    pc = c.pc;
+                    env = c.env.clone();
+                }
+                None => return zval::error(),
+            },
+            Some(Instr::Grab) => match stack.pop() {
+                Some(c) => {
+                    env = env::cons(c, env);
+                    pc += 1;
+                }
+                None => return zval::clos(closure::zam(code, pc + 1, env)),
+            },
+            Some(Instr::Push(arg)) => {
+                stack.push(closure::zam(arg.clone(), 0, env.clone()));
+                pc += 1;
+            }
+            _ => return zval::error(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stlc::eval_defunct1::ast;
+    use crate::stlc::eval_defunct1::env::{cons, empty};
+    use crate::stlc::eval_defunct1::eval::{eval_with, Cont, Machine, Strategy};
+    use crate::stlc::eval_defunct1::val as v1;
+    use crate::stlc::eval_krivine::eval::eval;
+    use crate::termgen::build::build;
+    use crate::termgen::shrink::check_agreement;
+
+    fn int(i: i32) -> Rc<ZVal> {
+        Rc::new(ZVal::Int(i))
+    }
+
+    fn env1() -> Rc<Env<ZClosure>> {
+        cons(
+            closure::zam(compile(&ast::int(10)), 0, empty()),
+            cons(closure::zam(compile(&ast::int(20)), 0, empty()), empty()),
+        )
+    }
+
+    fn omega() -> Rc<Ast> {
+        let w = ast::lam(ast::app(ast::var(0), ast::var(0)));
+        ast::app(w.clone(), w)
+    }
+
+    #[test]
+    fn compile_app() {
+        // (\x. x) 1
+        let code = compile(&ast::app(ast::lam(ast::var(0)), ast::int(1)));
+        assert_eq!(
+            *code,
+            vec![
+                Instr::Push(Rc::new(vec![Instr::Lit(1)])),
+                Instr::Grab,
+                Instr::Access(0)
+            ]
+        );
+    }
+
+    #[test]
+    fn run_literal() {
+        assert_eq!(run(compile(&ast::int(1)), env1()), int(1));
+        assert_eq!(run(compile(&ast::var(0)), env1()), int(10));
+        assert_eq!(run(compile(&ast::var(1)), env1()), int(20));
+        assert_eq!(
+            run(
+                compile(&ast::app(
+                    ast::app(ast::lam(ast::lam(ast::var(1))), ast::int(33)),
+                    ast::int(44)
+                )),
+                env1()
+            ),
+            int(33)
+        )
+    }
+
+    #[test]
+    fn run_unused_divergent_argument() {
+        let expr = ast::app(ast::lam(ast::int(7)), omega());
+        assert_eq!(run(compile(&expr), empty()), int(7));
+    }
+
+    #[test]
+    fn run_stuck() {
+        let expr = ast::app(ast::int(1), ast::int(2));
+        assert_eq!(run(compile(&expr), empty()), Rc::new(ZVal::Error));
+    }
+
+    #[test]
+    fn run_unsupported() {
+        let pair = ast::pair(ast::int(1), ast::int(2));
+        assert_eq!(run(compile(&pair), empty()), Rc::new(ZVal::Error));
+        assert_eq!(eval(pair.clone(), empty()), val::error());
+        // never reached under call-by-name
+        let expr = ast::app(ast::lam(ast::int(7)), pair);
+        assert_eq!(run(compile(&expr), empty()), int(7));
+    }
+
+    // Both machines give the call-by-name result of eval_defunct1.
+    #[test]
+    fn run_matches_eval_defunct1() {
+        check_agreement(false, 500, |t| {
+            let ast: Rc<Ast> = build(t);
+            let mut m = Machine::new(Strategy::CallByName);
+            let expected = eval_with(&mut m, ast.clone(), empty(), Cont::Cont0);
+            let krivine = eval(ast.clone(), empty());
+            let zam = run(compile(&ast), empty());
+            match (&*expected, &*krivine, &*zam) {
+                (v1::Val::Int(i), Val::Int(j), ZVal::Int(k)) => i == j && j == k,
+                (v1::Val::Clos(_, _), Val::Clos(_, _), ZVal::Clos(_)) => true,
+                _ => false,
+            }
+        });
+    }
+}
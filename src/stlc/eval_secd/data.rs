@@ -0,0 +1,131 @@
+use crate::stats;
+use crate::stats::Kind;
+use std::rc::Rc;
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Ast {
+    Int(i32),
+    Var(usize),
+    Lam(Rc<Ast>),
+    App(Rc<Ast>, Rc<Ast>),
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Instr {
+    Ldc(i32),            // push a constant
+    Ld(usize),           // push a variable
+    Ldf(Rc<Vec<Instr>>), // push a closure of the given code
+    Ap,                  // pop an argument and a closure, and call it
+    Rtn,                 // return to the caller saved in the dump
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Val {
+    Error,
+    Int(i32),
+    Clos(Rc<Env>, Rc<Vec<Instr>>),
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Env {
+    Nil,
+    Cons(Rc<Val>, Rc<Env>),
+}
+
+// The caller's registers, saved by `Ap` and restored by `Rtn`.
+#[derive(Eq, PartialEq, Debug)]
+pub struct Frame {
+    pub s: Vec<Rc<Val>>,
+    pub e: Rc<Env>,
+    pub c: Rc<Vec<Instr>>,
+    pub pc: usize,
+}
+
+// The four registers; the control is a code sequence with a position in it.
+#[derive(Eq, PartialEq, Debug)]
+pub struct Secd {
+    pub s: Vec<Rc<Val>>,
+    pub e: Rc<Env>,
+    pub c: Rc<Vec<Instr>>,
+    pub pc: usize,
+    pub d: Vec<Frame>,
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Status {
+    Running,
+    Halted(Rc<Val>),
+}
+
+stats::counted!(Instr, Val, Env);
+
+pub mod ast {
+    use super::*;
+
+    pub fn int(i: i32) -> Rc<Ast> {
+        Rc::new(Ast::Int(i))
+    }
+
+    pub fn var(i: usize) -> Rc<Ast> {
+        Rc::new(Ast::Var(i))
+    }
+
+    pub fn lam(a: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Lam(a))
+    }
+
+    pub fn app(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::App(a1, a2))
+    }
+}
+
+pub mod val {
+    use super::*;
+
+    fn alloc(v: Val) -> Rc<Val> {
+        stats::alloc(Kind::Val);
+        Rc::new(v)
+    }
+
+    pub fn error() -> Rc<Val> {
+        alloc(Val::Error)
+    }
+
+    pub fn int(i: i32) -> Rc<Val> {
+        alloc(Val::Int(i))
+    }
+
+    pub fn clos(env: Rc<Env>, code: Rc<Vec<Instr>>) -> Rc<Val> {
+        alloc(Val::Clos(env, code))
+    }
+}
+
+pub mod env {
+    use super::*;
+
+    fn alloc(env: Env) -> Rc<Env> {
+        stats::alloc(Kind::Env);
+        Rc::new(env)
+    }
+
+    pub fn empty() -> Rc<Env> {
+        alloc(Env::Nil)
+    }
+
+    pub fn cons(elm: Rc<Val>, env: Rc<Env>) -> Rc<Env> {
+        alloc(Env::Cons(elm, env))
+    }
+
+    pub fn lookup(env: Rc<Env>, idx: usize) -> Option<Rc<Val>> {
+        match &*env {
+            Env::Nil => None,
+            Env::Cons(head, tail) => {
+                if idx == 0 {
+                    Some(head.clone())
+                } else {
+                    lookup(tail.clone(), idx - 1)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use crate::stats;
+use crate::stats::Kind;
+use crate::stlc::eval_secd::data::*;
+use std::rc::Rc;
+
+// Every instruction is counted as code in the statistics.
+fn emit(code: &mut Vec<Instr>, instr: Instr) {
+    stats::alloc(Kind::Code);
+    code.push(instr);
+}
+
+fn compile_into(ast: &Ast, code: &mut Vec<Instr>) {
+    match ast {
+        Ast::Int(i) => emit(code, Instr::Ldc(*i)),
+        Ast::Var(idx) => emit(code, Instr::Ld(*idx)),
+        Ast::Lam(body) => {
+            let mut body_code = vec![];
+            compile_into(body, &mut body_code);
+            emit(&mut body_code, Instr::Rtn);
+            emit(code, Instr::Ldf(Rc::new(body_code)));
+        }
+        Ast::App(func, arg) => {
+            compile_into(func, code);
+            compile_into(arg, code);
+            emit(code, Instr::Ap);
+        }
+    }
+}
+
+// Function before argument, as in the other call-by-value evaluators.
+pub fn compile(ast: &Ast) -> Rc<Vec<Instr>> {
+    let mut code = vec![];
+    compile_into(ast, &mut code);
+    Rc::new(code)
+}
+
+impl Secd {
+    pub fn new(code: Rc<Vec<Instr>>, env: Rc<Env>) -> Secd {
+        Secd {
+            s: vec![],
+            e: env,
+            c: code,
+            pc: 0,
+            d: vec![],
+        }
+    }
+
+    fn halt(&mut self, v: Rc<Val>) -> Status {
+        self.s = vec![v.clone()];
+        self.pc = self.c.len();
+        Status::Halted(v)
+    }
+
+    // Execute one instruction. The machine halts when the top-level code
+    // runs out with an empty dump, leaving the result on the stack.
+    pub fn step(&mut self) -> Status {
+        let c = self.c.clone();
+        match c.get(self.pc) {
+            Some(Instr::Ldc(i)) => self.s.push(val::int(*i)),
+            Some(Instr::Ld(idx)) => match env::lookup(self.e.clone(), *idx) {
+                Some(v) => self.s.push(v),
+                None => return self.halt(val::error()),
+            },
+            Some(Instr::Ldf(body)) => self.s.push(val::clos(self.e.clone(), body.clone())),
+            Some(Instr::Ap) => {
+                let (Some(arg), Some(func)) = (self.s.pop(), self.s.pop()) else {
+                    return self.halt(val::error());
+                };
+                let Val::Clos(cenv, body) = &*func else {
+                    return self.halt(val::error());
+                };
+                stats::count(Kind::Cont);
+                self.d.push(Frame {
+                    s: std::mem::take(&mut self.s),
+                    e: std::mem::replace(&mut self.e, env::cons(arg, cenv.clone())),
+                    c: std::mem::replace(&mut self.c, body.clone()),
+                    pc: self.pc + 1,
+                });
+                self.pc = 0;
+                return Status::Running;
+            }
+            Some(Instr::Rtn) => {
+                let (Some(v), Some(frame)) = (self.s.pop(), self.d.pop()) else {
+                    return self.halt(val::error());
+                };
+                self.s = frame.s;
+                self.s.push(v);
+                self.e = frame.e;
+                self.c = frame.c;
+                self.pc = frame.pc;
+                return Status::Running;
+            }
+            None => {
+                return match (self.s.last(), self.d.is_empty()) {
+                    (Some(v), true) => Status::Halted(v.clone()),
+                    _ => self.halt(val::error()),
+                }
+            }
+        }
+        self.pc += 1;
+        Status::Running
+    }
+
+    pub fn run(&mut self) -> Rc<Val> {
+        loop {
+            if let Status::Halted(v) = self.step() {
+                return v;
+            }
+        }
+    }
+}
+
+pub fn eval(ast: &Ast, env: Rc<Env>) -> Rc<Val> {
+    Secd::new(compile(ast), env).run()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stlc::eval_secd::data::ast::*;
+    use crate::termgen::build::build;
+    use crate::termgen::shrink::check_agreement;
+
+    fn env1() -> Rc<Env> {
+        env::cons(val::int(10), env::cons(val::int(20), env::empty()))
+    }
+
+    #[test]
+    fn eval_literal() {
+        assert_eq!(eval(&int(1), env1()), val::int(1));
+        assert_eq!(eval(&var(0), env1()), val::int(10));
+        assert_eq!(eval(&var(1), env1()), val::int(20));
+        assert_eq!(
+            eval(&app(app(lam(lam(var(1))), int(33)), int(44)), env1()),
+            val::int(33)
+        );
+    }
+
+    #[test]
+    fn compile_app() {
+        assert_eq!(
+            *compile(&app(lam(var(0)), int(1))),
+            vec![
+                Instr::Ldf(Rc::new(vec![Instr::Ld(0), Instr::Rtn])),
+                Instr::Ldc(1),
+                Instr::Ap
+            ]
+        );
+    }
+
+    #[test]
+    fn step_registers() {
+        // (\x. x) 1
+        let code = compile(&app(lam(var(0)), int(1)));
+        let body = Rc::new(vec![Instr::Ld(0), Instr::Rtn]);
+        let mut m = Secd::new(code.clone(), env::empty());
+
+        assert_eq!(m.step(), Status::Running);
+        assert_eq!(m.s, vec![val::clos(env::empty(), body.clone())]);
+        assert_eq!(m.step(), Status::Running);
+        assert_eq!(m.step(), Status::Running); // Ap
+        assert!(m.s.is_empty());
+        assert_eq!(m.e, env::cons(val::int(1), env::empty()));
+        assert_eq!((m.c.clone(), m.pc), (body, 0));
+        assert_eq!(m.d.len(), 1);
+        assert_eq!(m.step(), Status::Running); // Ld 0
+        assert_eq!(m.step(), Status::Running); // Rtn
+        assert_eq!((m.c.clone(), m.pc), (code, 3));
+        assert!(m.d.is_empty());
+        assert_eq!(m.step(), Status::Halted(val::int(1)));
+    }
+
+    #[test]
+    fn eval_stuck() {
+        assert_eq!(eval(&app(int(1), int(2)), env::empty()), val::error());
+        assert_eq!(eval(&var(2), env1()), val::error());
+    }
+
+    #[test]
+    fn eval_matches_eval_defunct1() {
+        use crate::stlc::eval_defunct1::{env as env1, eval::eval as eval1, eval::Cont, val as v1};
+
+        check_agreement(false, 500, |t| {
+            let expected = eval1(build(t), env1::empty(), Cont::Cont0);
+            let actual = eval(&build::<Ast>(t), env::empty());
+            match (&*expected, &*actual) {
+                (v1::Val::Int(i), Val::Int(j)) => i == j,
+                (v1::Val::Clos(_, _), Val::Clos(_, _)) => true,
+                _ => false,
+            }
+        });
+    }
+}
@@ -0,0 +1,95 @@
+use crate::pretty;
+use crate::pretty::{Ctx, Prec, Term};
+use crate::stlc::eval_secd::data::{Ast, Env, Instr, Val};
+use std::fmt;
+use std::slice;
+
+impl Term for Ast {
+    fn prec(&self) -> Prec {
+        match self {
+            Ast::Int(_) | Ast::Var(_) => Prec::Atom,
+            Ast::Lam(_) => Prec::Open,
+            Ast::App(_, _) => Prec::App,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ast::Int(i) => write!(f, "{}", i),
+            Ast::Var(idx) => write!(f, "{}", ctx.var(*idx, lev)),
+            Ast::Lam(body) => pretty::lam(&**body, ctx, lev, f),
+            Ast::App(func, arg) => pretty::app(&**func, &**arg, ctx, lev, f),
+        }
+    }
+}
+
+pretty::display!(Ast);
+
+// The code of `Ldf` runs under a binder for its argument.
+fn fmt_code(code: &[Instr], ctx: &mut Ctx, f: &mut fmt::Formatter) -> fmt::Result {
+    pretty::seq(code, ctx, f, |instr, ctx, f| match instr {
+        Instr::Ldc(i) => write!(f, "ldc {}", i),
+        Instr::Ld(idx) => write!(f, "ld {}", ctx.var(*idx, 0)),
+        Instr::Ldf(body) => {
+            write!(f, "ldf {} {{", ctx.push(0))?;
+            let result = fmt_code(body, ctx, f);
+            ctx.pop();
+            result?;
+            write!(f, "}}")
+        }
+        Instr::Ap => write!(f, "ap"),
+        Instr::Rtn => write!(f, "rtn"),
+    })
+}
+
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_code(slice::from_ref(self), &mut Ctx::new(), f)
+    }
+}
+
+fn entries(mut env: &Env) -> Vec<&Val> {
+    let mut entries = vec![];
+    while let Env::Cons(head, tail) = env {
+        entries.push(&**head);
+        env = tail;
+    }
+    entries
+}
+
+impl fmt::Display for Env {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pretty::env(&entries(self), f)
+    }
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Val::Error => write!(f, "error"),
+            Val::Int(i) => write!(f, "{}", i),
+            Val::Clos(env, code) => pretty::closure(&entries(env), f, |ctx, f| {
+                write!(f, "\\{}. ", ctx.push(0))?;
+                fmt_code(code, ctx, f)
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stlc::eval_secd::data::ast::*;
+    use crate::stlc::eval_secd::data::{env, val};
+    use crate::stlc::eval_secd::eval::compile;
+
+    #[test]
+    fn print_secd() {
+        // (\x. \y. x) 1
+        let code = compile(&app(lam(lam(var(1))), int(1)));
+        let text: Vec<String> = code.iter().map(|i| i.to_string()).collect();
+        assert_eq!(text, ["ldf x0 {ldf x1 {ld x0; rtn}; rtn}", "ldc 1", "ap"]);
+        let env = env::cons(val::int(1), env::empty());
+        let clos = val::clos(env, compile(&var(1)));
+        assert_eq!(clos.to_string(), "{\\x1. ld x0 | x0 = 1}");
+    }
+}
@@ -0,0 +1,38 @@
+use crate::stats;
+use crate::stats::Kind;
+use std::rc::Rc;
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Ast {
+    Int(i32),
+    Var(usize),
+    Lam(Rc<Ast>),
+    App(Rc<Ast>, Rc<Ast>),
+}
+
+stats::counted!(Ast);
+
+pub mod ast {
+    use super::*;
+
+    fn alloc(a: Ast) -> Rc<Ast> {
+        stats::alloc(Kind::Code);
+        Rc::new(a)
+    }
+
+    pub fn int(i: i32) -> Rc<Ast> {
+        alloc(Ast::Int(i))
+    }
+
+    pub fn var(i: usize) -> Rc<Ast> {
+        alloc(Ast::Var(i))
+    }
+
+    pub fn lam(a: Rc<Ast>) -> Rc<Ast> {
+        alloc(Ast::Lam(a))
+    }
+
+    pub fn app(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
+        alloc(Ast::App(a1, a2))
+    }
+}
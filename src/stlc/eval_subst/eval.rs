@@ -0,0 +1,146 @@
+use crate::stlc::eval_subst::data::*;
+use std::rc::Rc;
+
+// Textbook call-by-value small-step semantics (TAPL ch. 6-7), as a
+// reference for the environment-based evaluators.
+
+// Add `d` to every variable index >= `cutoff`.
+pub fn shift(d: isize, cutoff: usize, ast: Rc<Ast>) -> Rc<Ast> {
+    match &*ast {
+        Ast::Int(_) => ast.clone(),
+        Ast::Var(idx) if *idx >= cutoff => ast::var((*idx as isize + d) as usize),
+        Ast::Var(_) => ast.clone(),
+        Ast::Lam(body) => ast::lam(shift(d, cutoff + 1, body.clone())),
+        Ast::App(func, arg) => ast::app(
+            shift(d, cutoff, func.clone()),
+            shift(d, cutoff, arg.clone()),
+        ),
+    }
+}
+
+// Replace variable `j` by `s` in `ast`.
+pub fn subst(j: usize, s: Rc<Ast>, ast: Rc<Ast>) -> Rc<Ast> {
+    match &*ast {
+        Ast::Int(_) => ast.clone(),
+        Ast::Var(idx) if *idx == j => s,
+        Ast::Var(_) => ast.clone(),
+        Ast::Lam(body) => ast::lam(subst(j + 1, shift(1, 0, s), body.clone())),
+        Ast::App(func, arg) => {
+            ast::app(subst(j, s.clone(), func.clone()), subst(j, s, arg.clone()))
+        }
+    }
+}
+
+pub fn is_value(ast: &Ast) -> bool {
+    matches!(ast, Ast::Int(_) | Ast::Lam(_))
+}
+
+// One reduction step, or `None` if the term is a value or stuck.
+pub fn step(ast: Rc<Ast>) -> Option<Rc<Ast>> {
+    match &*ast {
+        Ast::App(func, arg) if !is_value(func) => {
+            step(func.clone()).map(|func2| ast::app(func2, arg.clone()))
+        }
+        Ast::App(func, arg) if !is_value(arg) => {
+            step(arg.clone()).map(|arg2| ast::app(func.clone(), arg2))
+        }
+        Ast::App(func, arg) => match &**func {
+            Ast::Lam(body) => Some(shift(
+                -1,
+                0,
+                subst(0, shift(1, 0, arg.clone()), body.clone()),
+            )),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Iterator over a reduction sequence, starting with the term itself.
+pub struct Trace {
+    next: Option<Rc<Ast>>,
+}
+
+impl Iterator for Trace {
+    type Item = Rc<Ast>;
+
+    fn next(&mut self) -> Option<Rc<Ast>> {
+        let current = self.next.take()?;
+        self.next = step(current.clone());
+        Some(current)
+    }
+}
+
+pub fn trace(ast: Rc<Ast>) -> Trace {
+    Trace { next: Some(ast) }
+}
+
+// The last term of the reduction sequence: a value, or a stuck term.
+pub fn eval(ast: Rc<Ast>) -> Rc<Ast> {
+    trace(ast.clone()).last().unwrap_or(ast)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stlc::eval_subst::data::ast::*;
+
+    #[test]
+    fn shift_and_subst() {
+        assert_eq!(
+            shift(1, 0, lam(app(var(0), var(1)))),
+            lam(app(var(0), var(2)))
+        );
+        assert_eq!(shift(-1, 1, app(var(0), var(2))), app(var(0), var(1)));
+        assert_eq!(subst(0, int(5), app(var(0), var(1))), app(int(5), var(1)));
+        assert_eq!(
+            subst(0, var(3), lam(app(var(0), var(1)))),
+            lam(app(var(0), var(4)))
+        );
+    }
+
+    #[test]
+    fn step_literal() {
+        assert_eq!(step(int(1)), None);
+        assert_eq!(step(lam(var(0))), None);
+        assert_eq!(step(app(lam(var(0)), int(20))), Some(int(20)));
+        assert_eq!(step(app(int(1), int(2))), None);
+    }
+
+    #[test]
+    fn step_order() {
+        // the function is reduced before the argument
+        let redex = app(lam(var(0)), int(1));
+        assert_eq!(
+            step(app(redex.clone(), redex.clone())),
+            Some(app(int(1), redex.clone()))
+        );
+        assert_eq!(
+            step(app(lam(var(0)), redex.clone())),
+            Some(app(lam(var(0)), int(1)))
+        );
+    }
+
+    #[test]
+    fn trace_steps() {
+        let steps: Vec<Rc<Ast>> = trace(app(app(lam(lam(var(1))), int(33)), int(44))).collect();
+        assert_eq!(
+            steps,
+            vec![
+                app(app(lam(lam(var(1))), int(33)), int(44)),
+                app(lam(int(33)), int(44)),
+                int(33),
+            ]
+        );
+    }
+
+    #[test]
+    fn eval_under_binders() {
+        // (\x. \y. x) (\z. z) reduces to \y. \z. z, with indices adjusted
+        assert_eq!(eval(app(lam(lam(var(1))), lam(var(0)))), lam(lam(var(0))));
+        assert_eq!(
+            eval(app(lam(lam(app(var(1), var(0)))), lam(var(0)))),
+            lam(app(lam(var(0)), var(0)))
+        );
+    }
+}
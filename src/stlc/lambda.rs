@@ -0,0 +1,73 @@
+use crate::stlc::{anf, cps_trans, nbe};
+
+// The pure lambda terms some passes start from, whichever copy of `Ast`
+// they use.
+pub enum Shape<'a, A> {
+    Int,
+    Var(usize),
+    Lam(&'a A),
+    App(&'a A, &'a A),
+}
+
+pub trait Lambda: Sized {
+    fn shape(&self) -> Shape<'_, Self>;
+}
+
+// Number of free variables an (open) term may refer to.
+pub fn free_vars<A: Lambda>(ast: &A) -> usize {
+    match ast.shape() {
+        Shape::Int => 0,
+        Shape::Var(idx) => idx + 1,
+        Shape::Lam(body) => free_vars(body).saturating_sub(1),
+        Shape::App(func, arg) => free_vars(func).max(free_vars(arg)),
+    }
+}
+
+impl Lambda for nbe::data::Ast {
+    fn shape(&self) -> Shape<'_, Self> {
+        use nbe::data::Ast;
+        match self {
+            Ast::Int(_) => Shape::Int,
+            Ast::Var(idx) => Shape::Var(*idx),
+            Ast::Lam(body) => Shape::Lam(body),
+            Ast::App(func, arg) => Shape::App(func, arg),
+        }
+    }
+}
+
+impl Lambda for cps_trans::data::Ast {
+    fn shape(&self) -> Shape<'_, Self> {
+        use cps_trans::data::Ast;
+        match self {
+            Ast::Int(_) => Shape::Int,
+            Ast::Var(idx) => Shape::Var(*idx),
+            Ast::Lam(body) => Shape::Lam(body),
+            Ast::App(func, arg) => Shape::App(func, arg),
+        }
+    }
+}
+
+impl Lambda for anf::data::Ast {
+    fn shape(&self) -> Shape<'_, Self> {
+        use anf::data::Ast;
+        match self {
+            Ast::Int(_) => Shape::Int,
+            Ast::Var(idx) => Shape::Var(*idx),
+            Ast::Lam(body) => Shape::Lam(body),
+            Ast::App(func, arg) => Shape::App(func, arg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stlc::nbe::data::ast::*;
+
+    #[test]
+    fn count_free_vars() {
+        assert_eq!(free_vars(&*int(1)), 0);
+        assert_eq!(free_vars(&*lam(var(0))), 0);
+        assert_eq!(free_vars(&*lam(app(var(2), var(0)))), 2);
+    }
+}
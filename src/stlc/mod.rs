@@ -1,3 +1,11 @@
 pub mod eval_cps;
 pub mod eval_defunct1;
 pub mod eval_defunct2;
+pub mod nbe;
+pub mod eval_subst;
+pub mod closconv;
+pub mod cps_trans;
+pub mod anf;
+pub mod eval_krivine;
+pub mod eval_secd;
+pub mod lambda;
@@ -0,0 +1,132 @@
+use crate::stats;
+use crate::stats::Kind;
+use std::rc::Rc;
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Ast {
+    Int(i32),
+    Var(usize),
+    Lam(Rc<Ast>),
+    App(Rc<Ast>, Rc<Ast>),
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Val {
+    Error,
+    Int(i32),
+    Clos(Rc<Env>, Rc<Ast>),
+    Neu(Rc<Neutral>), // stuck on a free variable
+}
+
+// Variables in neutral terms are de Bruijn levels, so that they stay valid
+// while reading back under more binders.
+#[derive(Eq, PartialEq, Debug)]
+pub enum Neutral {
+    Var(usize),
+    App(Rc<Neutral>, Rc<Val>),
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum Env {
+    Nil,
+    Cons(Rc<Val>, Rc<Env>),
+}
+
+stats::counted!(Ast, Val, Neutral, Env);
+
+pub mod ast {
+    use super::*;
+
+    fn alloc(a: Ast) -> Rc<Ast> {
+        stats::alloc(Kind::Code);
+        Rc::new(a)
+    }
+
+    pub fn int(i: i32) -> Rc<Ast> {
+        alloc(Ast::Int(i))
+    }
+
+    pub fn var(i: usize) -> Rc<Ast> {
+        alloc(Ast::Var(i))
+    }
+
+    pub fn lam(a: Rc<Ast>) -> Rc<Ast> {
+        alloc(Ast::Lam(a))
+    }
+
+    pub fn app(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
+        alloc(Ast::App(a1, a2))
+    }
+}
+
+pub mod val {
+    use super::*;
+
+    fn alloc(v: Val) -> Rc<Val> {
+        stats::alloc(Kind::Val);
+        Rc::new(v)
+    }
+
+    pub fn error() -> Rc<Val> {
+        alloc(Val::Error)
+    }
+
+    pub fn int(i: i32) -> Rc<Val> {
+        alloc(Val::Int(i))
+    }
+
+    pub fn clos(env: Rc<Env>, body: Rc<Ast>) -> Rc<Val> {
+        alloc(Val::Clos(env, body))
+    }
+
+    pub fn neu(n: Rc<Neutral>) -> Rc<Val> {
+        alloc(Val::Neu(n))
+    }
+}
+
+pub mod neutral {
+    use super::*;
+
+    fn alloc(n: Neutral) -> Rc<Neutral> {
+        stats::alloc(Kind::Val);
+        Rc::new(n)
+    }
+
+    pub fn var(lvl: usize) -> Rc<Neutral> {
+        alloc(Neutral::Var(lvl))
+    }
+
+    pub fn app(func: Rc<Neutral>, arg: Rc<Val>) -> Rc<Neutral> {
+        alloc(Neutral::App(func, arg))
+    }
+}
+
+pub mod env {
+    use super::*;
+
+    fn alloc(env: Env) -> Rc<Env> {
+        stats::alloc(Kind::Env);
+        Rc::new(env)
+    }
+
+    pub fn empty() -> Rc<Env> {
+        alloc(Env::Nil)
+    }
+
+    pub fn cons(elm: Rc<Val>, env: Rc<Env>) -> Rc<Env> {
+        alloc(Env::Cons(elm, env))
+    }
+
+    pub fn lookup(env: Rc<Env>, idx: usize) -> Option<Rc<Val>> {
+        match &*env {
+            Env::Nil => None,
+            Env::Cons(head, tail) => {
+                if idx == 0 {
+                    Some(head.clone())
+                } else {
+                    lookup(tail.clone(), idx - 1)
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use crate::stlc::lambda::free_vars;
+use crate::stlc::nbe::data::*;
+use std::rc::Rc;
+
+// Normalization by evaluation: evaluate into `Val`, where free variables
+// become neutral terms, then read values back into beta-normal `Ast`s,
+// going under lambdas by applying closures to fresh variables.
+
+pub fn eval(ast: Rc<Ast>, env: Rc<Env>) -> Rc<Val> {
+    match &*ast {
+        Ast::Int(i) => val::int(*i),
+        Ast::Var(idx) => env::lookup(env, *idx).unwrap_or_else(val::error),
+        Ast::Lam(body) => val::clos(env, body.clone()),
+        Ast::App(func, arg) => {
+            let funcv = eval(func.clone(), env.clone());
+            let argv = eval(arg.clone(), env);
+            apply(funcv, argv)
+        }
+    }
+}
+
+fn apply(func: Rc<Val>, arg: Rc<Val>) -> Rc<Val> {
+    match &*func {
+        Val::Clos(cenv, body) => eval(body.clone(), env::cons(arg, cenv.clone())),
+        Val::Neu(n) => val::neu(neutral::app(n.clone(), arg)),
+        _ => val::error(),
+    }
+}
+
+// `depth` is the number of binders we are under; `None` if the value is
+// (or contains) a runtime error.
+pub fn readback(depth: usize, v: Rc<Val>) -> Option<Rc<Ast>> {
+    match &*v {
+        Val::Error => None,
+        Val::Int(i) => Some(ast::int(*i)),
+        Val::Clos(_, _) => {
+            let body = apply(v.clone(), val::neu(neutral::var(depth)));
+            readback(depth + 1, body).map(ast::lam)
+        }
+        Val::Neu(n) => readback_neutral(depth, n.clone()),
+    }
+}
+
+fn readback_neutral(depth: usize, n: Rc<Neutral>) -> Option<Rc<Ast>> {
+    match &*n {
+        Neutral::Var(lvl) => Some(ast::var(depth - lvl - 1)),
+        Neutral::App(func, arg) => Some(ast::app(
+            readback_neutral(depth, func.clone())?,
+            readback(depth, arg.clone())?,
+        )),
+    }
+}
+
+// Normal form of a term with `nfree` free variables.
+pub fn normalize_open(nfree: usize, ast: Rc<Ast>) -> Option<Rc<Ast>> {
+    let mut env = env::empty();
+    for lvl in 0..nfree {
+        env = env::cons(val::neu(neutral::var(lvl)), env);
+    }
+    readback(nfree, eval(ast, env))
+}
+
+pub fn normalize(ast: Rc<Ast>) -> Option<Rc<Ast>> {
+    normalize_open(free_vars(&*ast), ast)
+}
+
+pub fn beta_eq(a1: Rc<Ast>, a2: Rc<Ast>) -> bool {
+    let nfree = free_vars(&*a1).max(free_vars(&*a2));
+    match (normalize_open(nfree, a1), normalize_open(nfree, a2)) {
+        (Some(n1), Some(n2)) => n1 == n2,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stlc::nbe::data::ast::*;
+
+    fn church(n: usize) -> Rc<Ast> {
+        let mut body = var(0);
+        for _ in 0..n {
+            body = app(var(1), body);
+        }
+        lam(lam(body))
+    }
+
+    // \m. \n. \f. \x. m f (n f x)
+    fn plus() -> Rc<Ast> {
+        lam(lam(lam(lam(app(
+            app(var(3), var(1)),
+            app(app(var(2), var(1)), var(0)),
+        )))))
+    }
+
+    #[test]
+    fn normalize_closed() {
+        assert_eq!(normalize(int(1)), Some(int(1)));
+        assert_eq!(normalize(app(lam(var(0)), int(20))), Some(int(20)));
+        assert_eq!(normalize(lam(app(lam(var(0)), var(0)))), Some(lam(var(0))));
+        assert_eq!(
+            normalize(lam(app(lam(lam(var(1))), var(0)))),
+            Some(lam(lam(var(1))))
+        );
+    }
+
+    #[test]
+    fn normalize_open_terms() {
+        assert_eq!(normalize(var(3)), Some(var(3)));
+        assert_eq!(normalize(app(lam(var(0)), var(3))), Some(var(3)));
+        assert_eq!(
+            normalize(lam(app(var(1), app(lam(var(0)), var(0))))),
+            Some(lam(app(var(1), var(0))))
+        );
+        assert_eq!(
+            normalize(app(lam(lam(app(var(1), var(2)))), var(0))),
+            Some(lam(app(var(1), var(1))))
+        );
+    }
+
+    #[test]
+    fn normalize_errors() {
+        assert_eq!(normalize(app(int(1), int(2))), None);
+        assert_eq!(normalize(lam(app(int(1), var(0)))), None);
+    }
+
+    #[test]
+    fn church_arithmetic() {
+        assert_eq!(normalize(app(app(plus(), church(2)), church(3))), Some(church(5)));
+        assert!(beta_eq(app(app(plus(), church(1)), church(1)), church(2)));
+        assert!(!beta_eq(app(app(plus(), church(1)), church(1)), church(3)));
+    }
+
+    #[test]
+    fn beta_eq_open() {
+        assert!(beta_eq(app(lam(app(var(0), var(1))), var(0)), app(var(0), var(0))));
+        assert!(!beta_eq(var(0), var(1)));
+    }
+}
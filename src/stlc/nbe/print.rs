@@ -0,0 +1,81 @@
+use crate::pretty;
+use crate::pretty::{Ctx, Prec, Term};
+use crate::stlc::nbe::data::{Ast, Env, Neutral, Val};
+use std::fmt;
+
+impl Term for Ast {
+    fn prec(&self) -> Prec {
+        match self {
+            Ast::Int(_) | Ast::Var(_) => Prec::Atom,
+            Ast::Lam(_) => Prec::Open,
+            Ast::App(_, _) => Prec::App,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ast::Int(i) => write!(f, "{}", i),
+            Ast::Var(idx) => write!(f, "{}", ctx.var(*idx, lev)),
+            Ast::Lam(body) => pretty::lam(&**body, ctx, lev, f),
+            Ast::App(func, arg) => pretty::app(&**func, &**arg, ctx, lev, f),
+        }
+    }
+}
+
+pretty::display!(Ast);
+
+fn entries(mut env: &Env) -> Vec<&Val> {
+    let mut entries = vec![];
+    while let Env::Cons(head, tail) = env {
+        entries.push(&**head);
+        env = tail;
+    }
+    entries
+}
+
+impl fmt::Display for Env {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        pretty::env(&entries(self), f)
+    }
+}
+
+impl fmt::Display for Val {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Val::Error => write!(f, "error"),
+            Val::Int(i) => write!(f, "{}", i),
+            Val::Clos(env, body) => {
+                pretty::closure(&entries(env), f, |ctx, f| pretty::lam(&**body, ctx, 0, f))
+            }
+            Val::Neu(n) => n.fmt(f),
+        }
+    }
+}
+
+// A variable bound at level n is named `xn`, as in the term read back.
+impl fmt::Display for Neutral {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Neutral::Var(lvl) => write!(f, "x{}", lvl),
+            Neutral::App(func, arg) => match &**arg {
+                Val::Clos(_, _) | Val::Neu(_) => write!(f, "{} ({})", func, arg),
+                _ => write!(f, "{} {}", func, arg),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stlc::nbe::data::{neutral, val};
+
+    #[test]
+    fn print_neutral() {
+        let inner = val::neu(neutral::app(neutral::var(1), val::int(2)));
+        let v = val::neu(neutral::app(
+            neutral::app(neutral::var(0), inner),
+            val::int(3),
+        ));
+        assert_eq!(v.to_string(), "x0 (x1 2) 3");
+    }
+}
@@ -0,0 +1,93 @@
+use std::rc::Rc;
+
+// Surface syntax with named variables. Everything here is sugar over the
+// core language, which `desugar` targets.
+#[derive(Eq, PartialEq, Debug)]
+pub enum Expr {
+    Int(i32),
+    Var(String),
+    Lam(Vec<String>, Rc<Expr>),      // \x y. body
+    App(Rc<Expr>, Vec<Rc<Expr>>),    // f a b
+    Let(String, Rc<Expr>, Rc<Expr>), // let x = e1 in e2
+    Seq(Rc<Expr>, Rc<Expr>),         // e1; e2
+    Quo(Rc<Expr>),
+    Unq(Rc<Expr>),
+}
+
+// Core terms, kept only to print the result of desugaring.
+#[derive(Eq, PartialEq, Debug)]
+pub enum Ast {
+    Int(i32),
+    Var(usize),
+    Lam(Rc<Ast>),
+    App(Rc<Ast>, Rc<Ast>),
+    Quo(Rc<Ast>),
+    Unq(Rc<Ast>),
+}
+
+pub mod expr {
+    use super::*;
+
+    pub fn int(i: i32) -> Rc<Expr> {
+        Rc::new(Expr::Int(i))
+    }
+
+    pub fn var(x: &str) -> Rc<Expr> {
+        Rc::new(Expr::Var(String::from(x)))
+    }
+
+    pub fn lam(params: &[&str], body: Rc<Expr>) -> Rc<Expr> {
+        Rc::new(Expr::Lam(
+            params.iter().map(|x| String::from(*x)).collect(),
+            body,
+        ))
+    }
+
+    pub fn app(func: Rc<Expr>, args: Vec<Rc<Expr>>) -> Rc<Expr> {
+        Rc::new(Expr::App(func, args))
+    }
+
+    pub fn let_(x: &str, bound: Rc<Expr>, body: Rc<Expr>) -> Rc<Expr> {
+        Rc::new(Expr::Let(String::from(x), bound, body))
+    }
+
+    pub fn seq(first: Rc<Expr>, second: Rc<Expr>) -> Rc<Expr> {
+        Rc::new(Expr::Seq(first, second))
+    }
+
+    pub fn quo(body: Rc<Expr>) -> Rc<Expr> {
+        Rc::new(Expr::Quo(body))
+    }
+
+    pub fn unq(body: Rc<Expr>) -> Rc<Expr> {
+        Rc::new(Expr::Unq(body))
+    }
+}
+
+pub mod ast {
+    use super::*;
+
+    pub fn int(i: i32) -> Rc<Ast> {
+        Rc::new(Ast::Int(i))
+    }
+
+    pub fn var(i: usize) -> Rc<Ast> {
+        Rc::new(Ast::Var(i))
+    }
+
+    pub fn lam(a: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Lam(a))
+    }
+
+    pub fn app(a1: Rc<Ast>, a2: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::App(a1, a2))
+    }
+
+    pub fn quo(a: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Quo(a))
+    }
+
+    pub fn unq(a: Rc<Ast>) -> Rc<Ast> {
+        Rc::new(Ast::Unq(a))
+    }
+}
@@ -0,0 +1,249 @@
+use crate::build::Build;
+use crate::surface::data::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum DesugarError {
+    Unbound(String),
+    NoParams, // \. body
+    NoArgs,   // application to nothing
+    UnqOutsideQuo,
+    Unstaged,  // quotation in a language without stages
+    NoGlobals, // reference to a global in a language without them
+}
+
+impl fmt::Display for DesugarError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DesugarError::Unbound(x) => write!(f, "unbound variable {}", x),
+            DesugarError::NoParams => write!(f, "lambda without parameters"),
+            DesugarError::NoArgs => write!(f, "application without arguments"),
+            DesugarError::UnqOutsideQuo => write!(f, "unquote outside of a quotation"),
+            DesugarError::Unstaged => write!(f, "quotation in an unstaged language"),
+            DesugarError::NoGlobals => write!(f, "global reference in a language without globals"),
+        }
+    }
+}
+
+impl std::error::Error for DesugarError {}
+
+// Binders in scope, innermost last, each tagged with its stage. `None` is
+// the binder introduced for `e1; e2`, which no name can refer to.
+type Ctx = Vec<(i32, Option<String>)>;
+
+// De Bruijn indices count only the binders of the current stage, as in
+// the staged evaluators.
+fn lookup(ctx: &Ctx, lev: i32, x: &str) -> Result<usize, DesugarError> {
+    ctx.iter()
+        .rev()
+        .filter(|(l, _)| *l == lev)
+        .position(|(_, y)| y.as_deref() == Some(x))
+        .ok_or_else(|| DesugarError::Unbound(String::from(x)))
+}
+
+// Indices of the top-level definitions in scope, by name.
+pub type Globals = HashMap<String, usize>;
+
+fn desugar_in<A: Build>(
+    ctx: &mut Ctx,
+    globals: &Globals,
+    lev: i32,
+    e: &Expr,
+) -> Result<Rc<A>, DesugarError> {
+    match e {
+        Expr::Int(i) => Ok(A::int(*i)),
+        Expr::Var(x) => match lookup(ctx, lev, x) {
+            Ok(idx) => Ok(A::var(idx)),
+            // any local binder, even of another stage, hides a global
+            Err(err) if ctx.iter().any(|(_, y)| y.as_deref() == Some(x.as_str())) => Err(err),
+            Err(err) => match globals.get(x) {
+                Some(&idx) => A::global(idx).ok_or(DesugarError::NoGlobals),
+                None => Err(err),
+            },
+        },
+        Expr::Lam(params, _) if params.is_empty() => Err(DesugarError::NoParams),
+        Expr::Lam(params, body) => {
+            // \x y. body => \x. \y. body
+            for x in params {
+                ctx.push((lev, Some(x.clone())));
+            }
+            let body = desugar_in(ctx, globals, lev, body);
+            ctx.truncate(ctx.len() - params.len());
+            Ok(params.iter().fold(body?, |body, _| A::lam(body)))
+        }
+        Expr::App(_, args) if args.is_empty() => Err(DesugarError::NoArgs),
+        Expr::App(func, args) => {
+            // f a b => (f a) b
+            let mut result = desugar_in(ctx, globals, lev, func)?;
+            for arg in args {
+                result = A::app(result, desugar_in(ctx, globals, lev, arg)?);
+            }
+            Ok(result)
+        }
+        Expr::Let(x, bound, body) => {
+            // let x = e1 in e2 => (\x. e2) e1
+            let bound = desugar_in(ctx, globals, lev, bound)?;
+            ctx.push((lev, Some(x.clone())));
+            let body = desugar_in(ctx, globals, lev, body);
+            ctx.pop();
+            Ok(A::app(A::lam(body?), bound))
+        }
+        Expr::Seq(first, second) => {
+            // e1; e2 => (\_. e2) e1
+            let first = desugar_in(ctx, globals, lev, first)?;
+            ctx.push((lev, None));
+            let second = desugar_in(ctx, globals, lev, second);
+            ctx.pop();
+            Ok(A::app(A::lam(second?), first))
+        }
+        Expr::Quo(body) => {
+            A::quo(desugar_in(ctx, globals, lev + 1, body)?).ok_or(DesugarError::Unstaged)
+        }
+        Expr::Unq(_) if lev == 0 => Err(DesugarError::UnqOutsideQuo),
+        Expr::Unq(body) => {
+            A::unq(desugar_in(ctx, globals, lev - 1, body)?).ok_or(DesugarError::Unstaged)
+        }
+    }
+}
+
+// Translate a closed surface expression into any core `Ast`. Quotations
+// are an error when the target language is not staged.
+pub fn desugar<A: Build>(e: &Expr) -> Result<Rc<A>, DesugarError> {
+    desugar_with(&Globals::new(), e)
+}
+
+// Like `desugar`, but names not bound locally may refer to `globals`.
+pub fn desugar_with<A: Build>(globals: &Globals, e: &Expr) -> Result<Rc<A>, DesugarError> {
+    desugar_in(&mut Vec::new(), globals, 0, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::surface::data::expr::*;
+
+    fn core(e: &Expr) -> Rc<Ast> {
+        desugar(e).unwrap()
+    }
+
+    #[test]
+    fn desugar_let_and_seq() {
+        assert_eq!(
+            *core(&let_("x", int(1), var("x"))),
+            Ast::App(ast::lam(ast::var(0)), ast::int(1))
+        );
+        // the binder for `;` does not capture x
+        assert_eq!(
+            *core(&let_("x", int(1), seq(int(2), var("x")))),
+            Ast::App(
+                ast::lam(ast::app(ast::lam(ast::var(1)), ast::int(2))),
+                ast::int(1)
+            )
+        );
+        // let is not recursive
+        assert_eq!(
+            desugar::<Ast>(&let_("x", var("x"), int(1))),
+            Err(DesugarError::Unbound(String::from("x")))
+        );
+    }
+
+    #[test]
+    fn desugar_multi() {
+        // \x y. x, applied to 1 and 2
+        assert_eq!(
+            *core(&app(lam(&["x", "y"], var("x")), vec![int(1), int(2)])),
+            Ast::App(
+                ast::app(ast::lam(ast::lam(ast::var(1))), ast::int(1)),
+                ast::int(2)
+            )
+        );
+        // shadowing
+        assert_eq!(
+            *core(&lam(&["x", "x"], var("x"))),
+            *ast::lam(ast::lam(ast::var(0)))
+        );
+        assert_eq!(
+            desugar::<Ast>(&lam(&[], int(1))),
+            Err(DesugarError::NoParams)
+        );
+        assert_eq!(
+            desugar::<Ast>(&app(int(1), vec![])),
+            Err(DesugarError::NoArgs)
+        );
+    }
+
+    #[test]
+    fn desugar_stages() {
+        use crate::stlc::eval_defunct1;
+
+        // \x. <\y. ~x y>: y is the innermost binder of stage 1, x of stage 0
+        let e = lam(&["x"], quo(lam(&["y"], app(unq(var("x")), vec![var("y")]))));
+        assert_eq!(
+            *core(&e),
+            *ast::lam(ast::quo(ast::lam(ast::app(
+                ast::unq(ast::var(0)),
+                ast::var(0)
+            ))))
+        );
+        // variables of another stage are not in scope
+        assert_eq!(
+            desugar::<Ast>(&lam(&["x"], quo(var("x")))),
+            Err(DesugarError::Unbound(String::from("x")))
+        );
+        assert_eq!(
+            desugar::<Ast>(&unq(int(1))),
+            Err(DesugarError::UnqOutsideQuo)
+        );
+        // only staged languages have quotations
+        assert_eq!(
+            desugar::<eval_defunct1::ast::Ast>(&quo(int(1))),
+            Err(DesugarError::Unstaged)
+        );
+    }
+
+    #[test]
+    fn desugar_and_run() {
+        use crate::lamcirc::eval_cek;
+        use crate::stlc::eval_defunct1::{env, eval, val};
+
+        // let k = \x y. x in let id = \z. z in (id 0; k 5 6)
+        let e = let_(
+            "k",
+            lam(&["x", "y"], var("x")),
+            let_(
+                "id",
+                lam(&["z"], var("z")),
+                seq(
+                    app(var("id"), vec![int(0)]),
+                    app(var("k"), vec![int(5), int(6)]),
+                ),
+            ),
+        );
+        assert_eq!(
+            eval::eval(desugar(&e).unwrap(), env::empty(), eval::Cont::Cont0),
+            val::int(5)
+        );
+
+        // let c = <1> in <(\x. x) ~c>
+        let e = let_(
+            "c",
+            quo(int(1)),
+            quo(app(lam(&["x"], var("x")), vec![unq(var("c"))])),
+        );
+        let v = eval_cek::eval::eval(
+            0,
+            desugar(&e).unwrap(),
+            eval_cek::data::env::empty(),
+            eval_cek::eval::Cont::End,
+        );
+        assert_eq!(
+            v,
+            eval_cek::data::val::quo(eval_cek::data::ast::app(
+                eval_cek::data::ast::lam(eval_cek::data::ast::var(0)),
+                eval_cek::data::ast::int(1)
+            ))
+        );
+    }
+}
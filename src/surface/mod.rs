@@ -0,0 +1,4 @@
+pub mod data;
+pub mod desugar;
+pub mod print;
+pub mod program;
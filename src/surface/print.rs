@@ -0,0 +1,112 @@
+use crate::pretty;
+use crate::pretty::{Ctx, Prec, Term};
+use crate::surface::data::*;
+use std::fmt;
+
+// Printing puts parentheses around anything that is not atomic when it
+// appears as a function, an argument or the body of a quote or unquote.
+
+fn atomic_expr(e: &Expr) -> bool {
+    matches!(e, Expr::Int(_) | Expr::Var(_) | Expr::Quo(_))
+}
+
+fn fmt_expr_arg(e: &Expr, f: &mut fmt::Formatter) -> fmt::Result {
+    if atomic_expr(e) {
+        write!(f, "{}", e)
+    } else {
+        write!(f, "({})", e)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Int(i) => write!(f, "{}", i),
+            Expr::Var(x) => write!(f, "{}", x),
+            Expr::Lam(params, body) => write!(f, "\\{}. {}", params.join(" "), body),
+            Expr::App(func, args) => {
+                fmt_expr_arg(func, f)?;
+                for arg in args {
+                    write!(f, " ")?;
+                    fmt_expr_arg(arg, f)?;
+                }
+                Ok(())
+            }
+            Expr::Let(x, bound, body) => write!(f, "let {} = {} in {}", x, bound, body),
+            Expr::Seq(first, second) => {
+                match **first {
+                    Expr::Lam(..) | Expr::Let(..) | Expr::Seq(..) => write!(f, "({})", first)?,
+                    _ => write!(f, "{}", first)?,
+                }
+                write!(f, "; {}", second)
+            }
+            Expr::Quo(body) => write!(f, "<{}>", body),
+            Expr::Unq(body) => {
+                write!(f, "~")?;
+                fmt_expr_arg(body, f)
+            }
+        }
+    }
+}
+
+// Core terms are printed with generated names, see `pretty`.
+impl Term for Ast {
+    fn prec(&self) -> Prec {
+        match self {
+            Ast::Int(_) | Ast::Var(_) | Ast::Quo(_) => Prec::Atom,
+            Ast::App(_, _) | Ast::Unq(_) => Prec::App,
+            Ast::Lam(_) => Prec::Open,
+        }
+    }
+
+    fn fmt_in(&self, ctx: &mut Ctx, lev: i32, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ast::Int(i) => write!(f, "{}", i),
+            Ast::Var(idx) => write!(f, "{}", ctx.var(*idx, lev)),
+            Ast::Lam(body) => pretty::lam(&**body, ctx, lev, f),
+            Ast::App(func, arg) => pretty::app(&**func, &**arg, ctx, lev, f),
+            Ast::Quo(body) => pretty::quo(&**body, ctx, lev, f),
+            Ast::Unq(body) => pretty::unq(&**body, ctx, lev, f),
+        }
+    }
+}
+
+pretty::display!(Ast);
+
+#[cfg(test)]
+mod tests {
+    use crate::surface::data::expr::*;
+    use crate::surface::data::{ast, Ast};
+    use crate::surface::desugar::desugar;
+
+    #[test]
+    fn print_expr() {
+        let e = let_(
+            "f",
+            lam(&["x", "y"], app(var("x"), vec![var("y"), int(1)])),
+            seq(
+                app(var("f"), vec![lam(&["z"], var("z")), int(2)]),
+                quo(unq(var("f"))),
+            ),
+        );
+        assert_eq!(
+            format!("{}", e),
+            "let f = \\x y. x y 1 in f (\\z. z) 2; <~f>"
+        );
+    }
+
+    #[test]
+    fn print_desugared() {
+        let e = let_(
+            "k",
+            lam(&["x", "y"], var("x")),
+            app(var("k"), vec![int(1), int(2)]),
+        );
+        let core: std::rc::Rc<Ast> = desugar(&e).unwrap();
+        assert_eq!(format!("{}", core), "(\\x0. x0 1 2) (\\x0. \\x1. x0)");
+        let e = lam(&["a"], quo(lam(&["b"], app(unq(var("a")), vec![var("b")]))));
+        let core: std::rc::Rc<Ast> = desugar(&e).unwrap();
+        assert_eq!(format!("{}", core), "\\x0. <\\x1. ~x0 x1>");
+        assert_eq!(format!("{}", ast::lam(ast::var(3))), "\\x0. #3");
+    }
+}
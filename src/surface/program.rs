@@ -0,0 +1,433 @@
+use crate::build::Build;
+use crate::surface::data::*;
+use crate::surface::desugar::{desugar_with, DesugarError, Globals};
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+// A source file: the files it imports and its top-level definitions. All
+// definitions of a file are in scope in each of them, so they may be
+// mutually recursive, and so are the definitions of the imported files.
+#[derive(Eq, PartialEq, Debug)]
+pub struct Module {
+    pub imports: Vec<String>,
+    pub defs: Vec<(String, Rc<Expr>)>,
+}
+
+pub fn module(imports: &[&str], defs: Vec<(&str, Rc<Expr>)>) -> Module {
+    Module {
+        imports: imports.iter().map(|name| String::from(*name)).collect(),
+        defs: defs
+            .into_iter()
+            .map(|(name, body)| (String::from(name), body))
+            .collect(),
+    }
+}
+
+// A linked program. Definitions of all files reachable from the root are
+// numbered in one global table, imported files first, and refer to each
+// other through `Global` nodes.
+#[derive(Debug)]
+pub struct Program<A> {
+    pub names: Vec<(String, String)>, // file and name of each definition
+    pub defs: Vec<Rc<A>>,
+}
+
+impl<A> Program<A> {
+    pub fn index(&self, file: &str, name: &str) -> Option<usize> {
+        self.names.iter().position(|(f, x)| f == file && x == name)
+    }
+}
+
+#[derive(Eq, PartialEq, Debug)]
+pub enum ProgramError {
+    MissingFile(String),
+    CyclicImport(Vec<String>), // the files on the cycle, first one repeated
+    DuplicateName(String, String), // file and name
+    Desugar(String, String, DesugarError),
+    Unsupported(String, String), // a reference to a global, in a language without them
+}
+
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProgramError::MissingFile(file) => write!(f, "no file named {}", file),
+            ProgramError::CyclicImport(files) => write!(f, "cyclic import {}", files.join(" -> ")),
+            ProgramError::DuplicateName(file, x) => write!(f, "{}: {} is defined twice", file, x),
+            ProgramError::Desugar(file, x, err) => write!(f, "{}: in {}: {}", file, x, err),
+            ProgramError::Unsupported(file, x) => {
+                write!(f, "{}: in {}: the language has no globals", file, x)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProgramError {}
+
+struct Linker<'a, A> {
+    files: &'a HashMap<String, Module>,
+    path: Vec<String>,                // files being linked, importers first
+    linked: HashMap<String, Globals>, // definitions of each linked file
+    program: Program<A>,
+}
+
+fn define(scope: &mut Globals, file: &str, x: &str, idx: usize) -> Result<(), ProgramError> {
+    match scope.insert(String::from(x), idx) {
+        Some(other) if other != idx => Err(ProgramError::DuplicateName(
+            String::from(file),
+            String::from(x),
+        )),
+        _ => Ok(()),
+    }
+}
+
+impl<'a, A: Build> Linker<'a, A> {
+    fn link(&mut self, file: &str) -> Result<(), ProgramError> {
+        if self.linked.contains_key(file) {
+            return Ok(());
+        }
+        if let Some(start) = self.path.iter().position(|f| f == file) {
+            let mut cycle = self.path[start..].to_vec();
+            cycle.push(String::from(file));
+            return Err(ProgramError::CyclicImport(cycle));
+        }
+        let module = self
+            .files
+            .get(file)
+            .ok_or_else(|| ProgramError::MissingFile(String::from(file)))?;
+
+        self.path.push(String::from(file));
+        for import in &module.imports {
+            self.link(import)?;
+        }
+        self.path.pop();
+
+        // number the definitions first, so that they can refer to each other
+        let base = self.program.defs.len();
+        let mut own = Globals::new();
+        for (i, (x, _)) in module.defs.iter().enumerate() {
+            if own.insert(x.clone(), base + i).is_some() {
+                return Err(ProgramError::DuplicateName(String::from(file), x.clone()));
+            }
+        }
+        let mut scope = own.clone();
+        for import in &module.imports {
+            for (x, idx) in &self.linked[import] {
+                define(&mut scope, file, x, *idx)?;
+            }
+        }
+
+        for (x, body) in &module.defs {
+            let def = desugar_with(&scope, body).map_err(|err| match err {
+                DesugarError::NoGlobals => ProgramError::Unsupported(String::from(file), x.clone()),
+                err => ProgramError::Desugar(String::from(file), x.clone(), err),
+            })?;
+            self.program.names.push((String::from(file), x.clone()));
+            self.program.defs.push(def);
+        }
+        self.linked.insert(String::from(file), own);
+        Ok(())
+    }
+}
+
+// Link `root` and everything it imports, looking files up by name.
+pub fn link<A: Build>(
+    files: &HashMap<String, Module>,
+    root: &str,
+) -> Result<Program<A>, ProgramError> {
+    let mut linker = Linker {
+        files,
+        path: Vec::new(),
+        linked: HashMap::new(),
+        program: Program {
+            names: Vec::new(),
+            defs: Vec::new(),
+        },
+    };
+    linker.link(root)?;
+    Ok(linker.program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stlc::eval_defunct1::ast::{self, Ast};
+    use crate::surface::data::expr::*;
+
+    fn files(modules: Vec<(&str, Module)>) -> HashMap<String, Module> {
+        modules
+            .into_iter()
+            .map(|(name, m)| (String::from(name), m))
+            .collect()
+    }
+
+    // main imports lib directly and through util
+    fn example() -> HashMap<String, Module> {
+        files(vec![
+            (
+                "lib",
+                module(
+                    &[],
+                    vec![
+                        ("id", lam(&["x"], var("x"))),
+                        (
+                            "twice",
+                            lam(
+                                &["f", "x"],
+                                app(var("f"), vec![app(var("f"), vec![var("x")])]),
+                            ),
+                        ),
+                    ],
+                ),
+            ),
+            (
+                "util",
+                module(
+                    &["lib"],
+                    vec![("const", lam(&["x", "y"], app(var("id"), vec![var("x")])))],
+                ),
+            ),
+            (
+                "main",
+                module(
+                    &["lib", "util"],
+                    vec![
+                        // ping refers to a later definition
+                        ("ping", lam(&["x"], app(var("pong"), vec![var("x")]))),
+                        (
+                            "pong",
+                            lam(&["x"], app(var("const"), vec![var("x"), int(0)])),
+                        ),
+                        ("main", app(var("twice"), vec![var("ping"), int(42)])),
+                    ],
+                ),
+            ),
+        ])
+    }
+
+    #[test]
+    fn link_order() {
+        let program: Program<Ast> = link(&example(), "main").unwrap();
+        let names: Vec<(&str, &str)> = program
+            .names
+            .iter()
+            .map(|(f, x)| (f.as_str(), x.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("lib", "id"),
+                ("lib", "twice"),
+                ("util", "const"),
+                ("main", "ping"),
+                ("main", "pong"),
+                ("main", "main"),
+            ]
+        );
+        assert_eq!(program.index("main", "pong"), Some(4));
+        assert_eq!(program.index("util", "id"), None);
+    }
+
+    #[test]
+    fn link_and_run() {
+        use crate::lamcirc::{eval_cek, eval_cps, eval_vm, eval_vm2, eval_vm_gc};
+        use crate::stlc::eval_cps as stlc_cps;
+        use crate::stlc::eval_defunct1::{env, eval, val};
+        use crate::stlc::eval_defunct2 as d2;
+
+        let program = link(&example(), "main").unwrap();
+        let main = program.index("main", "main").unwrap();
+        for strategy in [
+            eval::Strategy::CallByValue,
+            eval::Strategy::CallByName,
+            eval::Strategy::CallByNeed,
+        ] {
+            let mut m = eval::Machine::new(strategy);
+            eval::define_globals(&mut m, &program.defs).unwrap();
+            assert_eq!(
+                eval::eval_with(&mut m, ast::global(main), env::empty(), eval::Cont::Cont0),
+                val::int(42)
+            );
+        }
+
+        let program = link(&example(), "main").unwrap();
+        let globals = stlc_cps::eval::define_globals(&program.defs).unwrap();
+        assert_eq!(globals[main], stlc_cps::val::int(42));
+
+        let program = link(&example(), "main").unwrap();
+        let globals = d2::eval::define_globals(&program.defs).unwrap();
+        assert_eq!(globals[main], d2::data::val::int(42));
+        let (_, globals) = d2::bytecode::define_globals(&program.defs).unwrap();
+        assert_eq!(globals[main], d2::bytecode::val::int(42));
+
+        let program = link(&example(), "main").unwrap();
+        let globals = eval_cek::eval::define_globals(&program.defs).unwrap();
+        assert_eq!(globals[main], eval_cek::data::val::int(42));
+
+        let program = link(&example(), "main").unwrap();
+        let globals = eval_cps::eval::define_globals(&program.defs).unwrap();
+        assert_eq!(globals[main], eval_cps::data::val::int(42));
+
+        let program = link(&example(), "main").unwrap();
+        let globals = eval_vm::eval::define_globals(&program.defs).unwrap();
+        assert_eq!(globals[main], eval_vm::data::val::int(42));
+        let mut m = eval_vm_gc::eval::Machine::new();
+        eval_vm_gc::eval::define_globals(&mut m, &program.defs).unwrap();
+        assert_eq!(*m.heap.val(m.globals[main]), eval_vm_gc::data::Val::Int(42));
+
+        let program = link(&example(), "main").unwrap();
+        let globals = eval_vm2::eval::define_globals(&program.defs).unwrap();
+        assert_eq!(globals[main], eval_vm2::data::val::int(42));
+    }
+
+    #[test]
+    fn define_forward_references() {
+        use crate::lamcirc::{eval_cek, eval_cps, eval_vm, eval_vm2, eval_vm_gc};
+        use crate::stlc::eval_cps as stlc_cps;
+        use crate::stlc::eval_defunct1::{env, eval, val};
+        use crate::stlc::eval_defunct2 as d2;
+
+        let run = |defs: Vec<(&str, Rc<Expr>)>, strategy| {
+            let program = link(&files(vec![("main", module(&[], defs))]), "main").unwrap();
+            let mut m = eval::Machine::new(strategy);
+            eval::define_globals(&mut m, &program.defs)?;
+            let x = ast::global(program.index("main", "x").unwrap());
+            Ok(eval::eval_with(&mut m, x, env::empty(), eval::Cont::Cont0))
+        };
+        // x needs y, directly or through f, before y is defined
+        let direct = || vec![("x", var("y")), ("y", int(1))];
+        let indirect = || {
+            vec![
+                ("f", lam(&["z"], var("y"))),
+                ("x", app(var("f"), vec![int(0)])),
+                ("y", int(1)),
+            ]
+        };
+        assert_eq!(run(direct(), eval::Strategy::CallByValue), Err(0));
+        assert_eq!(run(indirect(), eval::Strategy::CallByValue), Err(1));
+        // delayed definitions are only evaluated once all are defined
+        assert_eq!(run(direct(), eval::Strategy::CallByName), Ok(val::int(1)));
+        assert_eq!(run(indirect(), eval::Strategy::CallByNeed), Ok(val::int(1)));
+
+        for (defs, x) in [(direct(), 0), (indirect(), 1)] {
+            let main = files(vec![("main", module(&[], defs))]);
+            let program = link(&main, "main").unwrap();
+            assert_eq!(stlc_cps::eval::define_globals(&program.defs), Err(x));
+            let program = link(&main, "main").unwrap();
+            assert_eq!(d2::eval::define_globals(&program.defs), Err(x));
+            assert_eq!(d2::bytecode::define_globals(&program.defs), Err(x));
+            let program = link(&main, "main").unwrap();
+            assert_eq!(eval_cek::eval::define_globals(&program.defs), Err(x));
+            let program = link(&main, "main").unwrap();
+            assert_eq!(eval_cps::eval::define_globals(&program.defs), Err(x));
+            let program = link(&main, "main").unwrap();
+            assert_eq!(eval_vm::eval::define_globals(&program.defs), Err(x));
+            let mut m = eval_vm_gc::eval::Machine::new();
+            assert_eq!(
+                eval_vm_gc::eval::define_globals(&mut m, &program.defs),
+                Err(x)
+            );
+            let program = link(&main, "main").unwrap();
+            assert_eq!(eval_vm2::eval::define_globals(&program.defs), Err(x));
+        }
+    }
+
+    #[test]
+    fn link_locals_shadow_globals() {
+        let program: Program<Ast> = link(
+            &files(vec![(
+                "main",
+                module(
+                    &[],
+                    vec![("x", int(1)), ("f", lam(&["x"], var("x"))), ("g", var("x"))],
+                ),
+            )]),
+            "main",
+        )
+        .unwrap();
+        assert_eq!(program.defs[1], ast::lam(ast::var(0)));
+        assert_eq!(program.defs[2], ast::global(0));
+        // a binder of another stage still hides the global
+        let program: Result<Program<Ast>, _> = link(
+            &files(vec![(
+                "main",
+                module(&[], vec![("x", int(1)), ("f", lam(&["x"], quo(var("x"))))]),
+            )]),
+            "main",
+        );
+        assert_eq!(
+            program.unwrap_err(),
+            ProgramError::Desugar(
+                String::from("main"),
+                String::from("f"),
+                DesugarError::Unbound(String::from("x"))
+            )
+        );
+    }
+
+    #[test]
+    fn link_errors() {
+        use crate::stlc::eval_secd::data::Ast as SecdAst;
+
+        let cyclic = files(vec![
+            ("a", module(&["b"], vec![])),
+            ("b", module(&["c"], vec![])),
+            ("c", module(&["a"], vec![])),
+        ]);
+        assert_eq!(
+            link::<Ast>(&cyclic, "a").unwrap_err(),
+            ProgramError::CyclicImport(vec![
+                String::from("a"),
+                String::from("b"),
+                String::from("c"),
+                String::from("a")
+            ])
+        );
+
+        // importing a file twice is fine, even through different paths
+        let diamond = files(vec![
+            ("a", module(&["b", "c"], vec![])),
+            ("b", module(&["d"], vec![])),
+            ("c", module(&["d"], vec![])),
+            ("d", module(&[], vec![("x", int(1))])),
+        ]);
+        assert_eq!(link::<Ast>(&diamond, "a").unwrap().defs.len(), 1);
+
+        assert_eq!(
+            link::<Ast>(&cyclic, "z").unwrap_err(),
+            ProgramError::MissingFile(String::from("z"))
+        );
+
+        let twice = files(vec![("a", module(&[], vec![("x", int(1)), ("x", int(2))]))]);
+        assert_eq!(
+            link::<Ast>(&twice, "a").unwrap_err(),
+            ProgramError::DuplicateName(String::from("a"), String::from("x"))
+        );
+
+        // a definition may not clash with an imported one, nor may two imports
+        let clash = files(vec![
+            ("a", module(&["b"], vec![("x", int(1))])),
+            ("b", module(&[], vec![("x", int(2))])),
+        ]);
+        assert_eq!(
+            link::<Ast>(&clash, "a").unwrap_err(),
+            ProgramError::DuplicateName(String::from("a"), String::from("x"))
+        );
+        let clash = files(vec![
+            ("a", module(&["b", "c"], vec![])),
+            ("b", module(&[], vec![("x", int(1))])),
+            ("c", module(&[], vec![("x", int(2))])),
+        ]);
+        assert_eq!(
+            link::<Ast>(&clash, "a").unwrap_err(),
+            ProgramError::DuplicateName(String::from("a"), String::from("x"))
+        );
+
+        // definitions that refer to no other link into any language
+        assert_eq!(link::<SecdAst>(&diamond, "a").unwrap().defs.len(), 1);
+        assert_eq!(
+            link::<SecdAst>(&example(), "main").unwrap_err(),
+            ProgramError::Unsupported(String::from("util"), String::from("const"))
+        );
+    }
+}
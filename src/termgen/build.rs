@@ -0,0 +1,15 @@
+use crate::build::Build;
+use crate::termgen::data::Term;
+use std::rc::Rc;
+
+// Quotations are only generated for staged targets.
+pub fn build<A: Build>(t: &Term) -> Rc<A> {
+    match t {
+        Term::Int(i) => A::int(*i),
+        Term::Var(idx) => A::var(*idx),
+        Term::Lam(_, body) => A::lam(build(body)),
+        Term::App(func, arg) => A::app(build(func), build(arg)),
+        Term::Quo(body) => A::quo(build(body)).expect("quotation in an unstaged language"),
+        Term::Unq(body) => A::unq(build(body)).expect("unquotation in an unstaged language"),
+    }
+}
@@ -0,0 +1,77 @@
+use crate::termgen::data::*;
+use std::rc::Rc;
+
+// Binders in scope, innermost last, each tagged with its stage.
+pub type Ctx = Vec<(i32, Rc<Ty>)>;
+
+// The idx-th innermost binder of stage lev.
+pub fn lookup(ctx: &Ctx, lev: i32, idx: usize) -> Option<Rc<Ty>> {
+    ctx.iter()
+        .rev()
+        .filter(|(l, _)| *l == lev)
+        .nth(idx)
+        .map(|(_, ty)| ty.clone())
+}
+
+pub fn type_of(ctx: &mut Ctx, lev: i32, t: &Term) -> Option<Rc<Ty>> {
+    match t {
+        Term::Int(_) => Some(ty::int()),
+        Term::Var(idx) => lookup(ctx, lev, *idx),
+        Term::Lam(dom, body) => {
+            ctx.push((lev, dom.clone()));
+            let cod = type_of(ctx, lev, body);
+            ctx.pop();
+            cod.map(|cod| ty::arrow(dom.clone(), cod))
+        }
+        Term::App(func, arg) => {
+            let functy = type_of(ctx, lev, func)?;
+            let argty = type_of(ctx, lev, arg)?;
+            match &*functy {
+                Ty::Arrow(dom, cod) if *dom == argty => Some(cod.clone()),
+                _ => None,
+            }
+        }
+        Term::Quo(body) => type_of(ctx, lev + 1, body).map(ty::code),
+        Term::Unq(body) if lev > 0 => match &*type_of(ctx, lev - 1, body)? {
+            Ty::Code(ty) => Some(ty.clone()),
+            _ => None,
+        },
+        Term::Unq(_) => None,
+    }
+}
+
+// Closed and well-typed at the top level.
+pub fn well_typed(t: &Term) -> bool {
+    type_of(&mut Vec::new(), 0, t).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::termgen::data::term::*;
+
+    #[test]
+    fn check_simple() {
+        assert_eq!(type_of(&mut Vec::new(), 0, &int(1)), Some(ty::int()));
+        assert_eq!(
+            type_of(&mut Vec::new(), 0, &lam(ty::int(), var(0))),
+            Some(ty::arrow(ty::int(), ty::int()))
+        );
+        assert!(well_typed(&app(lam(ty::int(), var(0)), int(3))));
+        assert!(!well_typed(&var(0)));
+        assert!(!well_typed(&app(int(1), int(2))));
+    }
+
+    #[test]
+    fn check_staged() {
+        assert_eq!(
+            type_of(&mut Vec::new(), 0, &quo(int(1))),
+            Some(ty::code(ty::int()))
+        );
+        assert!(well_typed(&quo(unq(quo(int(1))))));
+        assert!(!well_typed(&unq(quo(int(1)))));
+        // a stage-0 binder is not visible at stage 1
+        assert!(!well_typed(&lam(ty::int(), quo(var(0)))));
+        assert!(well_typed(&lam(ty::code(ty::int()), quo(unq(var(0))))));
+    }
+}
@@ -0,0 +1,75 @@
+use std::rc::Rc;
+
+// Types are only used to keep generated terms terminating: every closed
+// well-typed term evaluates to a value in all of our evaluators.
+#[derive(Eq, PartialEq, Debug)]
+pub enum Ty {
+    Int,
+    Arrow(Rc<Ty>, Rc<Ty>),
+    Code(Rc<Ty>),
+}
+
+// Evaluator-independent term. Lambdas carry the type of their parameter so
+// that shrunk candidates can be re-checked. Variables are de Bruijn indices
+// counting only the binders of the same stage (as in lamcirc).
+#[derive(Eq, PartialEq, Debug)]
+pub enum Term {
+    Int(i32),
+    Var(usize),
+    Lam(Rc<Ty>, Rc<Term>),
+    App(Rc<Term>, Rc<Term>),
+    Quo(Rc<Term>),
+    Unq(Rc<Term>),
+}
+
+pub mod ty {
+    use super::*;
+
+    pub fn int() -> Rc<Ty> {
+        Rc::new(Ty::Int)
+    }
+
+    pub fn arrow(dom: Rc<Ty>, cod: Rc<Ty>) -> Rc<Ty> {
+        Rc::new(Ty::Arrow(dom, cod))
+    }
+
+    pub fn code(ty: Rc<Ty>) -> Rc<Ty> {
+        Rc::new(Ty::Code(ty))
+    }
+}
+
+pub mod term {
+    use super::*;
+
+    pub fn int(i: i32) -> Rc<Term> {
+        Rc::new(Term::Int(i))
+    }
+
+    pub fn var(i: usize) -> Rc<Term> {
+        Rc::new(Term::Var(i))
+    }
+
+    pub fn lam(ty: Rc<Ty>, body: Rc<Term>) -> Rc<Term> {
+        Rc::new(Term::Lam(ty, body))
+    }
+
+    pub fn app(func: Rc<Term>, arg: Rc<Term>) -> Rc<Term> {
+        Rc::new(Term::App(func, arg))
+    }
+
+    pub fn quo(body: Rc<Term>) -> Rc<Term> {
+        Rc::new(Term::Quo(body))
+    }
+
+    pub fn unq(body: Rc<Term>) -> Rc<Term> {
+        Rc::new(Term::Unq(body))
+    }
+
+    pub fn size(t: &Term) -> usize {
+        match t {
+            Term::Int(_) | Term::Var(_) => 1,
+            Term::Lam(_, body) | Term::Quo(body) | Term::Unq(body) => 1 + size(body),
+            Term::App(func, arg) => 1 + size(func) + size(arg),
+        }
+    }
+}
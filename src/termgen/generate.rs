@@ -0,0 +1,135 @@
+use crate::termgen::check::Ctx;
+use crate::termgen::data::*;
+use crate::termgen::rng::Rng;
+use std::rc::Rc;
+
+// Type-directed generator of closed terms. Terms are well-scoped by
+// construction, and since they are also well-typed they never diverge,
+// so they are safe to feed to every evaluator. With `staged` set, terms
+// may contain `Quo` and `Unq`, and `Unq` only ever appears under a `Quo`.
+pub struct Gen {
+    rng: Rng,
+    pub max_depth: usize,
+    pub staged: bool,
+}
+
+impl Gen {
+    pub fn new(seed: u64, max_depth: usize, staged: bool) -> Gen {
+        Gen {
+            rng: Rng::new(seed),
+            max_depth,
+            staged,
+        }
+    }
+
+    pub fn ty(&mut self, depth: usize) -> Rc<Ty> {
+        if depth == 0 || self.rng.chance(1, 2) {
+            return ty::int();
+        }
+        if self.staged && self.rng.chance(1, 3) {
+            ty::code(self.ty(depth - 1))
+        } else {
+            ty::arrow(self.ty(depth - 1), self.ty(depth - 1))
+        }
+    }
+
+    // A closed term of a random type.
+    pub fn closed(&mut self) -> Rc<Term> {
+        let ty = self.ty(2);
+        self.term(&ty)
+    }
+
+    // A closed term of the given type.
+    pub fn term(&mut self, ty: &Rc<Ty>) -> Rc<Term> {
+        let depth = self.max_depth;
+        self.gen(&mut Vec::new(), 0, ty, depth)
+    }
+
+    fn gen(&mut self, ctx: &mut Ctx, lev: i32, ty: &Rc<Ty>, depth: usize) -> Rc<Term> {
+        let vars = candidates(ctx, lev, ty);
+        if !vars.is_empty() && self.rng.chance(1, 3) {
+            return term::var(vars[self.rng.below(vars.len())]);
+        }
+        if depth == 0 {
+            return self.intro(ctx, lev, ty, 0);
+        }
+        match self.rng.below(4) {
+            0 | 1 => {
+                let dom = self.ty(1);
+                let func = self.gen(ctx, lev, &ty::arrow(dom.clone(), ty.clone()), depth - 1);
+                let arg = self.gen(ctx, lev, &dom, depth - 1);
+                term::app(func, arg)
+            }
+            2 if self.staged && lev > 0 => {
+                term::unq(self.gen(ctx, lev - 1, &ty::code(ty.clone()), depth - 1))
+            }
+            _ => self.intro(ctx, lev, ty, depth - 1),
+        }
+    }
+
+    // The introduction form of a type, always possible without variables.
+    fn intro(&mut self, ctx: &mut Ctx, lev: i32, ty: &Rc<Ty>, depth: usize) -> Rc<Term> {
+        match &**ty {
+            Ty::Int => term::int(self.rng.int(-10, 10)),
+            Ty::Arrow(dom, cod) => {
+                ctx.push((lev, dom.clone()));
+                let body = self.gen(ctx, lev, cod, depth);
+                ctx.pop();
+                term::lam(dom.clone(), body)
+            }
+            Ty::Code(ty) => term::quo(self.gen(ctx, lev + 1, ty, depth)),
+        }
+    }
+}
+
+// Indices of the stage-lev binders of the given type.
+fn candidates(ctx: &Ctx, lev: i32, ty: &Rc<Ty>) -> Vec<usize> {
+    ctx.iter()
+        .rev()
+        .filter(|(l, _)| *l == lev)
+        .enumerate()
+        .filter(|(_, (_, t))| t == ty)
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::termgen::check::well_typed;
+
+    #[test]
+    fn generated_terms_are_well_typed() {
+        for seed in 0..500 {
+            let mut g = Gen::new(seed, 5, false);
+            assert!(well_typed(&g.closed()));
+            let mut g = Gen::new(seed, 5, true);
+            assert!(well_typed(&g.closed()));
+        }
+    }
+
+    #[test]
+    fn generation_is_deterministic() {
+        for seed in 0..50 {
+            assert_eq!(
+                Gen::new(seed, 5, true).closed(),
+                Gen::new(seed, 5, true).closed()
+            );
+        }
+    }
+
+    #[test]
+    fn unstaged_terms_have_no_quotes() {
+        fn staged(t: &Term) -> bool {
+            match t {
+                Term::Int(_) | Term::Var(_) => false,
+                Term::Lam(_, body) => staged(body),
+                Term::App(func, arg) => staged(func) || staged(arg),
+                Term::Quo(_) | Term::Unq(_) => true,
+            }
+        }
+        for seed in 0..200 {
+            assert!(!staged(&Gen::new(seed, 5, false).closed()));
+        }
+    }
+}
@@ -0,0 +1,6 @@
+pub mod rng;
+pub mod data;
+pub mod check;
+pub mod generate;
+pub mod shrink;
+pub mod build;
@@ -0,0 +1,146 @@
+use crate::termgen::check::well_typed;
+use crate::termgen::data::*;
+use crate::termgen::generate::Gen;
+use std::rc::Rc;
+
+// Runs `agree` on `count` generated closed terms and reports the smallest
+// counterexample it can find.
+pub fn check_agreement(staged: bool, count: u64, agree: impl Fn(&Rc<Term>) -> bool) {
+    for seed in 0..count {
+        let t = Gen::new(seed, 6, staged).closed();
+        if !agree(&t) {
+            let small = shrink(t, |t| !agree(t));
+            panic!("seed {}: evaluators disagree on {:?}", seed, small);
+        }
+    }
+}
+
+// Greedily minimize a failing term: repeatedly replace it with the first
+// smaller well-typed candidate that still fails, until none does.
+pub fn shrink(t: Rc<Term>, mut fails: impl FnMut(&Rc<Term>) -> bool) -> Rc<Term> {
+    let mut current = t;
+    'outer: loop {
+        for cand in candidates(&current) {
+            if well_typed(&cand) && fails(&cand) {
+                current = cand;
+                continue 'outer;
+            }
+        }
+        return current;
+    }
+}
+
+// One-step shrinks of a term, biggest simplifications first.
+pub fn candidates(t: &Rc<Term>) -> Vec<Rc<Term>> {
+    let mut cands = Vec::new();
+    match &**t {
+        Term::Int(0) => {}
+        Term::Int(i) => {
+            cands.push(term::int(0));
+            cands.push(term::int(i / 2));
+        }
+        _ => cands.push(term::int(0)),
+    }
+    match &**t {
+        Term::Int(_) | Term::Var(_) => {}
+        Term::Lam(ty, body) => {
+            if let Some(b) = drop_binder(body, 0, 0) {
+                cands.push(b);
+            }
+            for b in candidates(body) {
+                cands.push(term::lam(ty.clone(), b));
+            }
+        }
+        Term::App(func, arg) => {
+            cands.push(func.clone());
+            cands.push(arg.clone());
+            for f in candidates(func) {
+                cands.push(term::app(f, arg.clone()));
+            }
+            for a in candidates(arg) {
+                cands.push(term::app(func.clone(), a));
+            }
+        }
+        Term::Quo(body) => {
+            if let Term::Unq(inner) = &**body {
+                cands.push(inner.clone());
+            }
+            for b in candidates(body) {
+                cands.push(term::quo(b));
+            }
+        }
+        Term::Unq(body) => {
+            if let Term::Quo(inner) = &**body {
+                cands.push(inner.clone());
+            }
+            for b in candidates(body) {
+                cands.push(term::unq(b));
+            }
+        }
+    }
+    cands
+}
+
+// The body of a lambda with its parameter removed, if it is unused. `lev`
+// is the stage relative to the binder, `cutoff` the index of the binder.
+fn drop_binder(t: &Rc<Term>, lev: i32, cutoff: usize) -> Option<Rc<Term>> {
+    match &**t {
+        Term::Int(_) => Some(t.clone()),
+        Term::Var(idx) if lev == 0 && *idx == cutoff => None,
+        Term::Var(idx) if lev == 0 && *idx > cutoff => Some(term::var(idx - 1)),
+        Term::Var(_) => Some(t.clone()),
+        Term::Lam(ty, body) => {
+            let cutoff = if lev == 0 { cutoff + 1 } else { cutoff };
+            drop_binder(body, lev, cutoff).map(|b| term::lam(ty.clone(), b))
+        }
+        Term::App(func, arg) => Some(term::app(
+            drop_binder(func, lev, cutoff)?,
+            drop_binder(arg, lev, cutoff)?,
+        )),
+        Term::Quo(body) => drop_binder(body, lev + 1, cutoff).map(term::quo),
+        Term::Unq(body) => drop_binder(body, lev - 1, cutoff).map(term::unq),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::termgen::data::term::*;
+    use crate::termgen::generate::Gen;
+
+    fn has_app(t: &Term) -> bool {
+        match t {
+            Term::Int(_) | Term::Var(_) => false,
+            Term::Lam(_, body) | Term::Quo(body) | Term::Unq(body) => has_app(body),
+            Term::App(_, _) => true,
+        }
+    }
+
+    #[test]
+    fn shrink_literal() {
+        assert_eq!(shrink(int(100), |t| matches!(**t, Term::Int(i) if i >= 7)), int(12));
+    }
+
+    #[test]
+    fn shrink_drops_unused_binder() {
+        let t = lam(ty::int(), lam(ty::int(), app(lam(ty::int(), var(0)), var(1))));
+        let small = shrink(t, |t| has_app(t));
+        assert_eq!(small, app(lam(ty::int(), int(0)), int(0)));
+    }
+
+    #[test]
+    fn shrink_keeps_failure() {
+        for seed in 0..100 {
+            let t = Gen::new(seed, 6, true).closed();
+            if !has_app(&t) {
+                continue;
+            }
+            let small = shrink(t.clone(), |t| has_app(t));
+            assert!(has_app(&small));
+            assert!(well_typed(&small));
+            assert!(size(&small) <= size(&t));
+            // a lambda and an argument, possibly under a quote
+            assert!(size(&small) <= 6, "{:?}", small);
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use compiler_learning::termgen::build::build;
+use compiler_learning::termgen::data::{ty, Term};
+use compiler_learning::termgen::generate::Gen;
+use compiler_learning::termgen::shrink::check_agreement;
+
+// Observable part of a result: closures only show that they are closures,
+// code fragments are compared through their (identical) Debug output.
+fn run_cek(t: &Term) -> String {
+    use compiler_learning::lamcirc::eval_cek::data::{env, Val};
+    use compiler_learning::lamcirc::eval_cek::eval::{eval, Cont};
+
+    match &*eval(0, build(t), env::empty(), Cont::End) {
+        Val::Error(msg) => format!("error: {}", msg),
+        Val::Int(i) => format!("{}", i),
+        Val::Clos(_, _) => String::from("<closure>"),
+        Val::Quo(code) => format!("quo {:?}", code),
+        Val::Fut(code) => format!("fut {:?}", code),
+    }
+}
+
+fn run_vm(t: &Term) -> String {
+    use compiler_learning::lamcirc::eval_vm::data::{env, Cont, Val};
+    use compiler_learning::lamcirc::eval_vm::eval::{compile, run};
+
+    match &*run(compile(build(t)), 0, env::empty(), Box::new(Cont::End)) {
+        Val::Error(msg) => format!("error: {}", msg),
+        Val::Int(i) => format!("{}", i),
+        Val::Clos(_, _) => String::from("<closure>"),
+        Val::Quo(code) => format!("quo {:?}", code),
+        Val::Fut(code) => format!("fut {:?}", code),
+    }
+}
+
+// With a tiny heap threshold, so that collections happen all the time.
+fn run_vm_gc(t: &Term) -> String {
+    use compiler_learning::lamcirc::eval_vm_gc::data::{Cont, Val};
+    use compiler_learning::lamcirc::eval_vm_gc::eval::{compile, run, Machine};
+
+    let mut m = Machine::with_threshold(8);
+    let env = m.heap.nil();
+    let v = run(&mut m, compile(build(t)), 0, env, Box::new(Cont::End));
+    match m.heap.val(v) {
+        Val::Error(msg) => format!("error: {}", msg),
+        Val::Int(i) => format!("{}", i),
+        Val::Clos(_, _) => String::from("<closure>"),
+        Val::Quo(code) => format!("quo {:?}", code),
+        Val::Fut(code) => format!("fut {:?}", code),
+    }
+}
+
+fn run_stlc_cps(t: &Term) -> String {
+    use compiler_learning::stlc::eval_cps::env::empty;
+    use compiler_learning::stlc::eval_cps::eval::run;
+    use compiler_learning::stlc::eval_cps::val::Val;
+
+    match &*run(build(t), empty()) {
+        Val::Error => String::from("error"),
+        Val::Int(i) => format!("{}", i),
+        Val::Clos(_, _) => String::from("<closure>"),
+        other => format!("{:?}", other),
+    }
+}
+
+fn run_stlc_defunct1(t: &Term) -> String {
+    use compiler_learning::stlc::eval_defunct1::env::empty;
+    use compiler_learning::stlc::eval_defunct1::eval::{eval, Cont};
+
+    show_defunct1(&eval(build(t), empty(), Cont::Cont0))
+}
+
+fn show_defunct1(v: &compiler_learning::stlc::eval_defunct1::val::Val) -> String {
+    use compiler_learning::stlc::eval_defunct1::val::Val;
+
+    match v {
+        Val::Error => String::from("error"),
+        Val::Int(i) => format!("{}", i),
+        Val::Clos(_, _) => String::from("<closure>"),
+        Val::Thunk(_) => String::from("<thunk>"),
+        other => format!("{:?}", other),
+    }
+}
+
+fn run_stlc_defunct2(t: &Term) -> String {
+    use compiler_learning::stlc::eval_defunct2::data::{env, Cont, Val};
+    use compiler_learning::stlc::eval_defunct2::eval::{apply_compt, eval};
+
+    match &*apply_compt(eval(build(t)), env::empty(), Cont::Cont0) {
+        Val::Error => String::from("error"),
+        Val::Int(i) => format!("{}", i),
+        Val::Clos(_, _) => String::from("<closure>"),
+        other => format!("{:?}", other),
+    }
+}
+
+#[test]
+fn lamcirc_cek_agrees_with_vm() {
+    check_agreement(true, 5000, |t| run_cek(t) == run_vm(t));
+}
+
+#[test]
+fn lamcirc_vm_gc_agrees_with_vm() {
+    check_agreement(true, 3000, |t| run_vm_gc(t) == run_vm(t));
+}
+
+#[test]
+fn stlc_evaluators_agree() {
+    check_agreement(false, 3000, |t| {
+        let expected = run_stlc_cps(t);
+        run_stlc_defunct1(t) == expected && run_stlc_defunct2(t) == expected
+    });
+}
+
+#[test]
+fn stlc_strategies_agree_on_integers() {
+    use compiler_learning::stlc::eval_defunct1::env::empty;
+    use compiler_learning::stlc::eval_defunct1::eval::{eval_with, Cont, Machine, Strategy};
+
+    for seed in 0..1000 {
+        let t = Gen::new(seed, 6, false).term(&ty::int());
+        let expected = run_stlc_defunct1(&t);
+        for strategy in [Strategy::CallByName, Strategy::CallByNeed] {
+            let mut m = Machine::new(strategy);
+            let v = eval_with(&mut m, build(&t), empty(), Cont::Cont0);
+            assert_eq!(show_defunct1(&v), expected, "seed {}: {:?}", seed, t);
+        }
+    }
+}
+
+#[test]
+fn stlc_krivine_agrees_on_integers() {
+    use compiler_learning::stlc::eval_defunct1::env::empty;
+    use compiler_learning::stlc::eval_krivine::data::{Val, ZVal};
+    use compiler_learning::stlc::eval_krivine::{eval::eval, zam};
+
+    for seed in 0..1000 {
+        let t = Gen::new(seed, 6, false).term(&ty::int());
+        let expected = run_stlc_defunct1(&t);
+        match &*eval(build(&t), empty()) {
+            Val::Int(i) => assert_eq!(format!("{}", i), expected, "seed {}: {:?}", seed, t),
+            other => panic!("seed {}: {:?} evaluated to {:?}", seed, t, other),
+        }
+        match &*zam::run(zam::compile(&build(&t)), empty()) {
+            ZVal::Int(i) => assert_eq!(format!("{}", i), expected, "seed {}: {:?}", seed, t),
+            other => panic!("seed {}: {:?} evaluated to {:?}", seed, t, other),
+        }
+    }
+}
+
+#[test]
+fn stlc_nbe_agrees_on_integers() {
+    use compiler_learning::stlc::nbe::data::Ast;
+    use compiler_learning::stlc::nbe::eval::normalize;
+
+    for seed in 0..1000 {
+        let t = Gen::new(seed, 6, false).term(&ty::int());
+        match normalize(build(&t)).as_deref() {
+            Some(Ast::Int(i)) => assert_eq!(format!("{}", i), run_stlc_defunct1(&t)),
+            other => panic!("seed {}: {:?} normalized to {:?}", seed, t, other),
+        }
+    }
+}
+
+#[test]
+fn stlc_small_step_agrees_on_integers() {
+    use compiler_learning::stlc::eval_subst::data::Ast;
+    use compiler_learning::stlc::eval_subst::eval::eval;
+
+    for seed in 0..1000 {
+        let t = Gen::new(seed, 6, false).term(&ty::int());
+        match &*eval(build(&t)) {
+            Ast::Int(i) => assert_eq!(format!("{}", i), run_stlc_defunct1(&t)),
+            other => panic!("seed {}: {:?} reduced to {:?}", seed, t, other),
+        }
+    }
+}